@@ -0,0 +1,101 @@
+use std::path::Path;
+
+use engine::base::Backend;
+use engine::headless::{Headless, HeadlessParams, Script};
+use r0t0blocks::blocks::State;
+use r0t0blocks::config::{Config, TICKS_PER_SECOND};
+use r0t0blocks::mode::{format_ticks, GameMode, ModeBehavior};
+use r0t0blocks::piece::PieceSet;
+
+// Games that never end, like Zen, are cut off after an hour of play.
+const MAX_GAME_SECONDS: u64 = 60 * 60;
+
+fn main() -> Result<(), String> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    let games = match option_value(&args, "--games") {
+        Some(value) => value.parse::<u64>().map_err(|e| format!("Invalid game count: {}", e))?,
+        None => 10,
+    };
+    if games == 0 {
+        return Err("Game count must be positive".into());
+    }
+
+    let seed = match option_value(&args, "--seed") {
+        Some(value) => value.parse::<u64>().map_err(|e| format!("Invalid seed: {}", e))?,
+        None => 1,
+    };
+
+    // online every game is a versus match against whoever the relay pairs
+    // the bot with
+    let connect_address = option_value(&args, "--connect");
+
+    let mode = match option_value(&args, "--mode") {
+        Some(name) => GameMode::ALL.iter().copied()
+            .find(|mode| mode.name().eq_ignore_ascii_case(name))
+            .ok_or_else(|| format!("Unknown mode: {}", name))?,
+        None => GameMode::ALL[0],
+    };
+    if mode.player_count() > 1 && connect_address.is_none() {
+        return Err(format!("The bot cannot play {}", mode.name()));
+    }
+
+    let pieces = match option_value(&args, "--pieces") {
+        Some(path) => PieceSet::load(Path::new(path))?,
+        None => PieceSet::standard(),
+    };
+
+    let mut total_lines = 0;
+    let mut wins = 0;
+    for game in 0..games {
+        // each game gets its own seed so runs can be compared piece for piece
+        let config = Config {
+            seed: Some(seed.wrapping_add(game)),
+            data_dir: None,
+            ..Config::default()
+        };
+        let (width_in_tiles, height_in_tiles) = State::screen_size(&config, &pieces);
+
+        let mut state = State::new(&pieces, config);
+        match connect_address {
+            Some(address) => state.autoplay_online(address)?,
+            None => state.autoplay(mode),
+        }
+
+        let params = HeadlessParams {
+            width_in_tiles,
+            height_in_tiles,
+            tick_count: MAX_GAME_SECONDS * TICKS_PER_SECOND as u64,
+            ticks_per_frame: u64::MAX,
+        };
+        Headless::new(params, Script::new()).run(&mut state)?;
+
+        if let Some(error) = state.network_error() {
+            return Err(error.to_string());
+        }
+
+        let stats = state.stats();
+        if connect_address.is_some() {
+            let result = if state.is_winner() { "won" } else { "lost" };
+            println!("Game {}: {}, {} lines, {} sent, {}", game + 1, result, stats.lines, stats.sent, format_ticks(stats.ticks));
+            wins += state.is_winner() as u64;
+        } else {
+            println!("Game {}: {} lines, score {}, level {}, {}", game + 1, stats.lines, stats.score, stats.level, format_ticks(stats.ticks));
+        }
+        total_lines += stats.lines;
+    }
+
+    if connect_address.is_some() {
+        println!("{} games online: {} won, {:.1} lines per game on average", games, wins, total_lines as f64 / games as f64);
+    } else {
+        println!("{} games of {}: {:.1} lines per game on average", games, mode.name(), total_lines as f64 / games as f64);
+    }
+    Ok(())
+}
+
+fn option_value<'a>(args: &'a [String], name: &str) -> Option<&'a str> {
+    args.iter()
+        .position(|arg| arg == name)
+        .and_then(|i| args.get(i + 1))
+        .map(|value| value.as_str())
+}
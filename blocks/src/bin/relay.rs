@@ -0,0 +1,45 @@
+use std::net::TcpListener;
+
+use r0t0blocks::relay::{accept_players, play_match, IDLE_TIMEOUT};
+
+const DEFAULT_ADDRESS: &str = "127.0.0.1:7777";
+
+// Pairs clients two by two and passes their ticks and attacks between them
+// until one of them loses. Matches are played one at a time.
+fn main() -> Result<(), String> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    let address = option_value(&args, "--listen").unwrap_or(DEFAULT_ADDRESS);
+
+    let seed = match option_value(&args, "--seed") {
+        Some(value) => Some(value.parse::<u64>().map_err(|e| format!("Invalid seed: {}", e))?),
+        None => None,
+    };
+
+    let max_matches = match option_value(&args, "--matches") {
+        Some(value) => Some(value.parse::<u64>().map_err(|e| format!("Invalid match count: {}", e))?),
+        None => None,
+    };
+
+    let listener = TcpListener::bind(address).map_err(|e| format!("Could not listen on {}: {}", address, e))?;
+    println!("Listening on {}", address);
+
+    let mut matches = 0;
+    while max_matches.is_none_or(|max_matches| matches < max_matches) {
+        let mut players = accept_players(&listener)?;
+        let seed = seed.unwrap_or_else(|| fastrand::u64(..));
+        matches += 1;
+
+        let winner = play_match(&mut players, seed, IDLE_TIMEOUT);
+        println!("Match {}: player {} wins", matches, winner + 1);
+    }
+
+    Ok(())
+}
+
+fn option_value<'a>(args: &'a [String], name: &str) -> Option<&'a str> {
+    args.iter()
+        .position(|arg| arg == name)
+        .and_then(|i| args.get(i + 1))
+        .map(|value| value.as_str())
+}
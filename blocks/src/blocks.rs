@@ -1,235 +1,577 @@
-use std::cmp::min;
+use std::cmp::max;
+use std::path::{Path, PathBuf};
 
 use enum_dispatch::enum_dispatch;
 use fastrand::Rng;
 
 use engine::base::{App, Number};
 use engine::geometry::Point;
-use engine::input::{Input, Key};
-use engine::time::{BlinkAnimation, DelayedRepeat, TimeAware, Timer};
-use engine::video::{draw_rect, draw_str, ScreenBuffer};
+use engine::input::{Actions, Input, Key};
+use engine::video::{draw_colored_str, draw_str, Color, ScreenBuffer};
+
+use crate::bot::{Bot, Turn, Weights};
+use crate::config::{Config, TICKS_PER_SECOND};
+use crate::controls::{Action, Controls};
+use crate::highscore::{now, HighScore, HighScores, MAX_NAME_LEN};
+use crate::mode::{format_ticks, GameMode, ModeBehavior, Outcome, Ranking, Stats, Versus};
+use crate::net::rules;
+use crate::online::Online;
+use crate::piece::PieceSet;
+use crate::player::Player;
+use crate::randomizer::RandomizerKind;
+use crate::save::SavedGame;
+use crate::storage::set_aside;
+
+const HIGH_SCORE_FILE: &str = "highscores.txt";
+const CONTROLS_FILE: &str = "controls.txt";
+const SAVE_FILE: &str = "save.txt";
+
+// The title screen starts a demo after sitting idle this long, and the
+// demo gives up after the given length.
+const DEMO_DELAY: Number = 10 * TICKS_PER_SECOND;
+const DEMO_LENGTH: Number = 60 * TICKS_PER_SECOND;
+
+const MAX_COMMAND_LEN: usize = 40;
+const CONSOLE_HELP: &str = "Commands: level N, garbage N, seed";
 
-use crate::field::Field;
-use crate::tetromino::{Frame, Tetromino};
+#[derive(Eq, PartialEq, Copy, Clone)]
+enum Autoplay {
+    Off,
+    /// Attract mode on the title screen, stopped by any key.
+    Demo,
+    /// Plays one game without recording scores, then asks to quit.
+    Benchmark,
+}
 
 pub struct State<'frame> {
     // external
-    tetrominos: [Tetromino<'frame>; 7],
+    pieces: &'frame PieceSet,
 
     // logic
-    curr_frame: usize,
-    curr_tet_index: usize,
-    next_tet_index: usize,
-    field: Field,
-    tet_pos: Point,
-    fall_timer: Timer,
-    filled_lines_animation: BlinkAnimation,
-    rng: Rng,
+    players: Vec<Player<'frame>>,
+    actions: Vec<Actions<Action>>,
+    mode: GameMode,
+    selected_mode: usize,
+    outcome: Option<Outcome>,
+    winner: Option<usize>,
+    high_scores: HighScores,
+    high_score_path: Option<PathBuf>,
+    controls: Controls,
+    controls_path: Option<PathBuf>,
+    selected_set: usize,
+    selected_action: usize,
+    // the controls screen is waiting for a key, which replaces the keys of
+    // the selected action unless it is added to them
+    rebinding: Option<bool>,
+    storage_error: Option<String>,
+    player_name: String,
+    new_rank: Option<usize>,
+    viewed_mode: usize,
+    console_line: String,
+    console_output: Option<Result<String, String>>,
+    // debug commands that change a game keep it off the high score table
+    is_game_modified: bool,
+    save_path: Option<PathBuf>,
+    has_saved_game: bool,
+    is_quit_requested: bool,
+    autoplay: Autoplay,
+    bot: Option<Bot>,
+    idle_ticks: Number,
+    online: Option<Online>,
+    network_error: Option<String>,
+    config: Config,
+    seed: u64,
+    seed_rng: Rng,
     screen: Screen,
     popup_screen: Option<Screen>,
-    left_repeater: DelayedRepeat,
-    right_repeater: DelayedRepeat,
-    down_repeater: DelayedRepeat,
-    score: Number,
 
     // visualisation
-    field_pos: Point,
+    screen_width: Number,
 }
 
 impl<'frame> State<'frame> {
-    fn spawn_pos() -> Point {
-        Point::new((Field::width() - Frame::width()) / 2, -2)
+    /// Screen size in tiles that fits two players' fields side by side.
+    pub fn screen_size(config: &Config, pieces: &PieceSet) -> (u32, u32) {
+        let (board_width, board_height) = Player::board_size(config, pieces);
+        (max(2 * board_width, 30) as u32, max(board_height, 24) as u32)
     }
 
-    pub fn new(frames: &'frame [Vec<Frame>; 7]) -> State {
-        let tetrominos = [
-            Tetromino::new(&frames[0]),
-            Tetromino::new(&frames[1]),
-            Tetromino::new(&frames[2]),
-            Tetromino::new(&frames[3]),
-            Tetromino::new(&frames[4]),
-            Tetromino::new(&frames[5]),
-            Tetromino::new(&frames[6]),
-        ];
+    pub fn new(pieces: &'frame PieceSet, config: Config) -> State<'frame> {
+        let mode = GameMode::ALL[0];
+        let seed_rng = Rng::new();
+        let seed = config.seed.unwrap_or_else(|| seed_rng.u64(..));
+
+        let high_score_path = config.data_dir.as_ref().map(|dir| dir.join(HIGH_SCORE_FILE));
+        let (high_scores, storage_error) = Self::load_high_scores(high_score_path.as_deref());
 
-        let field_pos = Point::new(3, 3);
+        let controls_path = config.data_dir.as_ref().map(|dir| dir.join(CONTROLS_FILE));
+        let (controls, controls_error) = Self::load_controls(controls_path.as_deref());
 
-        let score = 0;
-        let level = Self::level(score);
-        let fall_timer = Timer::new(Self::fall_period(level));
+        let save_path = config.data_dir.as_ref().map(|dir| dir.join(SAVE_FILE));
+        let has_saved_game = save_path.as_ref().is_some_and(|path| path.exists());
 
-        let rng = Rng::new();
+        let (screen_width, _) = Self::screen_size(&config, pieces);
 
-        let initial_screen = GameScreen.into();
+        let initial_screen = ModeSelectScreen.into();
 
         let mut state = State {
-            tetrominos,
-            curr_frame: 0,
-            curr_tet_index: 0,
-            next_tet_index: 0,
-            field: Field::new(),
-            field_pos,
-            tet_pos: Point::new(0, 0),
-            fall_timer,
-            filled_lines_animation: BlinkAnimation::new(),
-            rng,
+            pieces,
+            players: Vec::new(),
+            // one set for each player of the biggest game
+            actions: (0..GameMode::ALL.iter().map(|mode| mode.player_count()).max().unwrap_or(1)).map(|_| Actions::new()).collect(),
+            mode,
+            selected_mode: 0,
+            outcome: None,
+            winner: None,
+            high_scores,
+            high_score_path,
+            controls,
+            controls_path,
+            selected_set: 0,
+            selected_action: 0,
+            rebinding: None,
+            storage_error: storage_error.or(controls_error),
+            player_name: String::new(),
+            new_rank: None,
+            viewed_mode: 0,
+            console_line: String::new(),
+            console_output: None,
+            is_game_modified: false,
+            save_path,
+            has_saved_game,
+            is_quit_requested: false,
+            autoplay: Autoplay::Off,
+            bot: None,
+            idle_ticks: 0,
+            online: None,
+            network_error: None,
+            config,
+            seed,
+            seed_rng,
             screen: initial_screen,
             popup_screen: None,
-            left_repeater: DelayedRepeat::new(30, 5),
-            right_repeater: DelayedRepeat::new(30, 5),
-            down_repeater: DelayedRepeat::new(30, 3),
-            score,
+            screen_width: screen_width as Number,
         };
 
+        state.players = state.create_players();
         initial_screen.enter(&mut state);
 
         state
     }
 
-    fn current_frame(&self) -> &'frame Frame {
-        self.tetrominos[self.curr_tet_index].frames[self.curr_frame]
+    // Boards are laid out side by side, centred on the screen.
+    fn create_players(&self) -> Vec<Player<'frame>> {
+        let count = self.mode.player_count();
+        let (board_width, _) = Player::board_size(&self.config, self.pieces);
+        let left = (self.screen_width - board_width * count as Number) / 2;
+        (0..count)
+            .map(|i| Player::new(self.pieces, &self.config, self.mode, self.seed, i, Point::new(left + board_width * i as Number, 0)))
+            .collect()
     }
 
-    fn copy_frame(&mut self) {
-        let pos = self.tet_pos;
-        let curr_frame = self.current_frame();
-        self.field.copy_frame(curr_frame, pos);
+    // Actions are read on every screen, so a key held down when a game
+    // starts does not count as pressed in it. Online the player has the
+    // solo keys, and the bot stands in for the keyboard of its player.
+    fn update_actions(&mut self, input: &Input) {
+        let is_versus = self.mode.player_count() > 1 && self.online.is_none();
+        let local = self.local_player();
+        for (i, actions) in self.actions.iter_mut().enumerate() {
+            match &self.bot {
+                Some(bot) if i == local => actions.clone_from(bot.actions()),
+                _ => actions.update(input, self.controls.player(is_versus, i)),
+            }
+        }
     }
 
-    fn is_collide(&self, frame: &'frame Frame, p: Point) -> bool {
-        self.field.is_collide(frame, p)
+    fn is_action_front_edge(&self, action: Action) -> bool {
+        self.actions[..self.players.len()].iter().any(|actions| actions.is_front_edge(action))
     }
 
-    fn clean_filled_lines(&mut self) -> Number {
-        self.field.clean_filled_lines()
+    // The player at this machine: the one given by the relay online, the
+    // first one otherwise.
+    fn local_player(&self) -> usize {
+        self.online.as_ref().map_or(0, Online::player)
     }
 
-    fn move_colliding_tetromino(&mut self, new_pos: Point) {
-        if self.filled_lines_animation.is_started() {
+    // Ends the game once a player tops out or the mode says so. In versus
+    // the last player standing wins, and online the relay decides.
+    fn check_game_over(&mut self) {
+        if self.screen != GameScreen.into() {
             return;
         }
-        if self.is_collide(self.current_frame(), self.tet_pos) ||
-            !self.is_collide(self.current_frame(), new_pos) {
-            self.tet_pos = new_pos;
+
+        if let Some(online) = &self.online {
+            if let Some(winner) = online.winner() {
+                self.winner = winner;
+                self.finish_game(Outcome::ToppedOut);
+            }
+            return;
+        }
+
+        let standing: Vec<usize> = (0..self.players.len()).filter(|&i| !self.players[i].is_topped_out()).collect();
+        if standing.len() < self.players.len() {
+            self.winner = match standing[..] {
+                [winner] if self.players.len() > 1 => Some(winner),
+                _ => None,
+            };
+            self.finish_game(Outcome::ToppedOut);
+        } else if let Some(outcome) = self.mode.outcome(&self.stats()) {
+            self.finish_game(outcome);
         }
     }
 
-    fn next_frame(&self) -> usize {
-        (self.curr_frame + 1) % 4
+    // Lines sent by each player go to the next one.
+    fn exchange_garbage(&mut self) {
+        let count = self.players.len();
+        for i in 0..count {
+            let lines = self.players[i].take_outgoing();
+            if count > 1 {
+                self.players[(i + 1) % count].receive_garbage(lines);
+            }
+        }
     }
 
-    fn rotate_colliding_tetromino(&mut self) {
-        let new_frame_index = self.next_frame();
-        let new_frame = self.tetrominos[self.curr_tet_index].frames[new_frame_index];
-        if self.is_collide(self.current_frame(), self.tet_pos) ||
-            !self.is_collide(new_frame, self.tet_pos) {
-            self.curr_frame = new_frame_index;
+    fn finish_game(&mut self, outcome: Outcome) {
+        self.outcome = Some(outcome);
+        self.new_rank = None;
+
+        match self.autoplay {
+            Autoplay::Off => {}
+            Autoplay::Demo => {
+                self.stop_demo();
+                return;
+            }
+            Autoplay::Benchmark => {
+                self.change_screen(ResultsScreen.into());
+                self.is_quit_requested = true;
+                return;
+            }
+        }
+
+        let is_high_score = !self.is_game_modified && self.mode.ranking(outcome)
+            .is_some_and(|ranking| self.high_scores.rank(self.mode.name(), ranking, &self.high_score_entry()).is_some());
+        if is_high_score {
+            self.change_screen(NameEntryScreen.into());
+        } else {
+            self.change_screen(ResultsScreen.into());
         }
     }
 
-    fn finish_turn(&mut self) {
-        self.left_repeater.stop();
-        self.right_repeater.stop();
-        self.down_repeater.stop();
-        self.curr_tet_index = self.next_tet_index;
-        self.next_tet_index = self.rng.usize(0..7);
-        self.curr_frame = 0;
-        self.tet_pos = Self::spawn_pos();
+    fn high_score_entry(&self) -> HighScore {
+        let stats = self.stats();
+        HighScore {
+            name: self.player_name.clone(),
+            score: stats.score,
+            lines: stats.lines,
+            level: stats.level,
+            ticks: stats.ticks,
+            date: now(),
+        }
+    }
 
-        if self.is_collide(self.current_frame(), self.tet_pos) {
-            self.change_screen(RetryScreen.into());
+    fn submit_high_score(&mut self) {
+        let ranking = match self.outcome.and_then(|outcome| self.mode.ranking(outcome)) {
+            Some(ranking) => ranking,
+            None => return,
+        };
+
+        let entry = self.high_score_entry();
+        self.new_rank = self.high_scores.insert(self.mode.name(), ranking, entry);
+        if let Some(path) = &self.high_score_path {
+            if let Err(e) = self.high_scores.save(path) {
+                self.storage_error = Some(format!("Could not save scores: {}", e));
+            }
         }
     }
 
-    fn move_down(&mut self) {
-        if self.is_collide(self.current_frame(), self.tet_pos) {
-            return;
+    fn save_controls(&mut self) {
+        if let Some(path) = &self.controls_path {
+            if let Err(e) = self.controls.save(path) {
+                self.storage_error = Some(format!("Could not save controls: {}", e));
+            }
         }
+    }
 
-        let new_pos = self.tet_pos.add_y(1);
+    // Like high scores, unreadable controls are moved aside and the
+    // default keys are used.
+    fn load_controls(path: Option<&Path>) -> (Controls, Option<String>) {
+        let path = match path {
+            Some(path) => path,
+            None => return (Controls::new(), None),
+        };
 
-        if !self.is_collide(self.current_frame(), new_pos) {
-            self.tet_pos = new_pos;
-        } else {
-            self.copy_frame();
+        match Controls::load(path) {
+            Ok(controls) => (controls, None),
+            Err(e) => {
+                let _ = set_aside(path);
+                (Controls::new(), Some(format!("Controls were reset: {}", e)))
+            }
+        }
+    }
 
-            if self.field.is_any_line_filled() {
-                self.filled_lines_animation.start();
-                self.fall_timer.stop();
-            } else {
-                self.finish_turn();
+    // An unreadable file is moved aside rather than overwritten on the
+    // next save, and the game starts over with an empty table.
+    fn load_high_scores(path: Option<&Path>) -> (HighScores, Option<String>) {
+        let path = match path {
+            Some(path) => path,
+            None => return (HighScores::new(), None),
+        };
+
+        match HighScores::load(path) {
+            Ok(high_scores) => (high_scores, None),
+            Err(e) => {
+                let _ = set_aside(path);
+                (HighScores::new(), Some(format!("Scores were reset: {}", e)))
             }
         }
     }
 
-    fn change_screen(&mut self, new_screen: Screen) {
-        if self.screen == new_screen {
-            return;
+    pub fn score(&self) -> Number {
+        self.stats().score
+    }
+
+    pub fn mode(&self) -> GameMode {
+        self.mode
+    }
+
+    /// Stats of the player at this machine.
+    pub fn stats(&self) -> Stats {
+        self.players[self.local_player()].stats()
+    }
+
+    /// Whether the player at this machine won the last versus game.
+    pub fn is_winner(&self) -> bool {
+        self.winner == Some(self.local_player())
+    }
+
+    pub fn network_error(&self) -> Option<&str> {
+        self.network_error.as_deref()
+    }
+
+    /// Starts a new game in the given mode, skipping the mode selection.
+    pub fn start(&mut self, mode: GameMode) {
+        self.mode = mode;
+        self.close_popup_screen();
+        self.change_screen(GameScreen.into());
+    }
+
+    /// Starts a game in the given mode played by the bot, which asks to quit
+    /// once the game is over. Scores are not recorded.
+    pub fn autoplay(&mut self, mode: GameMode) {
+        self.autoplay = Autoplay::Benchmark;
+        self.bot = Some(Bot::new(Weights::default()));
+        self.start(mode);
+    }
+
+    /// Like `autoplay`, but the bot plays a versus match through the relay
+    /// at the given address.
+    pub fn autoplay_online(&mut self, address: &str) -> Result<(), String> {
+        self.connect(address)?;
+        self.autoplay = Autoplay::Benchmark;
+        self.bot = Some(Bot::new(Weights::default()));
+        Ok(())
+    }
+
+    /// Connects to a relay and waits there for an opponent.
+    pub fn connect(&mut self, address: &str) -> Result<(), String> {
+        self.online = Some(Online::connect(address, rules(&self.config, self.pieces))?);
+        self.network_error = None;
+        self.mode = GameMode::Versus(Versus);
+        self.close_popup_screen();
+        self.change_screen(WaitingScreen.into());
+        Ok(())
+    }
+
+    // Drops the connection after a network error. A bot playing online has
+    // nothing left to do.
+    fn disconnect(&mut self, error: String) {
+        self.online = None;
+        self.network_error = Some(format!("Online game ended: {}", error));
+        if self.autoplay == Autoplay::Benchmark {
+            self.is_quit_requested = true;
         }
+        self.change_screen(ModeSelectScreen.into());
+    }
 
-        self.screen = new_screen;
+    // The bot plays alone, so the demo skips modes for more players.
+    fn start_demo(&mut self) {
+        let mode = GameMode::ALL[self.selected_mode];
+        self.autoplay = Autoplay::Demo;
+        self.bot = Some(Bot::new(Weights::default()));
+        self.start(if mode.player_count() == 1 { mode } else { GameMode::ALL[0] });
+    }
 
-        new_screen.enter(self);
+    fn stop_demo(&mut self) {
+        self.autoplay = Autoplay::Off;
+        self.bot = None;
+        self.close_popup_screen();
+        self.change_screen(ModeSelectScreen.into());
     }
 
-    fn open_popup_screen(&mut self, popup_screen: Screen) {
-        if self.popup_screen.is_none() {
-            self.popup_screen = Some(popup_screen);
-            popup_screen.enter(self);
+    // The bot only gets to see the game while a piece can be moved.
+    fn turn(&self) -> Option<Turn<'_>> {
+        if self.screen != GameScreen.into() || self.popup_screen.is_some() {
+            return None;
         }
+        self.players[self.local_player()].turn()
     }
 
-    pub fn close_popup_screen(&mut self) {
-        self.popup_screen = None;
+    fn restore_saved_game(&mut self, saved: SavedGame) -> Result<(), String> {
+        let mode = GameMode::ALL.iter().copied().find(|mode| mode.name() == saved.mode)
+            .ok_or_else(|| format!("Unknown mode in save: {}", saved.mode))?;
+        let randomizer = saved.randomizer.parse::<RandomizerKind>()?;
+        if saved.pieces != self.pieces.name() {
+            return Err(format!("Saved game uses the {} pieces", saved.pieces));
+        }
+        if mode.player_count() != 1 {
+            return Err(format!("{} games cannot be saved", mode.name()));
+        }
+        if (saved.field_width, saved.field_height, saved.hidden_rows) != (self.config.field_width, self.config.field_height, self.config.hidden_rows) {
+            return Err(format!("Saved game was played on a {}x{} field", saved.field_width, saved.field_height));
+        }
+
+        let config = Config {
+            randomizer,
+            ..self.config.clone()
+        };
+        let mut player = Player::new(self.pieces, &config, mode, saved.seed, 0, self.players[0].origin());
+        let seed = saved.seed;
+        let is_modified = saved.is_modified;
+        player.restore(&config, saved)?;
+
+        self.mode = mode;
+        self.config = config;
+        self.seed = seed;
+        self.players = vec![player];
+        self.outcome = None;
+        self.winner = None;
+        self.is_game_modified = is_modified;
+        Ok(())
     }
 
-    pub fn update_score(&mut self, lines: Number) {
-        let score = if lines <= 0 {
-            0
-        } else if lines == 1 {
-            100
-        } else if lines == 2 {
-            250
-        } else if lines == 3 {
-            500
-        } else {
-            1000
+    // Versus games are not saved, quitting just ends them.
+    fn save_and_quit(&mut self) {
+        if let Some(path) = self.save_path.as_ref().filter(|_| self.mode.player_count() == 1) {
+            let saved = SavedGame {
+                is_modified: self.is_game_modified,
+                ..self.players[0].to_saved_game()
+            };
+            if let Err(e) = saved.save(path) {
+                self.storage_error = Some(format!("Could not save game: {}", e));
+                return;
+            }
+        }
+        self.is_quit_requested = true;
+    }
+
+    // Runs a debug console command and returns what to show for it.
+    fn run_command(&mut self, line: &str) -> Result<String, String> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            [] => Ok(String::new()),
+            ["help"] => Ok(CONSOLE_HELP.to_string()),
+            ["seed"] => Ok(format!("Seed: {}", self.seed)),
+            ["level", level] => {
+                let level = level.parse::<Number>().map_err(|e| format!("Invalid level: {}", e))?;
+                for player in self.players.iter_mut() {
+                    player.set_level(level);
+                }
+                self.is_game_modified = true;
+                Ok(format!("Level {}", self.stats().level))
+            }
+            ["garbage", lines] => {
+                let lines = lines.parse::<Number>().map_err(|e| format!("Invalid line count: {}", e))?;
+                let max_lines = self.config.field_height as Number;
+                if !(1..=max_lines).contains(&lines) {
+                    return Err(format!("Garbage must be 1 to {} lines", max_lines));
+                }
+                for player in self.players.iter_mut() {
+                    player.receive_garbage(lines);
+                }
+                self.is_game_modified = true;
+                Ok(format!("{} lines of garbage coming", lines))
+            }
+            _ => Err(format!("Unknown command: {}", line.trim())),
+        }
+    }
+
+    // The save is used up once resumed, and an unreadable one is moved
+    // aside like a corrupt high-score file.
+    fn resume(&mut self) {
+        let path = match &self.save_path {
+            Some(path) => path.clone(),
+            None => return,
         };
+        self.has_saved_game = false;
+
+        let result = SavedGame::load(&path).and_then(|saved| match saved {
+            Some(saved) => self.restore_saved_game(saved),
+            None => Err("Saved game is gone".into()),
+        });
+        match result {
+            Ok(()) => {
+                let _ = SavedGame::remove(&path);
+                self.screen = GameScreen.into();
+                self.open_popup_screen(PauseScreen.into());
+            }
+            Err(e) => {
+                let _ = set_aside(&path);
+                self.storage_error = Some(format!("Could not resume: {}", e));
+            }
+        }
+    }
 
-        self.score = min(self.score + score, 9999999);
+    pub fn seed(&self) -> u64 {
+        self.seed
     }
 
-    fn fall_period(level: Number) -> Number {
-        match level {
-            x if x <= 0 => 120,
-            1 => 60,
-            2 => 50,
-            3 => 40,
-            4 => 34,
-            5 => 28,
-            6 => 24,
-            7 => 16,
-            8 => 10,
-            _ => 8,
+    /// Upcoming pieces of the player at this machine.
+    pub fn next_pieces(&self) -> impl Iterator<Item = usize> + '_ {
+        self.players[self.local_player()].next_pieces()
+    }
+
+    // Online games use the seed the relay handed out.
+    fn reset_seed(&mut self) {
+        self.seed = self.online.as_ref().and_then(Online::seed)
+            .or(self.config.seed)
+            .unwrap_or_else(|| self.seed_rng.u64(..));
+    }
+
+    fn change_screen(&mut self, new_screen: Screen) {
+        if self.screen == new_screen {
+            return;
         }
+
+        self.screen = new_screen;
+
+        new_screen.enter(self);
     }
 
-    fn level(score: Number) -> Number {
-        if score < 0 {
-            0
-        } else {
-            min(9, score / 5000)
+    fn open_popup_screen(&mut self, popup_screen: Screen) {
+        if self.popup_screen.is_none() {
+            self.popup_screen = Some(popup_screen);
+            popup_screen.enter(self);
         }
     }
 
-    pub fn actualize_level(&mut self) {
-        let level = Self::level(self.score);
-        self.fall_timer = Timer::new(Self::fall_period(level));
+    pub fn close_popup_screen(&mut self) {
+        self.popup_screen = None;
     }
 }
 
 impl<'frame> App for State<'frame> {
     fn handle_input(&mut self, input: &Input) {
+        if self.autoplay == Autoplay::Demo && input.is_any_front_edge() {
+            self.stop_demo();
+            return;
+        }
+
+        if let Some(mut bot) = self.bot.take() {
+            bot.play(self.turn().as_ref());
+            self.bot = Some(bot);
+        }
+        self.update_actions(input);
+
         let current_screen = self.popup_screen.unwrap_or(self.screen);
         current_screen.handle_input(self, input);
     }
@@ -237,20 +579,38 @@ impl<'frame> App for State<'frame> {
     fn tick(&mut self) {
         let current_screen = self.popup_screen.unwrap_or(self.screen);
         current_screen.tick(self);
+
+        if self.autoplay == Autoplay::Demo && self.stats().ticks >= DEMO_LENGTH {
+            self.stop_demo();
+        }
     }
 
     fn draw(&self, buf: &mut ScreenBuffer) {
         let current_screen = self.popup_screen.unwrap_or(self.screen);
         current_screen.draw(self, buf);
+
+        if self.autoplay == Autoplay::Demo {
+            draw_colored_str(buf, Point::new(1, 0), "DEMO - press any key", Color::BLACK, Color::YELLOW);
+        }
+    }
+
+    fn is_quit_requested(&self) -> bool {
+        self.is_quit_requested
     }
 }
 
 #[enum_dispatch]
 #[derive(Eq, PartialEq, Copy, Clone)]
 enum Screen {
+    ModeSelectScreen,
     GameScreen,
-    RetryScreen,
+    NameEntryScreen,
+    ResultsScreen,
+    HighScoreScreen,
     PauseScreen,
+    WaitingScreen,
+    ControlsScreen,
+    ConsoleScreen,
 }
 
 #[enum_dispatch(Screen)]
@@ -266,135 +626,225 @@ struct GameScreen;
 
 impl ScreenBehavior for GameScreen {
     fn enter(&self, state: &mut State) {
-        state.score = 0;
-        state.fall_timer = Timer::new(State::fall_period(State::level(state.score)));
-        state.fall_timer.start();
-        state.curr_tet_index = state.rng.usize(0..7);
-        state.next_tet_index = state.rng.usize(0..7);
-        state.curr_frame = 0;
-        state.field.clear();
-        state.tet_pos = State::spawn_pos();
+        state.outcome = None;
+        state.winner = None;
+        state.is_game_modified = false;
+        state.reset_seed();
+        state.players = state.create_players();
     }
 
+    // Online games cannot be paused, pausing gives up instead.
     fn handle_input(&self, state: &mut State, input: &Input) {
-        if input.is_back_edge(Key::Left) {
-            state.left_repeater.stop();
+        let is_paused = state.is_action_front_edge(Action::Pause);
+        if let Some(online) = state.online.as_mut() {
+            let local = online.player();
+            let mut result = online.handle_input(&mut state.players, &state.actions[local]);
+            if result.is_ok() && is_paused {
+                result = online.resign();
+            }
+            if let Err(e) = result {
+                state.disconnect(e);
+                return;
+            }
+        } else {
+            for (player, actions) in state.players.iter_mut().zip(state.actions.iter()) {
+                player.handle_input(actions);
+            }
+
+            if is_paused {
+                state.open_popup_screen(PauseScreen.into());
+            } else if state.config.debug && input.is_front_edge(Key::Grave) {
+                state.open_popup_screen(ConsoleScreen.into());
+            }
+        }
+        state.check_game_over();
+    }
+
+    fn tick(&self, state: &mut State) {
+        if let Some(online) = state.online.as_mut() {
+            if let Err(e) = online.tick(&mut state.players) {
+                state.disconnect(e);
+                return;
+            }
+        } else {
+            for player in state.players.iter_mut() {
+                player.tick();
+            }
+            state.exchange_garbage();
         }
-        if input.is_back_edge(Key::Right) {
-            state.right_repeater.stop();
+        state.check_game_over();
+    }
+
+    fn draw(&self, state: &State, buf: &mut ScreenBuffer) {
+        let is_versus = state.players.len() > 1;
+        for (i, player) in state.players.iter().enumerate() {
+            if state.online.is_some() {
+                player.draw(buf, if i == state.local_player() { "You" } else { "Opponent" });
+            } else if is_versus {
+                player.draw(buf, &format!("Player {}", i + 1));
+            } else {
+                player.draw(buf, state.mode.name());
+            }
         }
-        if input.is_back_edge(Key::Down) {
-            state.down_repeater.stop();
+    }
+}
+
+#[derive(Eq, PartialEq, Copy, Clone)]
+struct ModeSelectScreen;
+
+impl ScreenBehavior for ModeSelectScreen {
+    fn enter(&self, state: &mut State) {
+        state.online = None;
+        state.selected_mode = GameMode::ALL.iter().position(|&mode| mode == state.mode).unwrap_or(0);
+        state.idle_ticks = 0;
+    }
+
+    fn handle_input(&self, state: &mut State, input: &Input) {
+        if input.is_any_front_edge() {
+            state.idle_ticks = 0;
         }
 
+        let mode_count = GameMode::ALL.len();
         if input.is_front_edge(Key::Up) {
-            state.rotate_colliding_tetromino();
+            state.selected_mode = (state.selected_mode + mode_count - 1) % mode_count;
         } else if input.is_front_edge(Key::Down) {
-            let new_pos = state.tet_pos.add_y(1);
-            state.move_colliding_tetromino(new_pos);
-            state.down_repeater.start();
-        } else if input.is_front_edge(Key::Left) {
-            let new_pos = state.tet_pos.sub_x(1);
-            state.move_colliding_tetromino(new_pos);
-            state.left_repeater.start();
-            state.right_repeater.stop();
-        } else if input.is_front_edge(Key::Right) {
-            let new_pos = state.tet_pos.add_x(1);
-            state.move_colliding_tetromino(new_pos);
-            state.right_repeater.start();
-            state.left_repeater.stop();
-        } else if input.is_front_edge(Key::Escape) {
-            state.open_popup_screen(PauseScreen.into());
-        } else if input.is_front_edge(Key::Equals) {
-            state.score += 5000;
-            state.fall_timer = Timer::new(State::fall_period(State::level(state.score)));
-            state.fall_timer.start();
+            state.selected_mode = (state.selected_mode + 1) % mode_count;
+        } else if input.is_front_edge(Key::Return) || input.is_front_edge(Key::Space) {
+            state.start(GameMode::ALL[state.selected_mode]);
+        } else if input.is_front_edge(Key::H) {
+            state.mode = GameMode::ALL[state.selected_mode];
+            state.change_screen(HighScoreScreen.into());
+        } else if input.is_front_edge(Key::C) {
+            state.change_screen(ControlsScreen.into());
+        } else if input.is_front_edge(Key::R) && state.has_saved_game {
+            state.resume();
         }
     }
 
     fn tick(&self, state: &mut State) {
-        state.left_repeater.tick();
-        state.right_repeater.tick();
-        state.down_repeater.tick();
-        state.fall_timer.tick();
-        state.filled_lines_animation.tick();
+        state.idle_ticks += 1;
+        if state.idle_ticks >= DEMO_DELAY {
+            state.start_demo();
+        }
+    }
 
-        if state.left_repeater.is_triggered() {
-            let new_pos = state.tet_pos.sub_x(1);
-            state.move_colliding_tetromino(new_pos);
+    fn draw(&self, state: &State, buf: &mut ScreenBuffer) {
+        draw_colored_str(buf, Point::new(1, 1), "r0t0blocks", Color::CYAN, Color::Default);
+        draw_str(buf, Point::new(1, 3), "Select mode:");
+
+        for (i, mode) in GameMode::ALL.iter().enumerate() {
+            let pos = Point::new(3, 5 + i as Number);
+            if i == state.selected_mode {
+                draw_colored_str(buf, pos.sub_x(2), ">", Color::YELLOW, Color::Default);
+                draw_colored_str(buf, pos, mode.name(), Color::YELLOW, Color::Default);
+            } else {
+                draw_str(buf, pos, mode.name());
+            }
         }
-        if state.right_repeater.is_triggered() {
-            let new_pos = state.tet_pos.add_x(1);
-            state.move_colliding_tetromino(new_pos);
+
+        let description_pos = Point::new(1, 6 + GameMode::ALL.len() as Number);
+        draw_str(buf, description_pos, GameMode::ALL[state.selected_mode].description());
+        draw_str(buf, description_pos.add_y(2), "Press enter to start.");
+        draw_str(buf, description_pos.add_y(3), "Press H for high scores.");
+        draw_str(buf, description_pos.add_y(4), "Press C for controls.");
+        if state.has_saved_game {
+            draw_colored_str(buf, description_pos.add_y(6), "Press R to resume game.", Color::GREEN, Color::Default);
         }
-        if state.down_repeater.is_triggered() {
-            let new_pos = state.tet_pos.add_y(1);
-            state.move_colliding_tetromino(new_pos);
+        if let Some(error) = &state.storage_error {
+            draw_colored_str(buf, description_pos.add_y(7), error, Color::RED, Color::Default);
         }
-        if state.filled_lines_animation.is_triggered() {
-            let filled_lines = state.clean_filled_lines();
-            state.update_score(filled_lines);
-            state.actualize_level();
-            state.fall_timer.start();
-            state.finish_turn();
+        if let Some(error) = &state.network_error {
+            draw_colored_str(buf, description_pos.add_y(8), error, Color::RED, Color::Default);
         }
-        if state.fall_timer.is_triggered() {
-            state.fall_timer.start();
-            state.move_down();
+    }
+}
+
+#[derive(Eq, PartialEq, Copy, Clone)]
+struct ResultsScreen;
+
+impl ScreenBehavior for ResultsScreen {
+    fn enter(&self, _state: &mut State) {
+
+    }
+
+    // There are no rematches online, the relay pairs players for one match.
+    fn handle_input(&self, state: &mut State, input: &Input) {
+        if input.is_front_edge(Key::Space) && state.online.is_none() {
+            state.start(state.mode);
+        } else if input.is_front_edge(Key::Escape) {
+            state.change_screen(ModeSelectScreen.into());
+        } else if input.is_front_edge(Key::H) {
+            state.change_screen(HighScoreScreen.into());
         }
     }
 
+    fn tick(&self, _state: &mut State) {
+
+    }
+
     fn draw(&self, state: &State, buf: &mut ScreenBuffer) {
-        draw_rect(buf, state.field_pos, Field::width() + 2, Field::height() + 2, '+');
-
-        for y in 0..Field::height() {
-            let pos_y = state.field_pos.y + y + 1;
-            if !state.field.is_line_filled(y) || state.filled_lines_animation.is_show() {
-                for x in 0..Field::width() {
-                    let pos_x = state.field_pos.x + x + 1;
-                    if state.field.is_filled(Point::new(x, y)) {
-                        buf.set_byte(Point::new(pos_x, pos_y), 0xb1u8);
-                    }
-                }
+        let outcome = state.outcome.unwrap_or(Outcome::ToppedOut);
+        let is_versus = state.players.len() > 1;
+        let (title, color) = if state.online.is_some() {
+            match state.winner {
+                Some(winner) if winner == state.local_player() => ("You win!".to_string(), Color::GREEN),
+                Some(_) => ("You lose.".to_string(), Color::RED),
+                None => ("Draw.".to_string(), Color::YELLOW),
             }
-        }
-
-        if !state.filled_lines_animation.is_started() {
-            for y in 0..Frame::height() {
-                for x in 0..Frame::width() {
-                    let pos = state.tet_pos + state.field_pos + Point::new(1, 1) + Point::new(x, y);
-                    if state.current_frame().is_filled(Point::new(x, y)) {
-                        buf.set_byte(pos, 0xb1u8);
-                    }
-                }
+        } else if is_versus {
+            match state.winner {
+                Some(winner) => (format!("Player {} wins!", winner + 1), Color::GREEN),
+                None => ("Draw.".to_string(), Color::YELLOW),
             }
-        }
-
-        for y in 0..Frame::height() {
-            for x in 0..Frame::width() {
-                let pos = state.field_pos.add_x(Field::width() + 4).add_y(Field::height() / 2) + Point::new(x, y);
-                if state.tetrominos[state.next_tet_index].frames[0].is_filled(Point::new(x, y)) {
-                    buf.set_byte(pos, 0xb1u8);
-                }
+        } else {
+            match outcome {
+                Outcome::Cleared => ("Cleared!".to_string(), Color::GREEN),
+                Outcome::TimeUp => ("Time up!".to_string(), Color::YELLOW),
+                Outcome::ToppedOut => ("Game over.".to_string(), Color::RED),
             }
+        };
+        draw_colored_str(buf, Point::new(0, 0), &title, Color::BLACK, color);
+        draw_colored_str(buf, Point::new(0, 1), state.mode.name(), Color::CYAN, Color::Default);
+
+        let results: Vec<String> = if is_versus {
+            state.players.iter().enumerate()
+                .flat_map(|(i, player)| state.mode.results(&player.stats(), outcome).into_iter().map(move |line| format!("Player {}: {}", i + 1, line)))
+                .collect()
+        } else {
+            state.mode.results(&state.stats(), outcome)
+        };
+        for (i, line) in results.iter().enumerate() {
+            draw_colored_str(buf, Point::new(0, 3 + i as Number), line, Color::YELLOW, Color::Default);
         }
 
-        draw_str(buf, state.field_pos.add_x(3 + Field::width()).add_y(1), &state.score.to_string());
-        draw_str(buf, state.field_pos.add_x(3 + Field::width()).add_y(2), &(State::level(state.score) + 1).to_string());
+        let mut footer_y = 4 + results.len() as Number;
+        if let Some(rank) = state.new_rank {
+            draw_colored_str(buf, Point::new(0, footer_y), &format!("New high score: #{}", rank + 1), Color::GREEN, Color::Default);
+            footer_y += 1;
+        }
+        draw_str(buf, Point::new(0, footer_y), &format!("Seed: {}", state.seed));
+        if state.online.is_none() {
+            draw_str(buf, Point::new(0, footer_y + 2), "Press space to try again.");
+        }
+        draw_str(buf, Point::new(0, footer_y + 3), "Press escape for menu.");
+        draw_str(buf, Point::new(0, footer_y + 4), "Press H for high scores.");
     }
 }
 
 #[derive(Eq, PartialEq, Copy, Clone)]
-struct RetryScreen;
+struct NameEntryScreen;
 
-impl ScreenBehavior for RetryScreen {
+impl ScreenBehavior for NameEntryScreen {
     fn enter(&self, _state: &mut State) {
 
     }
 
     fn handle_input(&self, state: &mut State, input: &Input) {
-        if input.is_front_edge(Key::Space) {
-            state.change_screen(GameScreen.into());
+        edit_line(&mut state.player_name, input, MAX_NAME_LEN);
+
+        if input.is_front_edge(Key::Return) && !state.player_name.trim().is_empty() {
+            state.submit_high_score();
+            state.change_screen(ResultsScreen.into());
         }
     }
 
@@ -403,9 +853,71 @@ impl ScreenBehavior for RetryScreen {
     }
 
     fn draw(&self, state: &State, buf: &mut ScreenBuffer) {
-        draw_str(buf, Point::new(0, 0), "Game over.");
-        draw_str(buf, Point::new(0, 1), &format!("Score: {}.", state.score));
-        draw_str(buf, Point::new(0, 2), "Press space to try again.");
+        draw_colored_str(buf, Point::new(0, 0), "New high score!", Color::BLACK, Color::YELLOW);
+        draw_colored_str(buf, Point::new(0, 1), state.mode.name(), Color::CYAN, Color::Default);
+        draw_str(buf, Point::new(0, 3), "Enter your name:");
+        draw_colored_str(buf, Point::new(0, 4), &format!("{}_", state.player_name), Color::YELLOW, Color::Default);
+        draw_str(buf, Point::new(0, 6), "Backspace to erase,");
+        draw_str(buf, Point::new(0, 7), "enter to save.");
+    }
+}
+
+#[derive(Eq, PartialEq, Copy, Clone)]
+struct HighScoreScreen;
+
+impl ScreenBehavior for HighScoreScreen {
+    fn enter(&self, state: &mut State) {
+        state.viewed_mode = GameMode::ALL.iter().position(|&mode| mode == state.mode).unwrap_or(0);
+    }
+
+    fn handle_input(&self, state: &mut State, input: &Input) {
+        let mode_count = GameMode::ALL.len();
+        if input.is_front_edge(Key::Left) {
+            state.viewed_mode = (state.viewed_mode + mode_count - 1) % mode_count;
+        } else if input.is_front_edge(Key::Right) {
+            state.viewed_mode = (state.viewed_mode + 1) % mode_count;
+        } else if input.is_front_edge(Key::Escape) || input.is_front_edge(Key::Return) {
+            state.change_screen(ModeSelectScreen.into());
+        }
+    }
+
+    fn tick(&self, _state: &mut State) {
+
+    }
+
+    fn draw(&self, state: &State, buf: &mut ScreenBuffer) {
+        let mode = GameMode::ALL[state.viewed_mode];
+        draw_str(buf, Point::new(0, 0), "High scores:");
+        draw_colored_str(buf, Point::new(13, 0), &format!("< {} >", mode.name()), Color::CYAN, Color::Default);
+
+        if let Some(error) = &state.storage_error {
+            draw_colored_str(buf, Point::new(0, 1), error, Color::RED, Color::Default);
+        }
+
+        let table = state.high_scores.table(mode.name());
+        if table.is_empty() {
+            draw_str(buf, Point::new(0, 3), "No scores yet.");
+        }
+
+        let is_timed = mode.ranking(Outcome::Cleared) == Some(Ranking::Time);
+        for (i, entry) in table.iter().enumerate() {
+            let pos = Point::new(0, 3 + 2 * i as Number);
+            let (primary, secondary) = if is_timed {
+                (format_ticks(entry.ticks), entry.score.to_string())
+            } else {
+                (entry.score.to_string(), format_ticks(entry.ticks))
+            };
+            let color = if Some(i) == state.new_rank && mode == state.mode {
+                Color::YELLOW
+            } else {
+                Color::Default
+            };
+            draw_colored_str(buf, pos, &format!("{:>2} {:<8} {:>7}", i + 1, entry.name, primary), color, Color::Default);
+            draw_str(buf, pos.add_x(20), &entry.date_text());
+            draw_str(buf, pos.add_y(1), &format!("   {} lines lv{} {}", entry.lines, entry.level, secondary));
+        }
+
+        draw_str(buf, Point::new(0, buf.height() as Number - 1), "Left/Right: mode, Esc: back");
     }
 }
 
@@ -414,14 +926,19 @@ struct PauseScreen;
 
 impl ScreenBehavior for PauseScreen {
     fn enter(&self, state: &mut State) {
-        state.left_repeater.stop();
-        state.right_repeater.stop();
-        state.down_repeater.stop();
+        for player in state.players.iter_mut() {
+            player.stop_repeaters();
+        }
     }
 
     fn handle_input(&self, state: &mut State, input: &Input) {
-        if input.is_front_edge(Key::Escape) {
+        if input.is_front_edge(Key::Escape) || state.is_action_front_edge(Action::Pause) {
+            state.close_popup_screen();
+        } else if input.is_front_edge(Key::Q) {
+            state.save_and_quit();
+        } else if input.is_front_edge(Key::M) {
             state.close_popup_screen();
+            state.change_screen(ModeSelectScreen.into());
         }
     }
 
@@ -429,7 +946,337 @@ impl ScreenBehavior for PauseScreen {
 
     }
 
-    fn draw(&self, _state: &State, buf: &mut ScreenBuffer) {
+    fn draw(&self, state: &State, buf: &mut ScreenBuffer) {
         draw_str(buf, Point::new(0, 0), "Pause.");
+        draw_str(buf, Point::new(0, 1), &format!("Seed: {}", state.seed));
+        draw_str(buf, Point::new(0, 2), &format!("Randomizer: {}", state.config.randomizer.name()));
+        if state.mode.player_count() == 1 {
+            draw_str(buf, Point::new(0, 4), "Press Q to save and quit.");
+        } else {
+            draw_str(buf, Point::new(0, 4), "Press Q to quit.");
+        }
+        draw_str(buf, Point::new(0, 5), "Press M to quit to menu.");
+        if let Some(error) = &state.storage_error {
+            draw_colored_str(buf, Point::new(0, 7), error, Color::RED, Color::Default);
+        }
+    }
+}
+
+#[derive(Eq, PartialEq, Copy, Clone)]
+struct WaitingScreen;
+
+impl ScreenBehavior for WaitingScreen {
+    fn enter(&self, _state: &mut State) {
+
+    }
+
+    fn handle_input(&self, state: &mut State, input: &Input) {
+        if input.is_front_edge(Key::Escape) {
+            state.change_screen(ModeSelectScreen.into());
+        }
+    }
+
+    fn tick(&self, state: &mut State) {
+        let result = match state.online.as_mut() {
+            Some(online) => online.wait_for_start(),
+            None => return,
+        };
+        match result {
+            Ok(Some(_)) => state.start(GameMode::Versus(Versus)),
+            Ok(None) => {}
+            Err(e) => state.disconnect(e),
+        }
+    }
+
+    fn draw(&self, state: &State, buf: &mut ScreenBuffer) {
+        draw_colored_str(buf, Point::new(1, 1), "r0t0blocks", Color::CYAN, Color::Default);
+        if let Some(online) = &state.online {
+            draw_str(buf, Point::new(1, 3), &format!("Connected to {}.", online.address()));
+        }
+        draw_str(buf, Point::new(1, 5), "Waiting for an opponent...");
+        draw_str(buf, Point::new(1, 7), "Press escape to cancel.");
+    }
+}
+
+#[derive(Eq, PartialEq, Copy, Clone)]
+struct ControlsScreen;
+
+impl ScreenBehavior for ControlsScreen {
+    fn enter(&self, state: &mut State) {
+        state.selected_set = 0;
+        state.selected_action = 0;
+        state.rebinding = None;
+    }
+
+    // Escape is for cancelling, so it cannot be bound here.
+    fn handle_input(&self, state: &mut State, input: &Input) {
+        let action = Action::ALL[state.selected_action];
+        if let Some(is_added) = state.rebinding {
+            if input.is_front_edge(Key::Escape) {
+                state.rebinding = None;
+            } else if let Some((key, _)) = input.edges().find(|&(_, is_pressed)| is_pressed) {
+                let bindings = state.controls.set_mut(state.selected_set);
+                if !is_added {
+                    bindings.set(action, &[]);
+                }
+                bindings.bind(action, key);
+                state.rebinding = None;
+                state.save_controls();
+            }
+            return;
+        }
+
+        let set_count = Controls::SET_NAMES.len();
+        let action_count = Action::ALL.len();
+        if input.is_front_edge(Key::Left) {
+            state.selected_set = (state.selected_set + set_count - 1) % set_count;
+        } else if input.is_front_edge(Key::Right) {
+            state.selected_set = (state.selected_set + 1) % set_count;
+        } else if input.is_front_edge(Key::Up) {
+            state.selected_action = (state.selected_action + action_count - 1) % action_count;
+        } else if input.is_front_edge(Key::Down) {
+            state.selected_action = (state.selected_action + 1) % action_count;
+        } else if input.is_front_edge(Key::Return) {
+            state.rebinding = Some(false);
+        } else if input.is_front_edge(Key::Space) {
+            state.rebinding = Some(true);
+        } else if input.is_front_edge(Key::D) {
+            *state.controls.set_mut(state.selected_set) = Controls::default_set(state.selected_set);
+            state.save_controls();
+        } else if input.is_front_edge(Key::Escape) {
+            state.change_screen(ModeSelectScreen.into());
+        }
+    }
+
+    fn tick(&self, _state: &mut State) {
+
+    }
+
+    fn draw(&self, state: &State, buf: &mut ScreenBuffer) {
+        draw_str(buf, Point::new(0, 0), "Controls:");
+        draw_colored_str(buf, Point::new(10, 0), &format!("< {} >", Controls::SET_NAMES[state.selected_set]), Color::CYAN, Color::Default);
+
+        if let Some(error) = &state.storage_error {
+            draw_colored_str(buf, Point::new(0, 1), error, Color::RED, Color::Default);
+        }
+
+        let bindings = state.controls.set(state.selected_set);
+        for (i, &action) in Action::ALL.iter().enumerate() {
+            let pos = Point::new(2, 3 + i as Number);
+            let keys: Vec<&str> = bindings.keys(action).iter().map(|key| key.name()).collect();
+            let color = if i == state.selected_action {
+                draw_colored_str(buf, pos.sub_x(2), ">", Color::YELLOW, Color::Default);
+                Color::YELLOW
+            } else {
+                Color::Default
+            };
+            draw_colored_str(buf, pos, action.label(), color, Color::Default);
+            draw_colored_str(buf, pos.add_x(14), &keys.join(" "), color, Color::Default);
+        }
+
+        let help_pos = Point::new(0, 4 + Action::ALL.len() as Number);
+        match state.rebinding {
+            Some(_) => {
+                let action = Action::ALL[state.selected_action];
+                draw_colored_str(buf, help_pos, &format!("Press a key for {}.", action.label().to_lowercase()), Color::BLACK, Color::YELLOW);
+                draw_str(buf, help_pos.add_y(1), "Escape cancels.");
+            }
+            None => {
+                draw_str(buf, help_pos, "Enter: set key, Space: add key");
+                draw_str(buf, help_pos.add_y(1), "D: default keys");
+                draw_str(buf, help_pos.add_y(2), "Left/Right: player, Esc: back");
+            }
+        }
+    }
+}
+
+#[derive(Eq, PartialEq, Copy, Clone)]
+struct ConsoleScreen;
+
+// The game stays on screen and stands still while the console is open.
+impl ScreenBehavior for ConsoleScreen {
+    fn enter(&self, state: &mut State) {
+        for player in state.players.iter_mut() {
+            player.stop_repeaters();
+        }
+        state.console_line.clear();
+        state.console_output = Some(Ok(CONSOLE_HELP.to_string()));
+    }
+
+    fn handle_input(&self, state: &mut State, input: &Input) {
+        if input.is_front_edge(Key::Escape) || input.is_front_edge(Key::Grave) {
+            state.close_popup_screen();
+        } else if input.is_front_edge(Key::Return) {
+            let line = std::mem::take(&mut state.console_line);
+            state.console_output = Some(state.run_command(&line));
+        } else {
+            edit_line(&mut state.console_line, input, MAX_COMMAND_LEN);
+        }
+    }
+
+    fn tick(&self, _state: &mut State) {
+
+    }
+
+    fn draw(&self, state: &State, buf: &mut ScreenBuffer) {
+        GameScreen.draw(state, buf);
+
+        let width = state.screen_width as usize;
+        let pos = Point::new(0, buf.height() as Number - 2);
+        let (output, color) = match &state.console_output {
+            Some(Ok(output)) => (output.as_str(), Color::Default),
+            Some(Err(error)) => (error.as_str(), Color::RED),
+            None => ("", Color::Default),
+        };
+        draw_colored_str(buf, pos, &format!("{:<width$}", output, width = width), color, Color::BLACK);
+        draw_colored_str(buf, pos.add_y(1), &format!("{:<width$}", format!("> {}_", state.console_line), width = width), Color::YELLOW, Color::BLACK);
+    }
+}
+
+// Names and commands are drawn byte by byte, so only printable ASCII is
+// taken. Backspace erases a character, with Ctrl the whole line.
+fn edit_line(line: &mut String, input: &Input, max_len: usize) {
+    for chr in input.text().chars().filter(|&chr| chr.is_ascii_graphic() || chr == ' ') {
+        if line.len() < max_len {
+            line.push(chr);
+        }
+    }
+
+    if input.is_front_edge(Key::Backspace) {
+        if input.modifiers().ctrl {
+            line.clear();
+        } else {
+            line.pop();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use engine::base::Backend;
+    use engine::headless::{Headless, HeadlessParams, Script};
+
+    fn run(config: Config, pieces: &PieceSet, script: Script, tick_count: u64) -> (State<'_>, Vec<ScreenBuffer>) {
+        let (width_in_tiles, height_in_tiles) = State::screen_size(&config, pieces);
+        let mut state = State::new(pieces, config);
+        let params = HeadlessParams {
+            width_in_tiles,
+            height_in_tiles,
+            tick_count,
+            ticks_per_frame: 1,
+        };
+        let mut headless = Headless::new(params, script);
+        headless.run(&mut state).unwrap();
+        (state, headless.into_frames())
+    }
+
+    fn row(buf: &ScreenBuffer, y: usize) -> String {
+        (0..buf.width())
+            .map(|x| match buf.byte_at(x, y) {
+                0 => ' ',
+                b if b < 128 => b as char,
+                0xb0 => '.',
+                _ => '#',
+            })
+            .collect()
+    }
+
+    // The last two rows above the bottom of the field.
+    fn field_bottom(buf: &ScreenBuffer) -> (String, String) {
+        let bottom = (0..buf.height()).rev().find(|&y| row(buf, y).contains("++++++++++++")).unwrap();
+        (row(buf, bottom - 2), row(buf, bottom - 1))
+    }
+
+    #[test]
+    fn piece_moves_left() {
+        let pieces = PieceSet::standard();
+        let config = Config {
+            seed: Some(1),
+            data_dir: None,
+            ..Config::default()
+        };
+        let script = Script::new().tap(1, Key::Return).tap(10, Key::Left);
+        let (_, frames) = run(config, &pieces, script, 20);
+
+        let (_, lower) = field_bottom(&frames[5]);
+        assert!(lower.contains("+   ...    +"), "{}", lower);
+
+        let (_, lower) = field_bottom(frames.last().unwrap());
+        assert!(lower.contains("+  ...     +"), "{}", lower);
+    }
+
+    #[test]
+    fn hard_drop_to_ghost() {
+        let pieces = PieceSet::standard();
+        let config = Config {
+            seed: Some(1),
+            data_dir: None,
+            ..Config::default()
+        };
+        let script = Script::new().tap(1, Key::Return).tap(10, Key::Space);
+        let (state, frames) = run(config, &pieces, script, 20);
+
+        let (upper, lower) = field_bottom(&frames[5]);
+        assert!(upper.contains("+    .     +"), "{}", upper);
+        assert!(lower.contains("+   ...    +"), "{}", lower);
+
+        let (upper, lower) = field_bottom(frames.last().unwrap());
+        assert!(upper.contains("+    #     +"), "{}", upper);
+        assert!(lower.contains("+   ###    +"), "{}", lower);
+
+        let stats = state.stats();
+        assert_eq!(stats.score, 38);
+        assert_eq!(stats.lines, 0);
+    }
+
+    #[test]
+    fn resume_keeps_console_changes() {
+        let pieces = PieceSet::standard();
+        let data_dir = std::env::temp_dir().join(format!("r0t0blocks-resume-{}", std::process::id()));
+        let config = Config {
+            seed: Some(1),
+            data_dir: Some(data_dir.clone()),
+            debug: true,
+            ..Config::default()
+        };
+
+        let script = Script::new()
+            .tap(1, Key::Return)
+            .tap(10, Key::Grave)
+            .type_text(12, "level 5")
+            .tap(14, Key::Return)
+            .tap(16, Key::Grave)
+            .tap(20, Key::Escape)
+            .tap(24, Key::Q);
+        let (state, _) = run(config.clone(), &pieces, script, 30);
+        assert!(state.is_quit_requested);
+
+        let (state, _) = run(config, &pieces, Script::new().tap(1, Key::R), 5);
+        let _ = std::fs::remove_dir_all(&data_dir);
+        assert!(state.is_game_modified);
+        assert_eq!(state.stats().level, 5);
+    }
+
+    #[test]
+    fn resume_needs_same_field_size() {
+        let pieces = PieceSet::standard();
+        let data_dir = std::env::temp_dir().join(format!("r0t0blocks-field-{}", std::process::id()));
+        let config = Config {
+            seed: Some(1),
+            data_dir: Some(data_dir.clone()),
+            ..Config::default()
+        };
+
+        let script = Script::new().tap(1, Key::Return).tap(10, Key::Escape).tap(14, Key::Q);
+        run(config.clone(), &pieces, script, 20);
+
+        let config = Config {
+            field_width: 12,
+            ..config
+        };
+        let (state, _) = run(config, &pieces, Script::new().tap(1, Key::R), 5);
+        let _ = std::fs::remove_dir_all(&data_dir);
+        assert_eq!(state.storage_error.as_deref(), Some("Could not resume: Saved game was played on a 10x20 field"));
     }
 }
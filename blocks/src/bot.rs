@@ -0,0 +1,315 @@
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, VecDeque};
+
+use engine::base::Number;
+use engine::geometry::Point;
+use engine::input::Actions;
+
+use crate::controls::Action;
+use crate::field::{Cell, Field};
+use crate::piece::{Frame, Piece, PieceSet};
+
+const MOVES: [Move; 5] = [Move::Left, Move::Right, Move::RotateClockwise, Move::RotateCounterClockwise, Move::SoftDrop];
+
+/// How much each property of the stack counts when scoring a placement.
+/// Cleared lines count for a placement, everything else against it.
+#[derive(Copy, Clone, Debug)]
+pub struct Weights {
+    pub holes: f32,
+    pub aggregate_height: f32,
+    pub bumpiness: f32,
+    pub lines_cleared: f32,
+}
+
+impl Default for Weights {
+    fn default() -> Weights {
+        Weights {
+            holes: 0.36,
+            aggregate_height: 0.51,
+            bumpiness: 0.18,
+            lines_cleared: 0.76,
+        }
+    }
+}
+
+/// What the bot sees of the game while a piece is in play.
+pub struct Turn<'a> {
+    pub field: &'a Field,
+    pub pieces: &'a PieceSet,
+    pub piece: usize,
+    pub rotation: usize,
+    pub pos: Point,
+    pub next_piece: Option<usize>,
+}
+
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+enum Move {
+    Left,
+    Right,
+    RotateClockwise,
+    RotateCounterClockwise,
+    SoftDrop,
+    HardDrop,
+}
+
+impl Move {
+    fn action(self) -> Action {
+        match self {
+            Move::Left => Action::MoveLeft,
+            Move::Right => Action::MoveRight,
+            Move::RotateClockwise => Action::RotateClockwise,
+            Move::RotateCounterClockwise => Action::RotateCounterClockwise,
+            Move::SoftDrop => Action::SoftDrop,
+            Move::HardDrop => Action::HardDrop,
+        }
+    }
+}
+
+#[derive(Eq, PartialEq, Hash, Copy, Clone, Debug)]
+struct Pose {
+    rotation: usize,
+    x: Number,
+    y: Number,
+}
+
+impl Pose {
+    fn new(rotation: usize, pos: Point) -> Pose {
+        Pose {
+            rotation,
+            x: pos.x,
+            y: pos.y,
+        }
+    }
+
+    fn pos(self) -> Point {
+        Point::new(self.x, self.y)
+    }
+
+    fn frame(self, piece: &Piece) -> &Frame {
+        &piece.frames[self.rotation]
+    }
+}
+
+/// Plays the game by taking actions the same way a player would. Each
+/// piece is placed where the heuristic likes it best, looking one piece
+/// ahead into the preview. It never uses hold.
+pub struct Bot {
+    weights: Weights,
+    actions: Actions<Action>,
+    plan: VecDeque<(Move, Pose)>,
+    // piece and pose the game should be in if the last move went as planned
+    expected: Option<(usize, Pose)>,
+    pressed: Option<Action>,
+}
+
+impl Bot {
+    pub fn new(weights: Weights) -> Bot {
+        Bot {
+            weights,
+            actions: Actions::new(),
+            plan: VecDeque::new(),
+            expected: None,
+            pressed: None,
+        }
+    }
+
+    pub fn actions(&self) -> &Actions<Action> {
+        &self.actions
+    }
+
+    /// Advances the actions by one tick. They are tapped on every other
+    /// tick so each one registers as a new one, and the plan is made again
+    /// whenever the piece is not where the last move should have left it.
+    pub fn play(&mut self, turn: Option<&Turn>) {
+        self.actions.tick();
+
+        if let Some(action) = self.pressed.take() {
+            self.actions.set(action, false);
+            return;
+        }
+
+        let turn = match turn {
+            Some(turn) => turn,
+            None => return,
+        };
+
+        let pose = Pose::new(turn.rotation, turn.pos);
+        if self.expected != Some((turn.piece, pose)) {
+            self.plan = plan(turn, &self.weights);
+            self.expected = Some((turn.piece, pose));
+        }
+
+        if let Some((next_move, next_pose)) = self.plan.pop_front() {
+            self.expected = if next_move == Move::HardDrop {
+                None
+            } else {
+                Some((turn.piece, next_pose))
+            };
+            self.actions.set(next_move.action(), true);
+            self.pressed = Some(next_move.action());
+        }
+    }
+}
+
+fn plan(turn: &Turn, weights: &Weights) -> VecDeque<(Move, Pose)> {
+    let piece = turn.pieces.get(turn.piece);
+    let start = Pose::new(turn.rotation, turn.pos);
+    if turn.field.is_collide(start.frame(piece), start.pos()) {
+        return VecDeque::new();
+    }
+
+    let search = Search::new(turn.field, piece, start);
+    let next_piece = turn.next_piece.map(|index| (index, turn.pieces.get(index)));
+
+    let mut best: Option<(f32, Pose, Pose)> = None;
+    for &(landing, from) in search.landings.iter() {
+        let (field, lines) = lock(turn.field, piece, turn.piece, landing);
+        let value = match next_piece {
+            Some((index, next)) => drop_placements(&field, next)
+                .map(|next_landing| {
+                    let (next_field, next_lines) = lock(&field, next, index, next_landing);
+                    evaluate(&next_field, lines + next_lines, weights)
+                })
+                .fold(None, |best: Option<f32>, value| Some(best.map_or(value, |best| best.max(value))))
+                .unwrap_or_else(|| evaluate(&field, lines, weights)),
+            None => evaluate(&field, lines, weights),
+        };
+
+        if best.is_none_or(|(best_value, _, _)| value > best_value) {
+            best = Some((value, landing, from));
+        }
+    }
+
+    match best {
+        Some((_, landing, from)) => {
+            let mut path = search.path_to(from);
+            path.push_back((Move::HardDrop, landing));
+            path
+        }
+        None => VecDeque::new(),
+    }
+}
+
+// Breadth-first search over every pose the piece can be moved into. Each
+// landing is kept with the first pose found to hard drop onto it, which
+// gives the shortest key sequence.
+struct Search {
+    parents: HashMap<Pose, Option<(Pose, Move)>>,
+    landings: Vec<(Pose, Pose)>,
+}
+
+impl Search {
+    fn new(field: &Field, piece: &Piece, start: Pose) -> Search {
+        let mut parents = HashMap::new();
+        let mut landings: Vec<(Pose, Pose)> = Vec::new();
+        let mut queue = VecDeque::new();
+        parents.insert(start, None);
+        queue.push_back(start);
+
+        while let Some(pose) = queue.pop_front() {
+            let landing = drop_pose(field, piece, pose);
+            if !landings.iter().any(|&(known, _)| known == landing) {
+                landings.push((landing, pose));
+            }
+
+            for next_move in MOVES.iter().copied() {
+                if let Some(next) = apply(field, piece, pose, next_move) {
+                    if let Entry::Vacant(entry) = parents.entry(next) {
+                        entry.insert(Some((pose, next_move)));
+                        queue.push_back(next);
+                    }
+                }
+            }
+        }
+
+        Search {
+            parents,
+            landings,
+        }
+    }
+
+    fn path_to(&self, pose: Pose) -> VecDeque<(Move, Pose)> {
+        let mut path = VecDeque::new();
+        let mut pose = pose;
+        while let Some(&Some((parent, parent_move))) = self.parents.get(&pose) {
+            path.push_front((parent_move, pose));
+            pose = parent;
+        }
+        path
+    }
+}
+
+// Mirrors the moves and wall kicks of the game itself.
+fn apply(field: &Field, piece: &Piece, pose: Pose, next_move: Move) -> Option<Pose> {
+    let offset = match next_move {
+        Move::Left => Point::new(-1, 0),
+        Move::Right => Point::new(1, 0),
+        Move::SoftDrop => Point::new(0, 1),
+        Move::HardDrop => return Some(drop_pose(field, piece, pose)),
+        Move::RotateClockwise | Move::RotateCounterClockwise => {
+            let is_clockwise = next_move == Move::RotateClockwise;
+            let frame_count = piece.frames.len();
+            let rotation = if is_clockwise {
+                (pose.rotation + 1) % frame_count
+            } else {
+                (pose.rotation + frame_count - 1) % frame_count
+            };
+            return piece.kicks.offsets(pose.rotation, is_clockwise).into_iter()
+                .map(|offset| Pose::new(rotation, pose.pos() + offset))
+                .find(|rotated| !field.is_collide(rotated.frame(piece), rotated.pos()));
+        }
+    };
+
+    let moved = Pose::new(pose.rotation, pose.pos() + offset);
+    if field.is_collide(moved.frame(piece), moved.pos()) {
+        None
+    } else {
+        Some(moved)
+    }
+}
+
+fn drop_pose(field: &Field, piece: &Piece, pose: Pose) -> Pose {
+    let mut pose = pose;
+    while !field.is_collide(pose.frame(piece), pose.pos().add_y(1)) {
+        pose.y += 1;
+    }
+    pose
+}
+
+// Placements reached by only rotating and shifting from the top of the
+// field, used to look ahead at the next piece.
+fn drop_placements<'a>(field: &'a Field, piece: &'a Piece) -> impl Iterator<Item = Pose> + 'a {
+    (0..piece.frames.len())
+        .flat_map(move |rotation| {
+            let side = piece.frames[rotation].width();
+            (-side..field.width()).map(move |x| Pose::new(rotation, Point::new(x, 0)))
+        })
+        .filter(move |pose| !field.is_collide(pose.frame(piece), pose.pos()))
+        .map(move |pose| drop_pose(field, piece, pose))
+}
+
+fn lock(field: &Field, piece: &Piece, index: usize, pose: Pose) -> (Field, Number) {
+    let mut field = field.clone();
+    field.copy_frame(pose.frame(piece), pose.pos(), Cell::Piece(index));
+    let lines = field.clean_filled_lines();
+    (field, lines)
+}
+
+fn evaluate(field: &Field, lines: Number, weights: &Weights) -> f32 {
+    let mut heights = Vec::with_capacity(field.width() as usize);
+    let mut holes = 0;
+    for x in 0..field.width() {
+        let top = (0..field.height()).find(|&y| field.is_filled(Point::new(x, y)));
+        let height = top.map_or(0, |top| field.height() - top);
+        holes += top.map_or(0, |top| (top..field.height()).filter(|&y| !field.is_filled(Point::new(x, y))).count());
+        heights.push(height);
+    }
+
+    let aggregate_height: Number = heights.iter().sum();
+    let bumpiness: Number = heights.windows(2).map(|pair| (pair[0] - pair[1]).abs()).sum();
+
+    weights.lines_cleared * lines as f32 -
+        weights.holes * holes as f32 -
+        weights.aggregate_height * aggregate_height as f32 -
+        weights.bumpiness * bumpiness as f32
+}
@@ -0,0 +1,58 @@
+use std::path::PathBuf;
+
+use engine::base::Number;
+
+use crate::randomizer::RandomizerKind;
+use crate::storage::data_dir;
+
+pub const TICKS_PER_SECOND: Number = 125;
+
+pub const MAX_PREVIEW_COUNT: usize = 6;
+
+pub const MIN_FIELD_WIDTH: usize = 4;
+pub const MAX_FIELD_WIDTH: usize = 40;
+pub const MIN_FIELD_HEIGHT: usize = 4;
+pub const MAX_FIELD_HEIGHT: usize = 40;
+
+#[derive(Clone)]
+pub struct Config {
+    /// Seed for piece generation. Every game uses it when set, otherwise
+    /// each game picks a fresh seed.
+    pub seed: Option<u64>,
+    pub randomizer: RandomizerKind,
+    pub field_width: usize,
+    /// Visible rows of the field.
+    pub field_height: usize,
+    /// Rows above the visible area where pieces spawn.
+    pub hidden_rows: usize,
+    /// Number of upcoming pieces shown, from 0 to `MAX_PREVIEW_COUNT`.
+    pub preview_count: usize,
+    /// Level the game starts at, capped by the game mode's maximum.
+    pub start_level: Number,
+    /// Ticks a grounded piece waits before locking.
+    pub lock_delay: Number,
+    /// How many moves or rotations on the ground restart the lock delay.
+    pub max_lock_resets: Number,
+    /// Where high scores are kept. Nothing is saved when unset.
+    pub data_dir: Option<PathBuf>,
+    /// Lets the backtick key open the debug console during offline games.
+    pub debug: bool,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            seed: None,
+            randomizer: RandomizerKind::Bag7,
+            field_width: 10,
+            field_height: 20,
+            hidden_rows: 20,
+            preview_count: 5,
+            start_level: 1,
+            lock_delay: 63,
+            max_lock_resets: 15,
+            data_dir: data_dir(),
+            debug: false,
+        }
+    }
+}
@@ -0,0 +1,214 @@
+use std::path::Path;
+
+use engine::input::{self, Bindings, Key};
+
+use crate::storage::{read_optional, write_atomic};
+
+const HEADER: &str = "# r0t0blocks controls 1";
+
+#[derive(Eq, PartialEq, Hash, Copy, Clone, Debug)]
+pub enum Action {
+    MoveLeft,
+    MoveRight,
+    SoftDrop,
+    HardDrop,
+    RotateClockwise,
+    RotateCounterClockwise,
+    Hold,
+    Pause,
+}
+
+impl Action {
+    pub const ALL: [Action; 8] = [
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::SoftDrop,
+        Action::HardDrop,
+        Action::RotateClockwise,
+        Action::RotateCounterClockwise,
+        Action::Hold,
+        Action::Pause,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Action::MoveLeft => "Move left",
+            Action::MoveRight => "Move right",
+            Action::SoftDrop => "Soft drop",
+            Action::HardDrop => "Hard drop",
+            Action::RotateClockwise => "Rotate right",
+            Action::RotateCounterClockwise => "Rotate left",
+            Action::Hold => "Hold",
+            Action::Pause => "Pause",
+        }
+    }
+}
+
+impl input::Action for Action {
+    fn all() -> &'static [Action] {
+        &Action::ALL
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Action::MoveLeft => "move_left",
+            Action::MoveRight => "move_right",
+            Action::SoftDrop => "soft_drop",
+            Action::HardDrop => "hard_drop",
+            Action::RotateClockwise => "rotate_clockwise",
+            Action::RotateCounterClockwise => "rotate_counter_clockwise",
+            Action::Hold => "hold",
+            Action::Pause => "pause",
+        }
+    }
+}
+
+/// Key bindings for solo games and for each side of a versus game on one
+/// keyboard. They are kept as a text file with a section per set.
+#[derive(Clone)]
+pub struct Controls {
+    sets: [Bindings<Action>; 3],
+}
+
+impl Controls {
+    pub const SET_NAMES: [&'static str; 3] = ["Solo", "Player 1", "Player 2"];
+
+    pub fn new() -> Controls {
+        Controls {
+            sets: [Self::default_set(0), Self::default_set(1), Self::default_set(2)],
+        }
+    }
+
+    /// Versus games split the keyboard into left and right hand halves.
+    /// Pads cannot be told apart, so only the solo set has them.
+    pub fn default_set(set: usize) -> Bindings<Action> {
+        match set {
+            0 => Bindings::new()
+                .with(Action::MoveLeft, &[Key::Left, Key::PadLeft, Key::PadStickLeft])
+                .with(Action::MoveRight, &[Key::Right, Key::PadRight, Key::PadStickRight])
+                .with(Action::SoftDrop, &[Key::Down, Key::PadDown, Key::PadStickDown])
+                .with(Action::HardDrop, &[Key::Space, Key::PadUp])
+                .with(Action::RotateClockwise, &[Key::Up, Key::X, Key::PadA])
+                .with(Action::RotateCounterClockwise, &[Key::Z, Key::PadB])
+                .with(Action::Hold, &[Key::C, Key::PadLeftShoulder, Key::PadRightShoulder])
+                .with(Action::Pause, &[Key::Escape, Key::PadStart]),
+            1 => Bindings::new()
+                .with(Action::MoveLeft, &[Key::A])
+                .with(Action::MoveRight, &[Key::D])
+                .with(Action::SoftDrop, &[Key::S])
+                .with(Action::HardDrop, &[Key::W])
+                .with(Action::RotateClockwise, &[Key::E])
+                .with(Action::RotateCounterClockwise, &[Key::Q])
+                .with(Action::Hold, &[Key::R])
+                .with(Action::Pause, &[Key::Escape]),
+            _ => Bindings::new()
+                .with(Action::MoveLeft, &[Key::J])
+                .with(Action::MoveRight, &[Key::L])
+                .with(Action::SoftDrop, &[Key::K])
+                .with(Action::HardDrop, &[Key::I])
+                .with(Action::RotateClockwise, &[Key::O])
+                .with(Action::RotateCounterClockwise, &[Key::U])
+                .with(Action::Hold, &[Key::P])
+                .with(Action::Pause, &[Key::Escape]),
+        }
+    }
+
+    /// Bindings of one player: the solo set, or their side of the keyboard
+    /// in a versus game.
+    pub fn player(&self, is_versus: bool, index: usize) -> &Bindings<Action> {
+        if is_versus {
+            &self.sets[1 + index % 2]
+        } else {
+            &self.sets[0]
+        }
+    }
+
+    pub fn set(&self, set: usize) -> &Bindings<Action> {
+        &self.sets[set]
+    }
+
+    pub fn set_mut(&mut self, set: usize) -> &mut Bindings<Action> {
+        &mut self.sets[set]
+    }
+
+    /// Missing files and sets keep the default keys.
+    pub fn load(path: &Path) -> Result<Controls, String> {
+        match read_optional(path)? {
+            Some(contents) => Self::parse(&contents),
+            None => Ok(Controls::new()),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        write_atomic(path, &self.to_text())
+    }
+
+    fn to_text(&self) -> String {
+        let mut text = format!("{}\n", HEADER);
+        for (name, bindings) in Self::SET_NAMES.iter().zip(self.sets.iter()) {
+            text += &format!("\n[{}]\n{}", name, bindings.to_text());
+        }
+        text
+    }
+
+    // Each `[set]` line starts the bindings of that set.
+    fn parse(contents: &str) -> Result<Controls, String> {
+        let mut controls = Controls::new();
+        let mut set = None;
+        for line in contents.lines().map(str::trim) {
+            if let Some(name) = line.strip_prefix('[').and_then(|line| line.strip_suffix(']')) {
+                let index = Self::SET_NAMES.iter().position(|&set_name| set_name == name)
+                    .ok_or_else(|| format!("Unknown controls: {}", name))?;
+                set = Some(index);
+            } else if let Some(set) = set {
+                controls.sets[set].apply(line)?;
+            } else if !line.is_empty() && !line.starts_with('#') {
+                return Err(format!("Binding outside of a set: {}", line));
+            }
+        }
+        Ok(controls)
+    }
+}
+
+impl Default for Controls {
+    fn default() -> Controls {
+        Controls::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let mut controls = Controls::new();
+        controls.set_mut(2).bind(Action::Hold, Key::Space);
+        let restored = Controls::parse(&controls.to_text()).unwrap();
+        assert_eq!(restored.to_text(), controls.to_text());
+    }
+
+    #[test]
+    fn missing_sets_keep_defaults() {
+        let controls = Controls::parse("# r0t0blocks controls 1\n\n[Player 1]\nhold: Space W\n").unwrap();
+        assert_eq!(controls.set(1).keys(Action::Hold), &[Key::Space, Key::W]);
+        assert_eq!(controls.set(1).keys(Action::HardDrop), &[]);
+        assert_eq!(controls.set(1).keys(Action::MoveLeft), &[Key::A]);
+        assert_eq!(controls.set(0).to_text(), Controls::default_set(0).to_text());
+        assert_eq!(controls.set(2).to_text(), Controls::default_set(2).to_text());
+    }
+
+    #[test]
+    fn parse_errors() {
+        let errors = [
+            ("[Player 3]\n", "Unknown controls: Player 3"),
+            ("hold: C\n", "Binding outside of a set: hold: C"),
+            ("[Solo]\nhold C\n", "Malformed binding: hold C"),
+            ("[Solo]\nspin: X\n", "Unknown action: spin"),
+            ("[Solo]\nhold: Shift\n", "Unknown key: Shift"),
+        ];
+        for &(text, error) in errors.iter() {
+            assert_eq!(Controls::parse(text).err(), Some(error.to_string()), "{}", text);
+        }
+    }
+}
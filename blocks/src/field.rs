@@ -1,45 +1,117 @@
 use engine::base::Number;
 use engine::geometry::Point;
 
-use crate::tetromino::{Frame, FRAME_SIDE};
+use crate::piece::Frame;
 use std::convert::TryFrom;
 
-const FIELD_WIDTH: usize = 10;
-const FIELD_HEIGHT: usize = 18;
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum Cell {
+    Empty,
+    /// Index of the piece in its set.
+    Piece(usize),
+    Garbage,
+    /// Free for modes to mark cells their own way.
+    Custom(u8),
+}
+
+impl Cell {
+    /// Short text form used in saved games, naming pieces by their letters.
+    pub fn token(self, letters: &[char]) -> String {
+        match self {
+            Cell::Empty => ".".to_string(),
+            Cell::Piece(index) => letters[index].to_string(),
+            Cell::Garbage => "#".to_string(),
+            Cell::Custom(value) => value.to_string(),
+        }
+    }
 
+    pub fn from_token(token: &str, letters: &[char]) -> Option<Cell> {
+        match token {
+            "." => Some(Cell::Empty),
+            "#" => Some(Cell::Garbage),
+            _ => {
+                let mut chars = token.chars();
+                let index = chars.next().and_then(|letter| letters.iter().position(|&x| x == letter));
+                match (index, chars.next()) {
+                    (Some(index), None) => Some(Cell::Piece(index)),
+                    _ => token.parse().ok().map(Cell::Custom),
+                }
+            }
+        }
+    }
+}
+
+/// Rows are counted from the top of the hidden buffer, so the visible area
+/// starts at `hidden_rows()`.
+#[derive(Clone)]
 pub struct Field {
-    squares: [bool; FIELD_WIDTH * FIELD_HEIGHT],
+    width: usize,
+    height: usize,
+    hidden_rows: usize,
+    squares: Vec<Cell>,
 }
 
 impl Field {
-    pub fn new() -> Field {
+    pub fn new(width: usize, visible_rows: usize, hidden_rows: usize) -> Field {
+        let height = visible_rows + hidden_rows;
         Field {
-            squares: [false; FIELD_WIDTH * FIELD_HEIGHT],
+            width,
+            height,
+            hidden_rows,
+            squares: vec![Cell::Empty; width * height],
         }
     }
 
-    pub const fn width() -> Number {
-        FIELD_WIDTH as Number
+    pub fn width(&self) -> Number {
+        self.width as Number
     }
 
-    pub const fn height() -> Number {
-        FIELD_HEIGHT as Number
+    /// Total height including the hidden buffer.
+    pub fn height(&self) -> Number {
+        self.height as Number
     }
 
-    pub fn is_filled(&self, p: Point) -> bool {
+    pub fn hidden_rows(&self) -> Number {
+        self.hidden_rows as Number
+    }
+
+    pub fn visible_rows(&self) -> Number {
+        (self.height - self.hidden_rows) as Number
+    }
+
+    /// Cells outside the field are empty.
+    pub fn cell(&self, p: Point) -> Cell {
         if let (Ok(x), Ok(y)) = (usize::try_from(p.x), usize::try_from(p.y)) {
-            if x < FIELD_WIDTH || y < FIELD_HEIGHT {
-                return self.squares[crate::index(x, y, FIELD_WIDTH)];
+            if x < self.width && y < self.height {
+                return self.squares[crate::index(x, y, self.width)];
             }
         }
-        return false;
+        return Cell::Empty;
+    }
+
+    pub fn is_filled(&self, p: Point) -> bool {
+        self.cell(p) != Cell::Empty
+    }
+
+    pub fn set_cell(&mut self, p: Point, cell: Cell) {
+        if p.x >= 0 && p.x < self.width() && p.y >= 0 && p.y < self.height() {
+            self.squares[crate::index(p.x as usize, p.y as usize, self.width)] = cell;
+        }
+    }
+
+    /// Like `is_filled`, but walls and floor count as occupied.
+    pub fn is_occupied(&self, p: Point) -> bool {
+        if p.x < 0 || p.x >= self.width() || p.y >= self.height() {
+            return true;
+        }
+        self.is_filled(p)
     }
 
     pub fn is_line_filled(&self, y: Number) -> bool {
         if let Ok(y) = usize::try_from(y) {
-            if y < FIELD_HEIGHT {
-                for x in (y * FIELD_WIDTH)..((y + 1) * FIELD_WIDTH) {
-                    if !self.squares[x] {
+            if y < self.height {
+                for x in (y * self.width)..((y + 1) * self.width) {
+                    if self.squares[x] == Cell::Empty {
                         return false;
                     }
                 }
@@ -50,7 +122,7 @@ impl Field {
     }
 
     pub fn is_any_line_filled(&self) -> bool {
-        for y in 0..Field::height() {
+        for y in 0..self.height() {
             if self.is_line_filled(y) {
                 return true;
             }
@@ -60,8 +132,8 @@ impl Field {
 
     pub fn clean_filled_lines(&mut self) -> Number {
         let mut filled_lines: Number = 0;
-        let mut read_line = FIELD_HEIGHT;
-        let mut first_write_line = FIELD_HEIGHT - 1;
+        let mut read_line = self.height;
+        let mut first_write_line = self.height - 1;
         let mut last_write_line = first_write_line + 1;
         loop {
             read_line -= 1;
@@ -71,8 +143,8 @@ impl Field {
                 filled_lines += 1;
             } else {
                 if first_write_line >= last_write_line {
-                    for i in 0..FIELD_WIDTH {
-                        self.squares[crate::index(i, first_write_line, FIELD_WIDTH)] = self.squares[crate::index(i, read_line, FIELD_WIDTH)];
+                    for i in 0..self.width {
+                        self.squares[crate::index(i, first_write_line, self.width)] = self.squares[crate::index(i, read_line, self.width)];
                     }
 
                     first_write_line -= 1;
@@ -90,8 +162,8 @@ impl Field {
         }
         if first_write_line >= last_write_line {
             for j in last_write_line..=first_write_line {
-                for i in 0..FIELD_WIDTH {
-                    self.squares[crate::index(i, j, FIELD_WIDTH)] = false;
+                for i in 0..self.width {
+                    self.squares[crate::index(i, j, self.width)] = Cell::Empty;
                 }
             }
         }
@@ -99,15 +171,33 @@ impl Field {
         filled_lines
     }
 
-    pub fn copy_frame(&mut self, frame: &Frame, p: Point) {
-        for j in 0..(FRAME_SIDE as Number) {
-            for i in 0..(FRAME_SIDE as Number) {
+    /// Pushes the stack up and fills the bottom `count` rows with garbage
+    /// open at column `hole`. Returns `false` when filled cells were pushed
+    /// out of the top.
+    pub fn insert_garbage(&mut self, count: Number, hole: Number) -> bool {
+        let count = (count.max(0) as usize).min(self.height);
+        let removed = count * self.width;
+        let is_overflow = self.squares[..removed].iter().any(|&cell| cell != Cell::Empty);
+
+        self.squares.drain(..removed);
+        for _ in 0..count {
+            for x in 0..self.width() {
+                self.squares.push(if x == hole { Cell::Empty } else { Cell::Garbage });
+            }
+        }
+
+        !is_overflow
+    }
+
+    pub fn copy_frame(&mut self, frame: &Frame, p: Point, cell: Cell) {
+        for j in 0..frame.height() {
+            for i in 0..frame.width() {
                 if frame.is_filled(Point::new(i, j)) {
                     let x = i + p.x;
                     let y = j + p.y;
 
-                    if x >= 0 && x < Self::width() && y >= 0 && y < Self::height() {
-                        self.squares[crate::index(x as usize, y as usize, FIELD_WIDTH)] = true;
+                    if x >= 0 && x < self.width() && y >= 0 && y < self.height() {
+                        self.squares[crate::index(x as usize, y as usize, self.width)] = cell;
                     }
                 }
             }
@@ -116,26 +206,26 @@ impl Field {
 
     pub fn is_collide(&self, frame: &Frame, p: Point) -> bool {
         let Point {x, y} = p;
-        if x + (FRAME_SIDE as Number) <= 0 {
+        if x + frame.width() <= 0 {
             return true;
         }
-        if x >= FIELD_WIDTH as Number {
+        if x >= self.width() {
             return true;
         }
-        if y >= FIELD_HEIGHT as Number {
+        if y >= self.height() {
             return true;
         }
 
-        for j in 0..(FRAME_SIDE as Number) {
-            for i in 0..(FRAME_SIDE as Number) {
+        for j in 0..frame.height() {
+            for i in 0..frame.width() {
                 if frame.is_filled(Point::new(i, j)) {
                     if x + (i) < 0 {
                         return  true;
                     }
-                    if x + i >= FIELD_WIDTH as Number {
+                    if x + i >= self.width() {
                         return true;
                     }
-                    if y + j >= FIELD_HEIGHT as Number {
+                    if y + j >= self.height() {
                         return true;
                     }
 
@@ -150,6 +240,113 @@ impl Field {
     }
 
     pub fn clear(&mut self) {
-        self.squares.fill(false);
+        self.squares.fill(Cell::Empty);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LETTERS: [char; 1] = ['T'];
+
+    fn field(rows: &[&str]) -> Field {
+        let mut field = Field::new(rows[0].len(), rows.len(), 0);
+        for (y, row) in rows.iter().enumerate() {
+            for (x, token) in row.chars().enumerate() {
+                let cell = Cell::from_token(&token.to_string(), &LETTERS).unwrap();
+                field.set_cell(Point::new(x as Number, y as Number), cell);
+            }
+        }
+        field
+    }
+
+    fn rows(field: &Field) -> Vec<String> {
+        (0..field.height())
+            .map(|y| (0..field.width()).map(|x| field.cell(Point::new(x, y)).token(&LETTERS)).collect())
+            .collect()
+    }
+
+    #[test]
+    fn filled_lines_are_cleared() {
+        let mut field = field(&[
+            "T...",
+            "####",
+            ".T..",
+            "####",
+            "####",
+            "..#.",
+        ]);
+        assert_eq!(field.clean_filled_lines(), 3);
+        assert_eq!(rows(&field), vec![
+            "....",
+            "....",
+            "....",
+            "T...",
+            ".T..",
+            "..#.",
+        ]);
+    }
+
+    #[test]
+    fn nothing_to_clear() {
+        let mut field = field(&[
+            "....",
+            "T.T.",
+            ".###",
+        ]);
+        assert_eq!(field.clean_filled_lines(), 0);
+        assert_eq!(rows(&field), vec![
+            "....",
+            "T.T.",
+            ".###",
+        ]);
+    }
+
+    #[test]
+    fn garbage_rises_with_hole() {
+        let mut field = field(&[
+            "....",
+            "....",
+            "T...",
+            "TT..",
+        ]);
+        assert!(field.insert_garbage(2, 2));
+        assert_eq!(rows(&field), vec![
+            "T...",
+            "TT..",
+            "##.#",
+            "##.#",
+        ]);
+        assert!(!field.is_any_line_filled());
+    }
+
+    #[test]
+    fn garbage_overflows_top() {
+        let mut field = field(&[
+            "....",
+            "T...",
+            "TT..",
+        ]);
+        assert!(!field.insert_garbage(2, 0));
+        assert_eq!(rows(&field), vec![
+            "TT..",
+            ".###",
+            ".###",
+        ]);
+    }
+
+    #[test]
+    fn garbage_count_is_capped() {
+        let mut field = field(&[
+            "....",
+            "....",
+        ]);
+        assert!(field.insert_garbage(5, 3));
+        assert_eq!(rows(&field), vec![
+            "###.",
+            "###.",
+        ]);
+        assert!(field.insert_garbage(-1, 0));
     }
 }
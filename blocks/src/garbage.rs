@@ -0,0 +1,177 @@
+use std::cmp::min;
+use std::collections::VecDeque;
+
+use engine::base::Number;
+
+use crate::scoring::{Award, TSpin};
+
+// Extra lines for each step of a combo, capped at the last entry.
+const COMBO_LINES: [Number; 12] = [0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 4, 5];
+
+/// Garbage lines a clear sends to the opponent before cancelling.
+pub fn attack(award: &Award) -> Number {
+    let clear = award.clear;
+    let lines = match (clear.t_spin, clear.lines) {
+        (_, 0) => return 0,
+        (TSpin::None, 1) => 0,
+        (TSpin::None, 2) => 1,
+        (TSpin::None, 3) => 2,
+        (TSpin::None, _) => 4,
+        (TSpin::Mini, lines) => lines - 1,
+        (TSpin::Full, lines) => 2 * lines,
+    };
+
+    let back_to_back = if award.is_back_to_back { 1 } else { 0 };
+    let combo = COMBO_LINES[min(award.combo.max(0) as usize, COMBO_LINES.len() - 1)];
+    lines + back_to_back + combo
+}
+
+/// Attacks waiting to rise into a player's field, oldest first.
+pub struct GarbageQueue {
+    attacks: VecDeque<Number>,
+}
+
+impl GarbageQueue {
+    pub fn new() -> GarbageQueue {
+        GarbageQueue {
+            attacks: VecDeque::new(),
+        }
+    }
+
+    pub fn push(&mut self, lines: Number) {
+        if lines > 0 {
+            self.attacks.push_back(lines);
+        }
+    }
+
+    pub fn attacks(&self) -> impl Iterator<Item = Number> + '_ {
+        self.attacks.iter().copied()
+    }
+
+    pub fn total(&self) -> Number {
+        self.attacks.iter().sum()
+    }
+
+    /// Cancels queued lines with an outgoing attack, oldest first, and
+    /// returns what is left of the attack to send.
+    pub fn cancel(&mut self, lines: Number) -> Number {
+        let mut lines = lines;
+        while lines > 0 {
+            let front = match self.attacks.front_mut() {
+                Some(front) => front,
+                None => break,
+            };
+            let cancelled = min(*front, lines);
+            *front -= cancelled;
+            lines -= cancelled;
+            if *front == 0 {
+                self.attacks.pop_front();
+            }
+        }
+        lines
+    }
+
+    /// Takes attacks worth up to `max_lines`, splitting the last one if it
+    /// does not fit.
+    pub fn take(&mut self, max_lines: Number) -> Vec<Number> {
+        let mut taken = Vec::new();
+        let mut remain = max_lines;
+        while remain > 0 {
+            let front = match self.attacks.front_mut() {
+                Some(front) => front,
+                None => break,
+            };
+            let lines = min(*front, remain);
+            *front -= lines;
+            remain -= lines;
+            taken.push(lines);
+            if *front == 0 {
+                self.attacks.pop_front();
+            }
+        }
+        taken
+    }
+}
+
+impl Default for GarbageQueue {
+    fn default() -> GarbageQueue {
+        GarbageQueue::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scoring::Clear;
+
+    fn award(lines: Number, t_spin: TSpin, is_back_to_back: bool, combo: Number) -> Award {
+        Award {
+            clear: Clear { lines, t_spin },
+            points: 0,
+            is_back_to_back,
+            combo,
+        }
+    }
+
+    #[test]
+    fn attack_table() {
+        let table = [
+            (0, TSpin::None, 0),
+            (1, TSpin::None, 0),
+            (2, TSpin::None, 1),
+            (3, TSpin::None, 2),
+            (4, TSpin::None, 4),
+            (0, TSpin::Mini, 0),
+            (1, TSpin::Mini, 0),
+            (2, TSpin::Mini, 1),
+            (0, TSpin::Full, 0),
+            (1, TSpin::Full, 2),
+            (2, TSpin::Full, 4),
+            (3, TSpin::Full, 6),
+        ];
+        for &(lines, t_spin, expected) in &table {
+            assert_eq!(attack(&award(lines, t_spin, false, 0)), expected, "{} lines {:?}", lines, t_spin);
+        }
+    }
+
+    #[test]
+    fn back_to_back_and_combo() {
+        assert_eq!(attack(&award(4, TSpin::None, true, 0)), 5);
+        assert_eq!(attack(&award(2, TSpin::Full, true, 0)), 5);
+        assert_eq!(attack(&award(1, TSpin::None, false, 2)), 1);
+        assert_eq!(attack(&award(1, TSpin::None, false, 4)), 2);
+        assert_eq!(attack(&award(2, TSpin::None, true, 11)), 7);
+        assert_eq!(attack(&award(2, TSpin::None, false, 100)), 6);
+        assert_eq!(attack(&award(0, TSpin::None, true, 5)), 0);
+    }
+
+    #[test]
+    fn cancel_oldest_first() {
+        let mut queue = GarbageQueue::new();
+        queue.push(2);
+        queue.push(0);
+        queue.push(3);
+        assert_eq!(queue.total(), 5);
+
+        assert_eq!(queue.cancel(3), 0);
+        assert_eq!(queue.attacks().collect::<Vec<_>>(), vec![2]);
+
+        assert_eq!(queue.cancel(4), 2);
+        assert_eq!(queue.total(), 0);
+        assert_eq!(queue.cancel(1), 1);
+    }
+
+    #[test]
+    fn take_splits_last_attack() {
+        let mut queue = GarbageQueue::new();
+        queue.push(2);
+        queue.push(3);
+        queue.push(1);
+
+        assert_eq!(queue.take(4), vec![2, 2]);
+        assert_eq!(queue.attacks().collect::<Vec<_>>(), vec![1, 1]);
+        assert_eq!(queue.take(0), Vec::<Number>::new());
+        assert_eq!(queue.take(8), vec![1, 1]);
+        assert_eq!(queue.total(), 0);
+    }
+}
@@ -0,0 +1,249 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use engine::base::Number;
+
+use crate::mode::Ranking;
+use crate::storage::{read_optional, write_atomic};
+
+pub const TABLE_LEN: usize = 10;
+pub const MAX_NAME_LEN: usize = 8;
+
+const HEADER: &str = "r0t0blocks highscores 1";
+
+#[derive(Clone, Debug)]
+pub struct HighScore {
+    pub name: String,
+    pub score: Number,
+    pub lines: Number,
+    pub level: Number,
+    pub ticks: Number,
+    /// Seconds since the Unix epoch.
+    pub date: u64,
+}
+
+impl HighScore {
+    fn is_not_worse(&self, other: &HighScore, ranking: Ranking) -> bool {
+        match ranking {
+            Ranking::Score => (self.score, -self.ticks) >= (other.score, -other.ticks),
+            Ranking::Time => (-self.ticks, self.score) >= (-other.ticks, other.score),
+        }
+    }
+
+    pub fn date_text(&self) -> String {
+        format_date(self.date)
+    }
+}
+
+/// Top results per game mode, keyed by the mode name.
+#[derive(Default)]
+pub struct HighScores {
+    tables: BTreeMap<String, Vec<HighScore>>,
+}
+
+impl HighScores {
+    pub fn new() -> HighScores {
+        HighScores {
+            tables: BTreeMap::new(),
+        }
+    }
+
+    pub fn table(&self, mode_name: &str) -> &[HighScore] {
+        self.tables.get(mode_name).map_or(&[], |table| table.as_slice())
+    }
+
+    /// Position the entry would take in the table, if it makes the cut.
+    pub fn rank(&self, mode_name: &str, ranking: Ranking, entry: &HighScore) -> Option<usize> {
+        let rank = self.table(mode_name).iter()
+            .take_while(|other| other.is_not_worse(entry, ranking))
+            .count();
+        if rank < TABLE_LEN {
+            Some(rank)
+        } else {
+            None
+        }
+    }
+
+    pub fn insert(&mut self, mode_name: &str, ranking: Ranking, entry: HighScore) -> Option<usize> {
+        let rank = self.rank(mode_name, ranking, &entry)?;
+        let table = self.tables.entry(mode_name.to_string()).or_default();
+        table.insert(rank, entry);
+        table.truncate(TABLE_LEN);
+        Some(rank)
+    }
+
+    pub fn load(path: &Path) -> Result<HighScores, String> {
+        match read_optional(path)? {
+            Some(contents) => Self::parse(&contents),
+            None => Ok(HighScores::new()),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        write_atomic(path, &self.to_text())
+    }
+
+    fn parse(contents: &str) -> Result<HighScores, String> {
+        let mut lines = contents.lines();
+        if lines.next() != Some(HEADER) {
+            return Err("Not a high-score file".into());
+        }
+
+        let mut high_scores = HighScores::new();
+        for (i, line) in lines.enumerate().filter(|(_, line)| !line.is_empty()) {
+            let (mode_name, entry) = parse_entry(line).ok_or_else(|| format!("Malformed high score on line {}", i + 2))?;
+            let table = high_scores.tables.entry(mode_name.to_string()).or_default();
+            if table.len() < TABLE_LEN {
+                table.push(entry);
+            }
+        }
+        Ok(high_scores)
+    }
+
+    fn to_text(&self) -> String {
+        let mut text = format!("{}\n", HEADER);
+        for (mode_name, table) in self.tables.iter() {
+            for entry in table.iter() {
+                text += &format!("{}\t{}\t{}\t{}\t{}\t{}\t{}\n",
+                    mode_name, entry.name, entry.score, entry.lines, entry.level, entry.ticks, entry.date);
+            }
+        }
+        text
+    }
+}
+
+fn parse_entry(line: &str) -> Option<(&str, HighScore)> {
+    let fields: Vec<&str> = line.split('\t').collect();
+    if fields.len() != 7 || fields[1].chars().count() > MAX_NAME_LEN {
+        return None;
+    }
+
+    let entry = HighScore {
+        name: fields[1].to_string(),
+        score: fields[2].parse().ok()?,
+        lines: fields[3].parse().ok()?,
+        level: fields[4].parse().ok()?,
+        ticks: fields[5].parse().ok()?,
+        date: fields[6].parse().ok()?,
+    };
+    Some((fields[0], entry))
+}
+
+pub fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |duration| duration.as_secs())
+}
+
+/// Formats a Unix timestamp as a UTC YYYY-MM-DD date.
+fn format_date(timestamp: u64) -> String {
+    // days to civil date, see http://howardhinnant.github.io/date_algorithms.html
+    let days = (timestamp / 86400) as i64 + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str, score: Number, ticks: Number) -> HighScore {
+        HighScore {
+            name: name.to_string(),
+            score,
+            lines: 40,
+            level: 1,
+            ticks,
+            date: 0,
+        }
+    }
+
+    fn names(high_scores: &HighScores, mode_name: &str) -> Vec<String> {
+        high_scores.table(mode_name).iter().map(|entry| entry.name.clone()).collect()
+    }
+
+    #[test]
+    fn ranked_by_score() {
+        let mut high_scores = HighScores::new();
+        assert_eq!(high_scores.insert("Marathon", Ranking::Score, entry("a", 100, 50)), Some(0));
+        assert_eq!(high_scores.insert("Marathon", Ranking::Score, entry("b", 300, 50)), Some(0));
+        assert_eq!(high_scores.insert("Marathon", Ranking::Score, entry("c", 200, 50)), Some(1));
+        // ties go to the faster game, then to the older one
+        assert_eq!(high_scores.insert("Marathon", Ranking::Score, entry("d", 200, 40)), Some(1));
+        assert_eq!(high_scores.insert("Marathon", Ranking::Score, entry("e", 200, 40)), Some(2));
+        assert_eq!(names(&high_scores, "Marathon"), ["b", "d", "e", "c", "a"]);
+    }
+
+    #[test]
+    fn ranked_by_time() {
+        let mut high_scores = HighScores::new();
+        high_scores.insert("Sprint", Ranking::Time, entry("a", 0, 300));
+        high_scores.insert("Sprint", Ranking::Time, entry("b", 0, 100));
+        high_scores.insert("Sprint", Ranking::Time, entry("c", 0, 200));
+        high_scores.insert("Sprint", Ranking::Time, entry("d", 10, 200));
+        assert_eq!(names(&high_scores, "Sprint"), ["b", "d", "c", "a"]);
+        assert!(high_scores.table("Marathon").is_empty());
+    }
+
+    #[test]
+    fn table_keeps_top_ten() {
+        let mut high_scores = HighScores::new();
+        for score in 1..=TABLE_LEN as Number {
+            high_scores.insert("Ultra", Ranking::Score, entry(&score.to_string(), score * 10, 0));
+        }
+        assert_eq!(high_scores.rank("Ultra", Ranking::Score, &entry("x", 5, 0)), None);
+        assert_eq!(high_scores.insert("Ultra", Ranking::Score, entry("x", 5, 0)), None);
+        assert_eq!(high_scores.insert("Ultra", Ranking::Score, entry("y", 15, 0)), Some(9));
+
+        let table = high_scores.table("Ultra");
+        assert_eq!(table.len(), TABLE_LEN);
+        assert_eq!(table[0].score, 100);
+        assert_eq!(table[TABLE_LEN - 1].score, 15);
+    }
+
+    #[test]
+    fn save_and_load() {
+        let mut high_scores = HighScores::new();
+        high_scores.insert("Marathon", Ranking::Score, HighScore { date: 1_700_000_000, ..entry("alice", 1000, 5000) });
+        high_scores.insert("Sprint", Ranking::Time, entry("bob", 0, 3000));
+
+        let path = std::env::temp_dir().join(format!("r0t0blocks-highscores-{}", std::process::id()));
+        high_scores.save(&path).unwrap();
+        let loaded = HighScores::load(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        let marathon = &loaded.table("Marathon")[0];
+        assert_eq!((marathon.name.as_str(), marathon.score, marathon.lines), ("alice", 1000, 40));
+        assert_eq!((marathon.level, marathon.ticks, marathon.date_text()), (1, 5000, "2023-11-14".to_string()));
+        assert_eq!(names(&loaded, "Sprint"), ["bob"]);
+        assert_eq!(loaded.to_text(), high_scores.to_text());
+    }
+
+    #[test]
+    fn missing_file_is_empty() {
+        let path = std::env::temp_dir().join("r0t0blocks-no-such-highscores");
+        assert!(HighScores::load(&path).unwrap().table("Marathon").is_empty());
+    }
+
+    #[test]
+    fn malformed_lines() {
+        assert!(HighScores::parse("").is_err());
+        assert!(HighScores::parse("r0t0blocks highscores 2\n").is_err());
+        for line in &[
+            "Marathon\ta\t1\t2\t3\t4",
+            "Marathon\ta\t1\t2\t3\t4\t5\t6",
+            "Marathon\tnineteen\t1\t2\t3\t4\t5x",
+            "Marathon\ttoo long name\t1\t2\t3\t4\t5",
+            "Marathon\ta\tmany\t2\t3\t4\t5",
+        ] {
+            assert!(HighScores::parse(&format!("{}\n{}\n", HEADER, line)).is_err(), "{}", line);
+        }
+        assert!(HighScores::parse(&format!("{}\n\nMarathon\ta\t1\t2\t3\t4\t5\n", HEADER)).is_ok());
+    }
+}
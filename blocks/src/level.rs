@@ -0,0 +1,218 @@
+use std::cmp::{max, min};
+
+use engine::base::Number;
+
+use crate::config::TICKS_PER_SECOND;
+
+/// Gravity is measured in 1/65536 of a cell per tick.
+pub const GRAVITY_UNIT: Number = 65536;
+
+/// 20G: the piece drops through the whole field in a single tick.
+pub const MAX_GRAVITY: Number = 20 * GRAVITY_UNIT;
+
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum LevelProgression {
+    /// Level goes up every so many cleared lines.
+    Lines(Number),
+    /// Level goes up every so many points.
+    Score(Number),
+    /// Level never changes.
+    Fixed,
+}
+
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum GravityCurve {
+    /// The original r0t0blocks speeds, topping out at one cell per 8 ticks.
+    Classic,
+    /// Guideline curve reaching 20G at level 20.
+    Guideline,
+}
+
+const GUIDELINE_MAX_LEVEL: Number = 20;
+
+const CLASSIC_FALL_PERIODS: [Number; 10] = [120, 60, 50, 40, 34, 28, 24, 16, 10, 8];
+
+impl GravityCurve {
+    pub fn gravity(self, level: Number) -> Number {
+        let level = max(level, 1);
+        match self {
+            GravityCurve::Classic => {
+                let index = min(level as usize - 1, CLASSIC_FALL_PERIODS.len() - 1);
+                GRAVITY_UNIT / CLASSIC_FALL_PERIODS[index]
+            }
+            // the formula falls short of 20G at level 20 and goes negative
+            // further up, so it stops there
+            GravityCurve::Guideline if level >= GUIDELINE_MAX_LEVEL => MAX_GRAVITY,
+            GravityCurve::Guideline => {
+                let steps = (level - 1) as f64;
+                let seconds_per_cell = (0.8 - steps * 0.007).powf(steps);
+                let cells_per_tick = 1.0 / (seconds_per_cell * TICKS_PER_SECOND as f64);
+                min((cells_per_tick * GRAVITY_UNIT as f64) as Number, MAX_GRAVITY)
+            }
+        }
+    }
+}
+
+/// Accumulates sub-cell gravity and hands out whole cells to fall.
+pub struct Gravity {
+    per_tick: Number,
+    accumulator: Number,
+}
+
+impl Gravity {
+    pub fn new(per_tick: Number) -> Gravity {
+        Gravity {
+            per_tick,
+            accumulator: 0,
+        }
+    }
+
+    pub fn set(&mut self, per_tick: Number) {
+        self.per_tick = per_tick;
+    }
+
+    pub fn reset(&mut self) {
+        self.accumulator = 0;
+    }
+
+    pub fn accumulator(&self) -> Number {
+        self.accumulator
+    }
+
+    pub fn set_accumulator(&mut self, accumulator: Number) {
+        self.accumulator = accumulator.clamp(0, GRAVITY_UNIT - 1);
+    }
+
+    pub fn tick(&mut self) -> Number {
+        self.accumulator += self.per_tick;
+        let cells = self.accumulator / GRAVITY_UNIT;
+        self.accumulator %= GRAVITY_UNIT;
+        cells
+    }
+}
+
+pub struct Levelling {
+    progression: LevelProgression,
+    start_level: Number,
+    max_level: Number,
+    level: Number,
+    lines: Number,
+}
+
+impl Levelling {
+    pub fn new(progression: LevelProgression, start_level: Number, max_level: Number) -> Levelling {
+        let start_level = max(start_level, 1);
+        Levelling {
+            progression,
+            start_level,
+            max_level,
+            level: start_level,
+            lines: 0,
+        }
+    }
+
+    pub fn level(&self) -> Number {
+        self.level
+    }
+
+    pub fn lines(&self) -> Number {
+        self.lines
+    }
+
+    /// Continues from a saved game.
+    pub fn set_progress(&mut self, level: Number, lines: Number) {
+        self.level = level.clamp(self.start_level, self.max_level);
+        self.lines = max(lines, 0);
+    }
+
+    /// Returns `true` when the level went up.
+    pub fn update(&mut self, cleared_lines: Number, score: Number) -> bool {
+        self.lines += cleared_lines;
+
+        let gained = match self.progression {
+            LevelProgression::Lines(lines) => self.lines / max(lines, 1),
+            LevelProgression::Score(points) => score / max(points, 1),
+            LevelProgression::Fixed => 0,
+        };
+        // the level never goes down, e.g. when starting above level 1
+        let level = min(max(self.start_level + gained, self.level), self.max_level);
+
+        let is_changed = level != self.level;
+        self.level = level;
+        is_changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn guideline_gravity() {
+        assert_eq!(GravityCurve::Guideline.gravity(1), GRAVITY_UNIT / TICKS_PER_SECOND);
+        assert_eq!(GravityCurve::Guideline.gravity(0), GravityCurve::Guideline.gravity(1));
+        assert_eq!(GravityCurve::Guideline.gravity(15), 74276);
+        for &level in &[20, 21, 115, 116, 1000, Number::MAX] {
+            assert_eq!(GravityCurve::Guideline.gravity(level), MAX_GRAVITY, "level {}", level);
+        }
+        for level in 1..200 {
+            assert!(GravityCurve::Guideline.gravity(level) <= GravityCurve::Guideline.gravity(level + 1), "level {}", level);
+        }
+    }
+
+    #[test]
+    fn classic_gravity() {
+        assert_eq!(GravityCurve::Classic.gravity(1), GRAVITY_UNIT / 120);
+        assert_eq!(GravityCurve::Classic.gravity(10), GRAVITY_UNIT / 8);
+        assert_eq!(GravityCurve::Classic.gravity(50), GRAVITY_UNIT / 8);
+    }
+
+    #[test]
+    fn gravity_accumulates() {
+        let mut gravity = Gravity::new(GRAVITY_UNIT / 3);
+        let cells: Vec<Number> = (0..6).map(|_| gravity.tick()).collect();
+        assert_eq!(cells, [0, 0, 0, 1, 0, 0]);
+
+        gravity.set(MAX_GRAVITY);
+        assert_eq!(gravity.tick(), 20);
+    }
+
+    #[test]
+    fn levels_by_lines() {
+        let mut levelling = Levelling::new(LevelProgression::Lines(10), 1, 20);
+        assert!(!levelling.update(9, 0));
+        assert_eq!(levelling.level(), 1);
+        assert!(levelling.update(1, 0));
+        assert_eq!(levelling.level(), 2);
+        assert!(levelling.update(24, 0));
+        assert_eq!((levelling.level(), levelling.lines()), (4, 34));
+        levelling.update(1000, 0);
+        assert_eq!(levelling.level(), 20);
+    }
+
+    #[test]
+    fn levels_from_start_level() {
+        // the first level up comes after as many lines as from level 1
+        let mut levelling = Levelling::new(LevelProgression::Lines(10), 5, 20);
+        assert!(!levelling.update(9, 0));
+        assert!(levelling.update(1, 0));
+        assert_eq!(levelling.level(), 6);
+    }
+
+    #[test]
+    fn levels_by_score() {
+        let mut levelling = Levelling::new(LevelProgression::Score(5000), 1, 10);
+        assert!(!levelling.update(1, 4999));
+        assert!(levelling.update(0, 5000));
+        assert_eq!(levelling.level(), 2);
+        assert!(!levelling.update(0, 0));
+        assert_eq!(levelling.level(), 2);
+    }
+
+    #[test]
+    fn fixed_level() {
+        let mut levelling = Levelling::new(LevelProgression::Fixed, 3, 3);
+        assert!(!levelling.update(100, 100000));
+        assert_eq!(levelling.level(), 3);
+    }
+}
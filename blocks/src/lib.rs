@@ -1,8 +1,23 @@
 pub mod blocks;
-pub mod tetromino;
+pub mod config;
+pub mod controls;
+pub mod garbage;
+pub mod highscore;
+pub mod level;
+pub mod mode;
+pub mod net;
+pub mod piece;
+pub mod randomizer;
+pub mod relay;
+pub mod replay;
+pub mod save;
+pub mod scoring;
+pub mod storage;
+mod bot;
 mod field;
+mod online;
+mod player;
 
 pub const fn index(x: usize, y: usize, row_width: usize) -> usize {
     x + y * row_width
 }
-
@@ -1,19 +1,138 @@
+use std::path::Path;
+
 use engine::{run, RunParams};
+use engine::base::{App, Backend};
+use engine::replay::{Player, Recorder, Replay};
+use engine::terminal::Terminal;
 use r0t0blocks::blocks::State;
-use r0t0blocks::tetromino::create_frames;
+use r0t0blocks::piece::PieceSet;
+use r0t0blocks::config::{Config, MAX_FIELD_HEIGHT, MAX_FIELD_WIDTH, MAX_PREVIEW_COUNT, MIN_FIELD_HEIGHT, MIN_FIELD_WIDTH};
+use r0t0blocks::randomizer::RandomizerKind;
+use r0t0blocks::replay::{parse_replay_header, replay_header};
 use engine::audio::Silence;
 
 fn main() -> Result<(), String> {
-    let frames = create_frames();
-    let mut state = State::new(&frames);
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let is_terminal = args.iter().any(|arg| arg == "--terminal");
+    let debug = args.iter().any(|arg| arg == "--debug");
+    let record_path = option_value(&args, "--record");
+    let replay_path = option_value(&args, "--replay");
+    let connect_address = option_value(&args, "--connect");
+    if connect_address.is_some() && (record_path.is_some() || replay_path.is_some()) {
+        return Err("Online games cannot be recorded or replayed".into());
+    }
+    let mut seed = match option_value(&args, "--seed") {
+        Some(value) => Some(value.parse::<u64>().map_err(|e| format!("Invalid seed: {}", e))?),
+        None => None,
+    };
+    if record_path.is_some() {
+        // a recording is only reproducible with a known seed
+        seed = Some(seed.unwrap_or_else(|| fastrand::u64(..)));
+    }
+
+    let randomizer = match option_value(&args, "--randomizer") {
+        Some(value) => value.parse::<RandomizerKind>()?,
+        None => RandomizerKind::Bag7,
+    };
+
+    let preview_count = match option_value(&args, "--previews") {
+        Some(value) => value.parse::<usize>().map_err(|e| format!("Invalid preview count: {}", e))?,
+        None => Config::default().preview_count,
+    };
+    if preview_count > MAX_PREVIEW_COUNT {
+        return Err(format!("Preview count must be at most {}", MAX_PREVIEW_COUNT));
+    }
+
+    let (field_width, field_height) = match option_value(&args, "--field") {
+        Some(value) => parse_field_size(value)?,
+        None => (Config::default().field_width, Config::default().field_height),
+    };
+
+    let mut config = Config {
+        seed,
+        randomizer,
+        preview_count,
+        field_width,
+        field_height,
+        debug,
+        ..Config::default()
+    };
+    if record_path.is_some() {
+        // saved games and custom controls would not be there on playback
+        config.data_dir = None;
+    }
+
+    let mut pieces = match option_value(&args, "--pieces") {
+        Some(path) => PieceSet::load(Path::new(path))?,
+        None => PieceSet::standard(),
+    };
+
+    // a replay brings its own config and pieces, the options above are ignored
+    let replay = match replay_path {
+        Some(path) => Some(Replay::load(Path::new(path))?),
+        None => None,
+    };
+    if let Some(replay) = &replay {
+        let (replay_config, replay_pieces) = parse_replay_header(replay.header())?;
+        config = replay_config;
+        pieces = replay_pieces;
+    }
+    let header = replay_header(&config, &pieces);
+    let (width_in_tiles, height_in_tiles) = State::screen_size(&config, &pieces);
+
+    let mut state = State::new(&pieces, config);
+    if let Some(address) = connect_address {
+        state.connect(address)?;
+    }
 
     let params = RunParams {
         tileset_path: "assets/tileset_24_24.bmp",
         app_name: "r0t0blocks",
         scale: 1,
-        width_in_tiles: 22,
-        height_in_tiles: 24,
+        width_in_tiles,
+        height_in_tiles,
+        tick_rate: 125,
+        render_rate: None,
+        vsync: true,
+        max_ticks_per_frame: 10,
     };
 
-    run(&mut state, params, |_| Silence)
-}
\ No newline at end of file
+    if let Some(replay) = replay {
+        let mut player = Player::new(&mut state, &replay);
+        run_app(&mut player, params, is_terminal)
+    } else if let Some(path) = record_path {
+        let mut recorder = Recorder::new(&mut state, header);
+        run_app(&mut recorder, params, is_terminal)?;
+        recorder.into_replay().save(Path::new(path))
+    } else {
+        run_app(&mut state, params, is_terminal)
+    }
+}
+
+fn run_app<A: App>(app: &mut A, params: RunParams, is_terminal: bool) -> Result<(), String> {
+    if is_terminal {
+        Terminal::new(params).run(app)
+    } else {
+        run(app, params, |_| Silence)
+    }
+}
+
+fn option_value<'a>(args: &'a [String], name: &str) -> Option<&'a str> {
+    args.iter()
+        .position(|arg| arg == name)
+        .and_then(|i| args.get(i + 1))
+        .map(|value| value.as_str())
+}
+
+// Field size is given as WIDTHxHEIGHT of the visible area, e.g. 10x20.
+fn parse_field_size(value: &str) -> Result<(usize, usize), String> {
+    let invalid = || format!("Invalid field size: {}", value);
+    let (width, height) = value.split_once('x').ok_or_else(invalid)?;
+    let width = width.parse::<usize>().map_err(|_| invalid())?;
+    let height = height.parse::<usize>().map_err(|_| invalid())?;
+
+    if !(MIN_FIELD_WIDTH..=MAX_FIELD_WIDTH).contains(&width) || !(MIN_FIELD_HEIGHT..=MAX_FIELD_HEIGHT).contains(&height) {
+        return Err(format!("Field must be {}x{} to {}x{}", MIN_FIELD_WIDTH, MIN_FIELD_HEIGHT, MAX_FIELD_WIDTH, MAX_FIELD_HEIGHT));
+    }
+    Ok((width, height))
+}
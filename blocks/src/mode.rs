@@ -0,0 +1,367 @@
+use enum_dispatch::enum_dispatch;
+
+use engine::base::Number;
+
+use crate::config::TICKS_PER_SECOND;
+use crate::level::{GravityCurve, LevelProgression};
+
+pub struct Stats {
+    pub score: Number,
+    pub lines: Number,
+    pub level: Number,
+    pub ticks: Number,
+    /// Garbage lines sent to the opponent after cancelling.
+    pub sent: Number,
+}
+
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum Outcome {
+    Cleared,
+    TimeUp,
+    ToppedOut,
+}
+
+/// How results of a mode are ordered in the high-score table.
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum Ranking {
+    Score,
+    Time,
+}
+
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum TopOut {
+    GameOver,
+    ClearField,
+}
+
+#[enum_dispatch]
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum GameMode {
+    Marathon,
+    Sprint,
+    Ultra,
+    Zen,
+    Versus,
+}
+
+impl GameMode {
+    pub const ALL: [GameMode; 5] = [
+        GameMode::Marathon(Marathon),
+        GameMode::Sprint(Sprint),
+        GameMode::Ultra(Ultra),
+        GameMode::Zen(Zen),
+        GameMode::Versus(Versus),
+    ];
+}
+
+#[enum_dispatch(GameMode)]
+pub trait ModeBehavior {
+    fn name(&self) -> &'static str;
+    fn description(&self) -> &'static str;
+    fn progression(&self) -> LevelProgression;
+    fn gravity_curve(&self) -> GravityCurve;
+    fn max_level(&self) -> Number;
+    fn outcome(&self, stats: &Stats) -> Option<Outcome>;
+    fn top_out(&self) -> TopOut;
+    /// How a finished game is ranked, or `None` if it does not qualify.
+    fn ranking(&self, outcome: Outcome) -> Option<Ranking>;
+    fn hud(&self, stats: &Stats) -> Vec<(&'static str, String)>;
+    fn results(&self, stats: &Stats, outcome: Outcome) -> Vec<String>;
+
+    /// Players sharing the screen. With more than one, cleared lines
+    /// send garbage and the last player standing wins.
+    fn player_count(&self) -> usize {
+        1
+    }
+}
+
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub struct Marathon;
+
+const MARATHON_LINES: Number = 150;
+
+impl ModeBehavior for Marathon {
+    fn name(&self) -> &'static str {
+        "Marathon"
+    }
+
+    fn description(&self) -> &'static str {
+        "Clear 150 lines."
+    }
+
+    fn progression(&self) -> LevelProgression {
+        LevelProgression::Lines(10)
+    }
+
+    fn gravity_curve(&self) -> GravityCurve {
+        GravityCurve::Guideline
+    }
+
+    fn max_level(&self) -> Number {
+        20
+    }
+
+    fn outcome(&self, stats: &Stats) -> Option<Outcome> {
+        if stats.lines >= MARATHON_LINES {
+            Some(Outcome::Cleared)
+        } else {
+            None
+        }
+    }
+
+    fn top_out(&self) -> TopOut {
+        TopOut::GameOver
+    }
+
+    fn ranking(&self, _outcome: Outcome) -> Option<Ranking> {
+        Some(Ranking::Score)
+    }
+
+    fn hud(&self, stats: &Stats) -> Vec<(&'static str, String)> {
+        vec![
+            ("Score", stats.score.to_string()),
+            ("Level", stats.level.to_string()),
+            ("Lines", format!("{}/{}", stats.lines, MARATHON_LINES)),
+        ]
+    }
+
+    fn results(&self, stats: &Stats, _outcome: Outcome) -> Vec<String> {
+        vec![
+            format!("Score: {}", stats.score),
+            format!("Lines: {}", stats.lines),
+            format!("Level: {}", stats.level),
+        ]
+    }
+}
+
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub struct Sprint;
+
+const SPRINT_LINES: Number = 40;
+
+impl ModeBehavior for Sprint {
+    fn name(&self) -> &'static str {
+        "Sprint"
+    }
+
+    fn description(&self) -> &'static str {
+        "Clear 40 lines, fast."
+    }
+
+    fn progression(&self) -> LevelProgression {
+        LevelProgression::Fixed
+    }
+
+    fn gravity_curve(&self) -> GravityCurve {
+        GravityCurve::Guideline
+    }
+
+    fn max_level(&self) -> Number {
+        1
+    }
+
+    fn outcome(&self, stats: &Stats) -> Option<Outcome> {
+        if stats.lines >= SPRINT_LINES {
+            Some(Outcome::Cleared)
+        } else {
+            None
+        }
+    }
+
+    fn top_out(&self) -> TopOut {
+        TopOut::GameOver
+    }
+
+    fn ranking(&self, outcome: Outcome) -> Option<Ranking> {
+        if outcome == Outcome::Cleared {
+            Some(Ranking::Time)
+        } else {
+            None
+        }
+    }
+
+    fn hud(&self, stats: &Stats) -> Vec<(&'static str, String)> {
+        vec![
+            ("Time", format_ticks(stats.ticks)),
+            ("Lines", format!("{}/{}", stats.lines, SPRINT_LINES)),
+        ]
+    }
+
+    fn results(&self, stats: &Stats, outcome: Outcome) -> Vec<String> {
+        if outcome == Outcome::Cleared {
+            vec![format!("Time: {}", format_ticks(stats.ticks))]
+        } else {
+            vec![format!("Lines: {}/{}", stats.lines, SPRINT_LINES)]
+        }
+    }
+}
+
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub struct Ultra;
+
+const ULTRA_TICKS: Number = 120 * TICKS_PER_SECOND;
+
+impl ModeBehavior for Ultra {
+    fn name(&self) -> &'static str {
+        "Ultra"
+    }
+
+    fn description(&self) -> &'static str {
+        "Score most in 2 minutes."
+    }
+
+    fn progression(&self) -> LevelProgression {
+        LevelProgression::Fixed
+    }
+
+    fn gravity_curve(&self) -> GravityCurve {
+        GravityCurve::Guideline
+    }
+
+    fn max_level(&self) -> Number {
+        1
+    }
+
+    fn outcome(&self, stats: &Stats) -> Option<Outcome> {
+        if stats.ticks >= ULTRA_TICKS {
+            Some(Outcome::TimeUp)
+        } else {
+            None
+        }
+    }
+
+    fn top_out(&self) -> TopOut {
+        TopOut::GameOver
+    }
+
+    fn ranking(&self, _outcome: Outcome) -> Option<Ranking> {
+        Some(Ranking::Score)
+    }
+
+    fn hud(&self, stats: &Stats) -> Vec<(&'static str, String)> {
+        vec![
+            ("Score", stats.score.to_string()),
+            ("Time", format_ticks(ULTRA_TICKS - stats.ticks)),
+            ("Lines", stats.lines.to_string()),
+        ]
+    }
+
+    fn results(&self, stats: &Stats, _outcome: Outcome) -> Vec<String> {
+        vec![
+            format!("Score: {}", stats.score),
+            format!("Lines: {}", stats.lines),
+        ]
+    }
+}
+
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub struct Zen;
+
+impl ModeBehavior for Zen {
+    fn name(&self) -> &'static str {
+        "Zen"
+    }
+
+    fn description(&self) -> &'static str {
+        "Relax. No top out."
+    }
+
+    // levels up by score through the original speeds, like the endless
+    // game Zen grew out of
+    fn progression(&self) -> LevelProgression {
+        LevelProgression::Score(5000)
+    }
+
+    fn gravity_curve(&self) -> GravityCurve {
+        GravityCurve::Classic
+    }
+
+    fn max_level(&self) -> Number {
+        10
+    }
+
+    fn outcome(&self, _stats: &Stats) -> Option<Outcome> {
+        None
+    }
+
+    fn top_out(&self) -> TopOut {
+        TopOut::ClearField
+    }
+
+    fn ranking(&self, _outcome: Outcome) -> Option<Ranking> {
+        None
+    }
+
+    fn hud(&self, stats: &Stats) -> Vec<(&'static str, String)> {
+        vec![
+            ("Score", stats.score.to_string()),
+            ("Level", stats.level.to_string()),
+            ("Lines", stats.lines.to_string()),
+        ]
+    }
+
+    fn results(&self, stats: &Stats, _outcome: Outcome) -> Vec<String> {
+        vec![
+            format!("Score: {}", stats.score),
+            format!("Lines: {}", stats.lines),
+        ]
+    }
+}
+
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub struct Versus;
+
+impl ModeBehavior for Versus {
+    fn name(&self) -> &'static str {
+        "Versus"
+    }
+
+    fn description(&self) -> &'static str {
+        "Two players, one keyboard."
+    }
+
+    fn progression(&self) -> LevelProgression {
+        LevelProgression::Fixed
+    }
+
+    fn gravity_curve(&self) -> GravityCurve {
+        GravityCurve::Guideline
+    }
+
+    fn max_level(&self) -> Number {
+        1
+    }
+
+    fn outcome(&self, _stats: &Stats) -> Option<Outcome> {
+        None
+    }
+
+    fn top_out(&self) -> TopOut {
+        TopOut::GameOver
+    }
+
+    fn ranking(&self, _outcome: Outcome) -> Option<Ranking> {
+        None
+    }
+
+    fn hud(&self, stats: &Stats) -> Vec<(&'static str, String)> {
+        vec![
+            ("Lines", stats.lines.to_string()),
+            ("Sent", stats.sent.to_string()),
+        ]
+    }
+
+    fn results(&self, stats: &Stats, _outcome: Outcome) -> Vec<String> {
+        vec![format!("{} lines, {} sent", stats.lines, stats.sent)]
+    }
+
+    fn player_count(&self) -> usize {
+        2
+    }
+}
+
+/// Formats game time as m:ss.cc.
+pub fn format_ticks(ticks: Number) -> String {
+    let centiseconds = ticks.max(0) * 100 / TICKS_PER_SECOND;
+    format!("{}:{:02}.{:02}", centiseconds / 6000, centiseconds / 100 % 60, centiseconds % 100)
+}
@@ -0,0 +1,275 @@
+use std::convert::TryFrom;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{Shutdown, TcpStream};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, TryRecvError};
+use std::thread;
+use std::time::Duration;
+
+use engine::base::Number;
+use engine::replay::{read_varint, write_varint};
+
+use crate::config::Config;
+use crate::controls::Action;
+use crate::piece::PieceSet;
+
+pub const PROTOCOL_VERSION: u64 = 2;
+
+// Strings are only rules and refusal reasons, a longer one is garbage.
+const MAX_STRING_LEN: usize = 4096;
+
+/// What the game and the relay say to each other. The relay passes each
+/// player's ticks and attacks on to the opponent unchanged.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum Message {
+    /// First message from a client. Two clients are only paired when they
+    /// play by the same rules.
+    Hello { version: u64, rules: String },
+    /// Sent by the relay to start a match: the seed both games use and
+    /// which player the client is.
+    Start { seed: u64, player: usize },
+    /// One tick of a player's game: the action edges it handled and the
+    /// attacks that arrived before them. Replaying these gives the
+    /// opponent an exact copy of the player's field.
+    Tick { actions: Vec<(Action, bool)>, garbage: Vec<Number> },
+    /// Lines sent to the opponent, already cancelled against incoming ones.
+    Attack { lines: Number },
+    /// The player topped out or gave up.
+    GameOver,
+    /// Sent by the relay to both players once the match is decided.
+    End { winner: Option<usize> },
+    /// Sent by the relay to a client it will not pair.
+    Refused { reason: String },
+}
+
+impl Message {
+    pub fn read<R: Read>(reader: &mut R) -> Result<Message, String> {
+        let message = match read_varint(reader)? {
+            0 => Message::Hello {
+                version: read_varint(reader)?,
+                rules: read_string(reader)?,
+            },
+            1 => Message::Start {
+                seed: read_varint(reader)?,
+                player: read_usize(reader)?,
+            },
+            2 => {
+                let action_count = read_usize(reader)?;
+                let actions = (0..action_count).map(|_| read_action_edge(reader)).collect::<Result<_, _>>()?;
+                let attack_count = read_usize(reader)?;
+                let garbage = (0..attack_count).map(|_| read_number(reader)).collect::<Result<_, _>>()?;
+                Message::Tick { actions, garbage }
+            }
+            3 => Message::Attack {
+                lines: read_number(reader)?,
+            },
+            4 => Message::GameOver,
+            5 => Message::End {
+                winner: match read_usize(reader)? {
+                    0 => None,
+                    player => Some(player - 1),
+                },
+            },
+            6 => Message::Refused {
+                reason: read_string(reader)?,
+            },
+            tag => return Err(format!("Unknown message {}", tag)),
+        };
+        Ok(message)
+    }
+
+    pub fn write<W: Write>(&self, writer: &mut W) -> Result<(), String> {
+        match self {
+            Message::Hello { version, rules } => {
+                write_varint(writer, 0)?;
+                write_varint(writer, *version)?;
+                write_string(writer, rules)
+            }
+            Message::Start { seed, player } => {
+                write_varint(writer, 1)?;
+                write_varint(writer, *seed)?;
+                write_varint(writer, *player as u64)
+            }
+            Message::Tick { actions, garbage } => {
+                write_varint(writer, 2)?;
+                write_varint(writer, actions.len() as u64)?;
+                for &(action, is_pressed) in actions {
+                    write_action_edge(writer, action, is_pressed)?;
+                }
+                write_varint(writer, garbage.len() as u64)?;
+                for &lines in garbage {
+                    write_varint(writer, lines.max(0) as u64)?;
+                }
+                Ok(())
+            }
+            Message::Attack { lines } => {
+                write_varint(writer, 3)?;
+                write_varint(writer, (*lines).max(0) as u64)
+            }
+            Message::GameOver => write_varint(writer, 4),
+            Message::End { winner } => {
+                write_varint(writer, 5)?;
+                write_varint(writer, winner.map_or(0, |player| player as u64 + 1))
+            }
+            Message::Refused { reason } => {
+                write_varint(writer, 6)?;
+                write_string(writer, reason)
+            }
+        }
+    }
+}
+
+/// Settings both players' games must share to stay in step.
+pub fn rules(config: &Config, pieces: &PieceSet) -> String {
+    format!(
+        "{} pieces, {} randomizer, {}x{}+{} field, level {}, lock delay {}/{}",
+        pieces.name(),
+        config.randomizer.name(),
+        config.field_width,
+        config.field_height,
+        config.hidden_rows,
+        config.start_level,
+        config.lock_delay,
+        config.max_lock_resets,
+    )
+}
+
+/// A TCP connection read on its own thread, so it can be polled once a
+/// tick without blocking the game.
+pub struct Connection {
+    stream: TcpStream,
+    incoming: Receiver<Result<Message, String>>,
+}
+
+impl Connection {
+    pub fn connect(address: &str) -> Result<Connection, String> {
+        let stream = TcpStream::connect(address).map_err(|e| format!("Could not connect to {}: {}", address, e))?;
+        Connection::new(stream)
+    }
+
+    pub fn new(stream: TcpStream) -> Result<Connection, String> {
+        stream.set_nodelay(true).map_err(|e| e.to_string())?;
+        let mut reader = BufReader::new(stream.try_clone().map_err(|e| e.to_string())?);
+        let (sender, incoming) = channel();
+
+        thread::spawn(move || loop {
+            let message = match reader.fill_buf() {
+                Ok([]) => Err("Connection closed".to_string()),
+                Ok(_) => Message::read(&mut reader),
+                Err(e) => Err(e.to_string()),
+            };
+            let is_closed = message.is_err();
+            if sender.send(message).is_err() || is_closed {
+                break;
+            }
+        });
+
+        Ok(Connection {
+            stream,
+            incoming,
+        })
+    }
+
+    // Messages go out in one write each, as the stream is not buffered.
+    pub fn send(&mut self, message: &Message) -> Result<(), String> {
+        let mut bytes = Vec::new();
+        message.write(&mut bytes)?;
+        self.stream.write_all(&bytes).map_err(|e| e.to_string())
+    }
+
+    /// The next message if one has arrived. A closed connection is an error.
+    pub fn receive(&mut self) -> Result<Option<Message>, String> {
+        match self.incoming.try_recv() {
+            Ok(message) => message.map(Some),
+            Err(TryRecvError::Empty) => Ok(None),
+            Err(TryRecvError::Disconnected) => Err("Connection closed".into()),
+        }
+    }
+
+    /// Like `receive`, but waits up to `timeout` for a message to arrive.
+    pub fn wait(&mut self, timeout: Duration) -> Result<Option<Message>, String> {
+        match self.incoming.recv_timeout(timeout) {
+            Ok(message) => message.map(Some),
+            Err(RecvTimeoutError::Timeout) => Ok(None),
+            Err(RecvTimeoutError::Disconnected) => Err("Connection closed".into()),
+        }
+    }
+}
+
+// Shutting the socket down also ends the reading thread.
+impl Drop for Connection {
+    fn drop(&mut self) {
+        let _ = self.stream.shutdown(Shutdown::Both);
+    }
+}
+
+fn read_usize<R: Read>(reader: &mut R) -> Result<usize, String> {
+    usize::try_from(read_varint(reader)?).map_err(|e| e.to_string())
+}
+
+fn read_number<R: Read>(reader: &mut R) -> Result<Number, String> {
+    Number::try_from(read_varint(reader)?).map_err(|e| e.to_string())
+}
+
+// Actions are sent by their place in `Action::ALL`, like keys in replays
+// with the pressed flag in the lowest bit.
+fn read_action_edge<R: Read>(reader: &mut R) -> Result<(Action, bool), String> {
+    let code = read_usize(reader)?;
+    let action = Action::ALL.get(code >> 1).copied().ok_or("Unknown action")?;
+    Ok((action, code & 1 != 0))
+}
+
+fn write_action_edge<W: Write>(writer: &mut W, action: Action, is_pressed: bool) -> Result<(), String> {
+    let index = Action::ALL.iter().position(|&a| a == action).unwrap_or(0) as u64;
+    write_varint(writer, index << 1 | is_pressed as u64)
+}
+
+fn read_string<R: Read>(reader: &mut R) -> Result<String, String> {
+    let len = read_usize(reader)?;
+    if len > MAX_STRING_LEN {
+        return Err(format!("String of {} bytes is too long", len));
+    }
+    let mut bytes = vec![0u8; len];
+    reader.read_exact(&mut bytes).map_err(|e| e.to_string())?;
+    String::from_utf8(bytes).map_err(|e| e.to_string())
+}
+
+fn write_string<W: Write>(writer: &mut W, value: &str) -> Result<(), String> {
+    write_varint(writer, value.len() as u64)?;
+    writer.write_all(value.as_bytes()).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn message_round_trip() {
+        let messages = [
+            Message::Hello { version: PROTOCOL_VERSION, rules: "rules".into() },
+            Message::Start { seed: u64::MAX, player: 1 },
+            Message::Tick { actions: vec![(Action::HardDrop, true), (Action::MoveLeft, false)], garbage: vec![2, 4] },
+            Message::Attack { lines: 3 },
+            Message::GameOver,
+            Message::End { winner: None },
+            Message::End { winner: Some(0) },
+            Message::Refused { reason: "no".into() },
+        ];
+        for message in messages.iter() {
+            let mut bytes = Vec::new();
+            message.write(&mut bytes).unwrap();
+            assert_eq!(&Message::read(&mut Cursor::new(bytes)).unwrap(), message);
+        }
+    }
+
+    #[test]
+    fn long_string_is_refused() {
+        let mut bytes = Vec::new();
+        Message::Refused { reason: "x".repeat(MAX_STRING_LEN + 1) }.write(&mut bytes).unwrap();
+        assert!(Message::read(&mut Cursor::new(bytes)).is_err());
+
+        let mut bytes = vec![6];
+        write_varint(&mut bytes, u64::MAX).unwrap();
+        assert!(Message::read(&mut Cursor::new(bytes)).is_err());
+    }
+}
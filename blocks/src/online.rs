@@ -0,0 +1,167 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use engine::base::Number;
+use engine::input::Actions;
+
+use crate::config::TICKS_PER_SECOND;
+use crate::controls::Action;
+use crate::net::{Connection, Message, PROTOCOL_VERSION};
+use crate::player::Player;
+
+struct RemoteTick {
+    actions: Vec<(Action, bool)>,
+    garbage: Vec<Number>,
+}
+
+/// A match against a player on another machine, through the relay.
+///
+/// Each game only runs its own player for real. The opponent's field is
+/// replayed from the ticks they send: the actions they took and the
+/// attacks that reached them, so it ends up exactly like theirs, only a
+/// little late.
+pub struct Online {
+    connection: Connection,
+    address: String,
+    start: Option<(u64, usize)>,
+    remote_actions: Actions<Action>,
+    remote_ticks: VecDeque<RemoteTick>,
+    incoming: Vec<Number>,
+    is_game_over_sent: bool,
+    winner: Option<Option<usize>>,
+}
+
+impl Online {
+    pub fn connect(address: &str, rules: String) -> Result<Online, String> {
+        let mut connection = Connection::connect(address)?;
+        connection.send(&Message::Hello {
+            version: PROTOCOL_VERSION,
+            rules,
+        })?;
+
+        Ok(Online {
+            connection,
+            address: address.to_string(),
+            start: None,
+            remote_actions: Actions::new(),
+            remote_ticks: VecDeque::new(),
+            incoming: Vec::new(),
+            is_game_over_sent: false,
+            winner: None,
+        })
+    }
+
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+
+    /// Seed and player index once the relay has paired us with an opponent.
+    /// Waits for up to a tick, so waiting does not go faster than real time.
+    pub fn wait_for_start(&mut self) -> Result<Option<(u64, usize)>, String> {
+        let timeout = Duration::from_millis(1000 / TICKS_PER_SECOND as u64);
+        match self.connection.wait(timeout)? {
+            Some(Message::Start { player, .. }) if player >= 2 => return Err(format!("Relay started us as player {}", player + 1)),
+            Some(Message::Start { seed, player }) => self.start = Some((seed, player)),
+            Some(Message::Refused { reason }) => return Err(reason),
+            _ => {}
+        }
+        Ok(self.start)
+    }
+
+    pub fn seed(&self) -> Option<u64> {
+        self.start.map(|(seed, _)| seed)
+    }
+
+    pub fn player(&self) -> usize {
+        self.start.map_or(0, |(_, player)| player)
+    }
+
+    /// The winner once the relay has called the match.
+    pub fn winner(&self) -> Option<Option<usize>> {
+        self.winner
+    }
+
+    /// Attacks that arrived since the last tick go in before the input, in
+    /// the same order the opponent's copy of this game will see them.
+    pub fn handle_input(&mut self, players: &mut [Player], actions: &Actions<Action>) -> Result<(), String> {
+        self.receive()?;
+        // the relay hangs up once it has called the match
+        if self.winner.is_some() {
+            return Ok(());
+        }
+
+        let local = &mut players[self.player()];
+        if local.is_topped_out() {
+            return Ok(());
+        }
+        for &lines in self.incoming.iter() {
+            local.receive_garbage(lines);
+        }
+        local.handle_input(actions);
+
+        let actions = actions.edges().collect();
+        let garbage = std::mem::take(&mut self.incoming);
+        self.connection.send(&Message::Tick { actions, garbage })
+    }
+
+    pub fn tick(&mut self, players: &mut [Player]) -> Result<(), String> {
+        let local = &mut players[self.player()];
+        if !local.is_topped_out() {
+            local.tick();
+        }
+        let lines = local.take_outgoing();
+        if lines > 0 {
+            self.connection.send(&Message::Attack { lines })?;
+        }
+        if local.is_topped_out() {
+            self.resign()?;
+        }
+
+        self.replay_remote(players);
+        Ok(())
+    }
+
+    /// Ends the match as lost.
+    pub fn resign(&mut self) -> Result<(), String> {
+        if self.is_game_over_sent {
+            return Ok(());
+        }
+        self.is_game_over_sent = true;
+        self.connection.send(&Message::GameOver)
+    }
+
+    // Messages stop being read once the match is called, so the opponent's
+    // last ticks are replayed before the end.
+    fn receive(&mut self) -> Result<(), String> {
+        while self.winner.is_none() {
+            match self.connection.receive()? {
+                Some(Message::Tick { actions, garbage }) => self.remote_ticks.push_back(RemoteTick { actions, garbage }),
+                Some(Message::Attack { lines }) => self.incoming.push(lines),
+                Some(Message::End { winner }) => self.winner = Some(winner),
+                Some(_) => {}
+                None => break,
+            }
+        }
+        Ok(())
+    }
+
+    // The opponent's outgoing lines arrive as attacks of their own, so the
+    // replayed ones are dropped.
+    fn replay_remote(&mut self, players: &mut [Player]) {
+        let remote = &mut players[1 - self.player()];
+        while let Some(tick) = self.remote_ticks.pop_front() {
+            for lines in tick.garbage {
+                remote.receive_garbage(lines);
+            }
+            for (action, is_pressed) in tick.actions {
+                self.remote_actions.set(action, is_pressed);
+            }
+            remote.handle_input(&self.remote_actions);
+            self.remote_actions.tick();
+            if !remote.is_topped_out() {
+                remote.tick();
+            }
+            remote.take_outgoing();
+        }
+    }
+}
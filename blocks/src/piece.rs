@@ -0,0 +1,461 @@
+use std::convert::TryFrom;
+use std::fs;
+use std::path::Path;
+
+use engine::base::Number;
+use engine::geometry::Point;
+use engine::video::Color;
+
+const STANDARD_SET: &str = include_str!("../../assets/pieces/tetromino.txt");
+const STANDARD_SET_NAME: &str = "tetromino";
+
+// SRS wall kick offsets tried in order, by rotation state before a clockwise
+// rotation. Y points down, so the signs are flipped against the usual tables.
+// Counter-clockwise kicks from state `s` are the negated clockwise kicks
+// into `s`.
+const JLSTZ_KICKS: [[(Number, Number); 5]; 4] = [
+    [(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)],
+    [(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)],
+    [(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)],
+    [(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)],
+];
+
+const I_KICKS: [[(Number, Number); 5]; 4] = [
+    [(0, 0), (-2, 0), (1, 0), (-2, 1), (1, -2)],
+    [(0, 0), (-1, 0), (2, 0), (-1, -2), (2, 1)],
+    [(0, 0), (2, 0), (-1, 0), (2, -1), (-1, 2)],
+    [(0, 0), (1, 0), (-2, 0), (1, 2), (-2, -1)],
+];
+
+const BASIC_KICKS: [(Number, Number); 4] = [(0, 0), (-1, 0), (1, 0), (0, -1)];
+
+/// Wall kicks tried when a rotation is blocked.
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum Kicks {
+    None,
+    /// One step left, right or up.
+    Basic,
+    Srs,
+    SrsI,
+}
+
+impl Kicks {
+    pub fn offsets(self, from: usize, is_clockwise: bool) -> Vec<Point> {
+        let table = match self {
+            Kicks::None => return vec![Point::new(0, 0)],
+            Kicks::Basic => return BASIC_KICKS.iter().map(|&(x, y)| Point::new(x, y)).collect(),
+            Kicks::Srs => &JLSTZ_KICKS,
+            Kicks::SrsI => &I_KICKS,
+        };
+
+        if is_clockwise {
+            table[from % 4].iter().map(|&(x, y)| Point::new(x, y)).collect()
+        } else {
+            let to = (from + 3) % 4;
+            table[to].iter().map(|&(x, y)| Point::new(-x, -y)).collect()
+        }
+    }
+}
+
+/// A square grid holding one rotation state.
+pub struct Frame {
+    side: usize,
+    squares: Vec<bool>,
+}
+
+impl Frame {
+    fn new(side: usize, squares: Vec<bool>) -> Frame {
+        Frame {
+            side,
+            squares,
+        }
+    }
+
+    pub fn width(&self) -> Number {
+        self.side as Number
+    }
+
+    pub fn height(&self) -> Number {
+        self.side as Number
+    }
+
+    pub fn is_filled(&self, p: Point) -> bool {
+        if let (Ok(x), Ok(y)) = (usize::try_from(p.x), usize::try_from(p.y)) {
+            if x < self.side && y < self.side {
+                return self.squares[crate::index(x, y, self.side)];
+            }
+        }
+        return false;
+    }
+
+    fn rotated_clockwise(&self) -> Frame {
+        let side = self.side;
+        let mut squares = vec![false; side * side];
+        for y in 0..side {
+            for x in 0..side {
+                squares[crate::index(side - 1 - y, x, side)] = self.squares[crate::index(x, y, side)];
+            }
+        }
+        Frame::new(side, squares)
+    }
+
+    /// Top-left corner and size of the smallest box around the squares.
+    pub fn bounds(&self) -> (Point, Number, Number) {
+        let filled: Vec<Point> = (0..self.height())
+            .flat_map(|y| (0..self.width()).map(move |x| Point::new(x, y)))
+            .filter(|&p| self.is_filled(p))
+            .collect();
+
+        let min_x = filled.iter().map(|p| p.x).min().unwrap_or(0);
+        let min_y = filled.iter().map(|p| p.y).min().unwrap_or(0);
+        let max_x = filled.iter().map(|p| p.x).max().unwrap_or(-1);
+        let max_y = filled.iter().map(|p| p.y).max().unwrap_or(-1);
+        (Point::new(min_x, min_y), max_x - min_x + 1, max_y - min_y + 1)
+    }
+}
+
+pub struct Piece {
+    /// Identifies the piece in data and save files.
+    pub letter: char,
+    pub color: Color,
+    pub kicks: Kicks,
+    pub has_t_spins: bool,
+    /// Rotation states in clockwise order, starting with the spawn state.
+    pub frames: Vec<Frame>,
+}
+
+pub struct PieceSet {
+    name: String,
+    /// The text the set was parsed from.
+    text: String,
+    pieces: Vec<Piece>,
+}
+
+impl PieceSet {
+    /// The seven tetrominoes.
+    pub fn standard() -> PieceSet {
+        Self::parse(STANDARD_SET_NAME, STANDARD_SET).expect("Built-in piece set is malformed")
+    }
+
+    /// Loads a piece set named after the file.
+    pub fn load(path: &Path) -> Result<PieceSet, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let name = path.file_stem().map_or_else(|| "custom".into(), |stem| stem.to_string_lossy().into_owned());
+        Self::parse(&name, &text).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn len(&self) -> usize {
+        self.pieces.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pieces.is_empty()
+    }
+
+    pub fn get(&self, index: usize) -> &Piece {
+        &self.pieces[index]
+    }
+
+    pub fn letters(&self) -> Vec<char> {
+        self.pieces.iter().map(|piece| piece.letter).collect()
+    }
+
+    /// Size of the box that fits the spawn state of every piece.
+    pub fn preview_size(&self) -> (Number, Number) {
+        self.pieces.iter()
+            .map(|piece| piece.frames[0].bounds())
+            .fold((0, 0), |(width, height), (_, w, h)| (width.max(w), height.max(h)))
+    }
+
+    pub fn parse(name: &str, text: &str) -> Result<PieceSet, String> {
+        let mut pieces = Vec::new();
+        let mut lines = text.lines().enumerate().peekable();
+
+        while let Some((i, line)) = lines.next() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut words = line.split_whitespace();
+            if words.next() != Some("piece") {
+                return Err(format!("Expected a piece on line {}", i + 1));
+            }
+            let letter = words.next().and_then(|word| word.parse::<char>().ok())
+                .filter(|letter| letter.is_ascii_alphabetic())
+                .ok_or_else(|| format!("Piece on line {} needs a one letter name", i + 1))?;
+            let color = words.next().ok_or_else(|| format!("Piece on line {} needs a colour", i + 1)).and_then(parse_color)?;
+
+            let mut kicks = Kicks::Basic;
+            let mut has_t_spins = false;
+            for flag in words {
+                match flag {
+                    "srs" => kicks = Kicks::Srs,
+                    "srs-i" => kicks = Kicks::SrsI,
+                    "no-kicks" => kicks = Kicks::None,
+                    "t-spin" => has_t_spins = true,
+                    _ => return Err(format!("Unknown flag {} on line {}", flag, i + 1)),
+                }
+            }
+
+            // grid rows run until a blank line, `-` starts the next state
+            let mut grids = vec![Vec::new()];
+            while let Some((_, row)) = lines.next_if(|(_, row)| !row.trim().is_empty()) {
+                let row = row.trim();
+                if row == "-" {
+                    grids.push(Vec::new());
+                } else {
+                    grids.last_mut().unwrap().push(row);
+                }
+            }
+
+            let mut frames = grids.iter()
+                .map(|grid| parse_frame(grid))
+                .collect::<Option<Vec<_>>>()
+                .ok_or_else(|| format!("Piece {} has a malformed grid", letter))?;
+            if frames.len() == 1 {
+                for _ in 1..4 {
+                    let frame = frames.last().unwrap().rotated_clockwise();
+                    frames.push(frame);
+                }
+            }
+
+            if frames.iter().any(|frame| frame.side != frames[0].side) {
+                return Err(format!("Rotation states of piece {} differ in size", letter));
+            }
+            if matches!(kicks, Kicks::Srs | Kicks::SrsI) && frames.len() != 4 {
+                return Err(format!("SRS kicks need four rotation states in piece {}", letter));
+            }
+            if has_t_spins && frames[0].side != 3 {
+                return Err(format!("T-spins need a 3x3 grid in piece {}", letter));
+            }
+            if pieces.iter().any(|piece: &Piece| piece.letter == letter) {
+                return Err(format!("Piece {} is defined twice", letter));
+            }
+
+            pieces.push(Piece {
+                letter,
+                color,
+                kicks,
+                has_t_spins,
+                frames,
+            });
+        }
+
+        if pieces.is_empty() {
+            return Err("No pieces in set".into());
+        }
+
+        Ok(PieceSet {
+            name: name.to_string(),
+            text: text.to_string(),
+            pieces,
+        })
+    }
+}
+
+// Rows of `#` and `.`, padded to a square.
+fn parse_frame(rows: &[&str]) -> Option<Frame> {
+    let width = rows.first()?.chars().count();
+    if rows.iter().any(|row| row.chars().count() != width || row.chars().any(|c| c != '#' && c != '.')) {
+        return None;
+    }
+
+    let side = width.max(rows.len());
+    let mut squares = vec![false; side * side];
+    for (y, row) in rows.iter().enumerate() {
+        for (x, square) in row.chars().enumerate() {
+            squares[crate::index(x, y, side)] = square == '#';
+        }
+    }
+
+    if squares.iter().any(|&is_filled| is_filled) {
+        Some(Frame::new(side, squares))
+    } else {
+        None
+    }
+}
+
+fn parse_color(text: &str) -> Result<Color, String> {
+    let color = match text {
+        "white" => Color::WHITE,
+        "gray" => Color::GRAY,
+        "red" => Color::RED,
+        "green" => Color::GREEN,
+        "blue" => Color::BLUE,
+        "yellow" => Color::YELLOW,
+        "cyan" => Color::CYAN,
+        "magenta" => Color::MAGENTA,
+        "orange" => Color::ORANGE,
+        _ => {
+            let hex = text.strip_prefix('#').filter(|hex| hex.len() == 6)
+                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                .ok_or_else(|| format!("Unknown colour: {}", text))?;
+            Color::Rgb((hex >> 16) as u8, (hex >> 8) as u8, hex as u8)
+        }
+    };
+    Ok(color)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // From and to states with the kicks as the SRS tables list them, with
+    // y pointing up.
+    type KickRow = (usize, usize, [(Number, Number); 5]);
+
+    const SRS_JLSTZ: [KickRow; 8] = [
+        (0, 1, [(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)]),
+        (1, 0, [(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)]),
+        (1, 2, [(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)]),
+        (2, 1, [(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)]),
+        (2, 3, [(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)]),
+        (3, 2, [(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)]),
+        (3, 0, [(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)]),
+        (0, 3, [(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)]),
+    ];
+
+    const SRS_I: [KickRow; 8] = [
+        (0, 1, [(0, 0), (-2, 0), (1, 0), (-2, -1), (1, 2)]),
+        (1, 0, [(0, 0), (2, 0), (-1, 0), (2, 1), (-1, -2)]),
+        (1, 2, [(0, 0), (-1, 0), (2, 0), (-1, 2), (2, -1)]),
+        (2, 1, [(0, 0), (1, 0), (-2, 0), (1, -2), (-2, 1)]),
+        (2, 3, [(0, 0), (2, 0), (-1, 0), (2, 1), (-1, -2)]),
+        (3, 2, [(0, 0), (-2, 0), (1, 0), (-2, -1), (1, 2)]),
+        (3, 0, [(0, 0), (1, 0), (-2, 0), (1, -2), (-2, 1)]),
+        (0, 3, [(0, 0), (-1, 0), (2, 0), (-1, 2), (2, -1)]),
+    ];
+
+    fn offsets(kicks: Kicks, from: usize, is_clockwise: bool) -> Vec<(Number, Number)> {
+        kicks.offsets(from, is_clockwise).iter().map(|p| (p.x, p.y)).collect()
+    }
+
+    fn check_kicks(kicks: Kicks, table: &[KickRow]) {
+        for &(from, to, kick_offsets) in table {
+            let is_clockwise = to == (from + 1) % 4;
+            let expected: Vec<(Number, Number)> = kick_offsets.iter().map(|&(x, y)| (x, -y)).collect();
+            assert_eq!(offsets(kicks, from, is_clockwise), expected, "{:?} {} -> {}", kicks, from, to);
+        }
+    }
+
+    #[test]
+    fn srs_kicks() {
+        check_kicks(Kicks::Srs, &SRS_JLSTZ);
+    }
+
+    #[test]
+    fn srs_i_kicks() {
+        check_kicks(Kicks::SrsI, &SRS_I);
+    }
+
+    #[test]
+    fn other_kicks() {
+        assert_eq!(offsets(Kicks::None, 2, false), vec![(0, 0)]);
+        assert_eq!(offsets(Kicks::Basic, 0, true), offsets(Kicks::Basic, 3, false));
+        assert_eq!(offsets(Kicks::Basic, 0, true)[0], (0, 0));
+    }
+
+    fn grid(frame: &Frame) -> Vec<String> {
+        (0..frame.height())
+            .map(|y| (0..frame.width()).map(|x| if frame.is_filled(Point::new(x, y)) { '#' } else { '.' }).collect())
+            .collect()
+    }
+
+    #[test]
+    fn standard_set() {
+        let pieces = PieceSet::standard();
+        assert_eq!(pieces.name(), "tetromino");
+        assert_eq!(pieces.letters(), vec!['I', 'O', 'T', 'J', 'L', 'S', 'Z']);
+        assert!((0..pieces.len()).all(|i| pieces.get(i).frames.len() == 4));
+
+        let t = pieces.get(2);
+        assert_eq!((t.kicks, t.has_t_spins), (Kicks::Srs, true));
+        assert_eq!(grid(&t.frames[1]), [".#.", ".##", ".#."]);
+        assert_eq!(pieces.get(0).kicks, Kicks::SrsI);
+        assert_eq!(pieces.get(1).kicks, Kicks::None);
+        assert_eq!(pieces.preview_size(), (4, 2));
+    }
+
+    #[test]
+    fn bundled_sets() {
+        let sets = [
+            ("domino", include_str!("../../assets/pieces/domino.txt"), 1, 2),
+            ("tromino", include_str!("../../assets/pieces/tromino.txt"), 2, 3),
+            ("pentomino", include_str!("../../assets/pieces/pentomino.txt"), 12, 5),
+        ];
+        for &(name, text, count, side) in sets.iter() {
+            let pieces = PieceSet::parse(name, text).unwrap();
+            assert_eq!(pieces.len(), count, "{}", name);
+            assert_eq!(pieces.text(), text);
+            let widest = (0..pieces.len()).map(|i| pieces.get(i).frames[0].width()).max();
+            assert_eq!(widest, Some(side), "{}", name);
+        }
+    }
+
+    #[test]
+    fn non_square_grid_is_padded() {
+        let pieces = PieceSet::parse("test", "piece L orange\n#.\n#.\n##\n").unwrap();
+        let frames = &pieces.get(0).frames;
+        assert_eq!(grid(&frames[0]), ["#..", "#..", "##."]);
+        assert_eq!(grid(&frames[1]), ["###", "#..", "..."]);
+        assert_eq!(frames[0].bounds().1, 2);
+        assert_eq!(frames[0].bounds().2, 3);
+    }
+
+    #[test]
+    fn rotation_counts() {
+        let pieces = PieceSet::parse("domino", include_str!("../../assets/pieces/domino.txt")).unwrap();
+        assert_eq!(pieces.get(0).frames.len(), 2);
+
+        let text = "piece V red\n#.\n##\n-\n##\n#.\n-\n##\n.#\n";
+        assert_eq!(PieceSet::parse("three", text).unwrap().get(0).frames.len(), 3);
+
+        let text = "piece X red\n.#.\n###\n.#.\n";
+        assert_eq!(PieceSet::parse("plus", text).unwrap().get(0).frames.len(), 4);
+    }
+
+    #[test]
+    fn malformed_sets() {
+        let cases = [
+            ("", "No pieces"),
+            ("# only a comment\n", "No pieces"),
+            ("tile A red\n##\n", "Expected a piece on line 1"),
+            ("piece red\n##\n", "one letter name"),
+            ("piece 1 red\n##\n", "one letter name"),
+            ("piece A\n##\n", "needs a colour"),
+            ("piece A mauve\n##\n", "Unknown colour"),
+            ("piece A #12345\n##\n", "Unknown colour"),
+            ("piece A red spin\n##\n", "Unknown flag spin"),
+            ("piece A red\n", "malformed grid"),
+            ("piece A red\n..\n..\n", "malformed grid"),
+            ("piece A red\n#.\n#\n", "malformed grid"),
+            ("piece A red\n#x\n", "malformed grid"),
+            ("piece A red\n##\n-\n#..\n#..\n#..\n", "differ in size"),
+            ("piece A red srs\n##\n-\n#.\n#.\n", "four rotation states"),
+            ("piece A red t-spin\n##\n", "3x3 grid"),
+            ("piece A red\n##\n\npiece A blue\n#\n", "defined twice"),
+        ];
+        for &(text, error) in cases.iter() {
+            match PieceSet::parse("bad", text) {
+                Ok(_) => panic!("{:?} parsed", text),
+                Err(e) => assert!(e.contains(error), "{:?}: {}", text, e),
+            }
+        }
+    }
+
+    #[test]
+    fn colours() {
+        let pieces = PieceSet::parse("test", "piece A #ff8000\n#\n\npiece B cyan\n#\n").unwrap();
+        assert!(pieces.get(0).color == Color::Rgb(0xff, 0x80, 0x00));
+        assert!(pieces.get(1).color == Color::CYAN);
+    }
+}
@@ -0,0 +1,972 @@
+use std::cmp::{max, min};
+
+use fastrand::Rng;
+
+use engine::base::Number;
+use engine::geometry::Point;
+use engine::input::Actions;
+use engine::time::{BlinkAnimation, DelayedRepeat, TimeAware, Timer};
+use engine::video::{draw_colored_str, draw_rect, draw_str, Color, ScreenBuffer};
+
+use crate::bot::Turn;
+use crate::config::{Config, MAX_PREVIEW_COUNT};
+use crate::controls::Action;
+use crate::field::{Cell, Field};
+use crate::garbage::{attack, GarbageQueue};
+use crate::level::{Gravity, Levelling};
+use crate::mode::{GameMode, ModeBehavior, Stats, TopOut};
+use crate::piece::{Frame, PieceSet};
+use crate::randomizer::{PieceQueue, RandomizerKind};
+use crate::save::SavedGame;
+use crate::scoring::{Award, Clear, Scoring, TSpin};
+
+// Queued garbage rises after a piece locks without clearing lines, at most
+// this many rows at a time.
+const MAX_GARBAGE_PER_PIECE: Number = 8;
+
+/// One player's side of the game: their field, the piece in play, the
+/// queue and hold, scoring and incoming garbage.
+pub struct Player<'frame> {
+    // external
+    pieces: &'frame PieceSet,
+    mode: GameMode,
+    seed: u64,
+    randomizer: RandomizerKind,
+    preview_count: usize,
+    max_lock_resets: Number,
+
+    // logic
+    curr_frame: usize,
+    curr_tet_index: usize,
+    hold_tet_index: Option<usize>,
+    is_hold_used: bool,
+    field: Field,
+    tet_pos: Point,
+    is_topped_out: bool,
+    game_ticks: Number,
+    levelling: Levelling,
+    gravity: Gravity,
+    lock_timer: Timer,
+    lock_resets: Number,
+    lowest_y: Number,
+    filled_lines_animation: BlinkAnimation,
+    queue: PieceQueue,
+    left_repeater: DelayedRepeat,
+    right_repeater: DelayedRepeat,
+    down_repeater: DelayedRepeat,
+    scoring: Scoring,
+    last_kick: Option<usize>,
+    pending_t_spin: TSpin,
+    last_award: Option<Award>,
+    award_timer: Timer,
+    garbage: GarbageQueue,
+    garbage_seed: u64,
+    garbage_rng: Rng,
+    garbage_holes: u64,
+    outgoing: Number,
+    sent: Number,
+
+    // visualisation
+    origin: Point,
+    field_pos: Point,
+}
+
+impl<'frame> Player<'frame> {
+    /// Players share the seed so they get the same pieces, but each gets
+    /// their own garbage holes.
+    pub fn new(pieces: &'frame PieceSet, config: &Config, mode: GameMode, seed: u64, index: usize, origin: Point) -> Player<'frame> {
+        let levelling = Self::create_levelling(config, mode);
+        let gravity = Gravity::new(mode.gravity_curve().gravity(levelling.level()));
+        let preview_count = min(config.preview_count, MAX_PREVIEW_COUNT);
+        let queue = PieceQueue::new(config.randomizer.create(seed, pieces.len()), preview_count);
+
+        let mut player = Player {
+            pieces,
+            mode,
+            seed,
+            randomizer: config.randomizer,
+            preview_count,
+            max_lock_resets: config.max_lock_resets,
+            curr_frame: 0,
+            curr_tet_index: 0,
+            hold_tet_index: None,
+            is_hold_used: false,
+            field: Field::new(config.field_width, config.field_height, config.hidden_rows),
+            tet_pos: Point::new(0, 0),
+            is_topped_out: false,
+            game_ticks: 0,
+            levelling,
+            gravity,
+            lock_timer: Timer::new(config.lock_delay),
+            lock_resets: 0,
+            lowest_y: 0,
+            filled_lines_animation: BlinkAnimation::new(),
+            queue,
+            left_repeater: DelayedRepeat::new(30, 5),
+            right_repeater: DelayedRepeat::new(30, 5),
+            down_repeater: DelayedRepeat::new(30, 3),
+            scoring: Scoring::new(),
+            last_kick: None,
+            pending_t_spin: TSpin::None,
+            last_award: None,
+            award_timer: Timer::new(250),
+            garbage: GarbageQueue::new(),
+            garbage_seed: seed.wrapping_add(index as u64),
+            garbage_rng: Rng::with_seed(seed.wrapping_add(index as u64)),
+            garbage_holes: 0,
+            outgoing: 0,
+            sent: 0,
+            origin,
+            field_pos: origin + Self::field_pos(pieces),
+        };
+
+        let tet_index = player.queue.pop();
+        player.spawn_tetromino(tet_index);
+
+        player
+    }
+
+    // The hold box left of the field grows with the widest piece.
+    fn field_pos(pieces: &PieceSet) -> Point {
+        let (preview_width, _) = pieces.preview_size();
+        Point::new(max(8, preview_width + 4), 3)
+    }
+
+    /// Size in tiles of one player's field with its hold and preview columns.
+    pub fn board_size(config: &Config, pieces: &PieceSet) -> (Number, Number) {
+        let field_pos = Self::field_pos(pieces);
+        let (preview_width, preview_height) = pieces.preview_size();
+        let preview_count = min(config.preview_count, MAX_PREVIEW_COUNT) as Number;
+
+        let width = field_pos.x + config.field_width as Number + 3 + preview_width + 3;
+        let field_bottom = field_pos.y + config.field_height as Number + 2;
+        let preview_bottom = field_pos.y + preview_height + 3 + max(preview_count - 1, 0) * (preview_height + 1);
+        (width, max(field_bottom, preview_bottom) + 1)
+    }
+
+    fn create_levelling(config: &Config, mode: GameMode) -> Levelling {
+        let max_level = mode.max_level();
+        Levelling::new(mode.progression(), min(config.start_level, max_level), max_level)
+    }
+
+    pub fn stats(&self) -> Stats {
+        Stats {
+            score: self.scoring.score(),
+            lines: self.levelling.lines(),
+            level: self.levelling.level(),
+            ticks: self.game_ticks,
+            sent: self.sent,
+        }
+    }
+
+    pub fn origin(&self) -> Point {
+        self.origin
+    }
+
+    pub fn next_pieces(&self) -> impl Iterator<Item = usize> + '_ {
+        self.queue.pieces()
+    }
+
+    pub fn is_topped_out(&self) -> bool {
+        self.is_topped_out
+    }
+
+    /// What the bot gets to see, unless lines are being cleared.
+    pub fn turn(&self) -> Option<Turn<'_>> {
+        if self.filled_lines_animation.is_started() {
+            return None;
+        }
+
+        Some(Turn {
+            field: &self.field,
+            pieces: self.pieces,
+            piece: self.curr_tet_index,
+            rotation: self.curr_frame,
+            pos: self.tet_pos,
+            next_piece: self.queue.pieces().next(),
+        })
+    }
+
+    pub fn stop_repeaters(&mut self) {
+        self.left_repeater.stop();
+        self.right_repeater.stop();
+        self.down_repeater.stop();
+    }
+
+    /// Garbage sent since the last call, after cancelling.
+    pub fn take_outgoing(&mut self) -> Number {
+        std::mem::take(&mut self.outgoing)
+    }
+
+    /// Jumps to a level, keeping the lines cleared so far.
+    pub fn set_level(&mut self, level: Number) {
+        self.levelling.set_progress(level, self.levelling.lines());
+        self.gravity.set(self.mode.gravity_curve().gravity(self.levelling.level()));
+    }
+
+    pub fn receive_garbage(&mut self, lines: Number) {
+        self.garbage.push(lines);
+    }
+
+    // Pieces spawn straddling the top of the visible area, sticking out
+    // into the hidden rows.
+    fn spawn_pos(&self, frame: &Frame) -> Point {
+        let (top_left, _, height) = frame.bounds();
+        let bottom = top_left.y + height - 1;
+        Point::new((self.field.width() - frame.width()) / 2, max(self.field.hidden_rows() - bottom, 0))
+    }
+
+    fn current_frame(&self) -> &'frame Frame {
+        &self.pieces.get(self.curr_tet_index).frames[self.curr_frame]
+    }
+
+    fn copy_frame(&mut self) {
+        let pos = self.tet_pos;
+        let curr_frame = self.current_frame();
+        self.field.copy_frame(curr_frame, pos, Cell::Piece(self.curr_tet_index));
+    }
+
+    fn is_collide(&self, frame: &'frame Frame, p: Point) -> bool {
+        self.field.is_collide(frame, p)
+    }
+
+    fn clean_filled_lines(&mut self) -> Number {
+        self.field.clean_filled_lines()
+    }
+
+    fn move_colliding_tetromino(&mut self, new_pos: Point) -> bool {
+        if self.filled_lines_animation.is_started() {
+            return false;
+        }
+        if self.is_collide(self.current_frame(), self.tet_pos) ||
+            !self.is_collide(self.current_frame(), new_pos) {
+            self.tet_pos = new_pos;
+            self.last_kick = None;
+            self.update_lock(true);
+            return true;
+        }
+        false
+    }
+
+    fn soft_drop(&mut self) {
+        let new_pos = self.tet_pos.add_y(1);
+        if self.move_colliding_tetromino(new_pos) {
+            self.scoring.soft_drop(1);
+        }
+    }
+
+    fn is_grounded(&self) -> bool {
+        self.is_collide(self.current_frame(), self.tet_pos.add_y(1))
+    }
+
+    fn drop_pos(&self) -> Point {
+        let mut pos = self.tet_pos;
+        while !self.is_collide(self.current_frame(), pos.add_y(1)) {
+            pos = pos.add_y(1);
+        }
+        pos
+    }
+
+    // Moves and rotations on the ground restart the lock delay until the
+    // resets run out. Reaching a new lowest row gives the resets back.
+    fn update_lock(&mut self, is_player_move: bool) {
+        if self.tet_pos.y > self.lowest_y {
+            self.lowest_y = self.tet_pos.y;
+            self.lock_resets = 0;
+        }
+
+        if !self.is_grounded() {
+            self.lock_timer.stop();
+        } else if is_player_move {
+            if self.lock_resets < self.max_lock_resets {
+                self.lock_resets += 1;
+                self.lock_timer.start();
+            }
+        } else if !self.lock_timer.is_started() {
+            self.lock_timer.start();
+        }
+    }
+
+    fn next_frame(&self, is_clockwise: bool) -> usize {
+        let frame_count = self.pieces.get(self.curr_tet_index).frames.len();
+        if is_clockwise {
+            (self.curr_frame + 1) % frame_count
+        } else {
+            (self.curr_frame + frame_count - 1) % frame_count
+        }
+    }
+
+    fn rotate_colliding_tetromino(&mut self, is_clockwise: bool) {
+        let piece = self.pieces.get(self.curr_tet_index);
+        let new_frame_index = self.next_frame(is_clockwise);
+        let new_frame = &piece.frames[new_frame_index];
+
+        if self.is_collide(self.current_frame(), self.tet_pos) {
+            self.curr_frame = new_frame_index;
+            return;
+        }
+
+        for (kick, offset) in piece.kicks.offsets(self.curr_frame, is_clockwise).into_iter().enumerate() {
+            let new_pos = self.tet_pos + offset;
+            if !self.is_collide(new_frame, new_pos) {
+                self.curr_frame = new_frame_index;
+                self.tet_pos = new_pos;
+                self.last_kick = Some(kick);
+                self.update_lock(true);
+                return;
+            }
+        }
+    }
+
+    fn spawn_tetromino(&mut self, tet_index: usize) {
+        self.curr_tet_index = tet_index;
+        self.curr_frame = 0;
+        self.tet_pos = self.spawn_pos(self.current_frame());
+        self.last_kick = None;
+        self.gravity.reset();
+        self.lock_timer.stop();
+        self.lock_resets = 0;
+        self.lowest_y = self.tet_pos.y;
+
+        if self.is_collide(self.current_frame(), self.tet_pos) {
+            self.top_out();
+        }
+    }
+
+    fn top_out(&mut self) {
+        match self.mode.top_out() {
+            TopOut::GameOver => self.is_topped_out = true,
+            TopOut::ClearField => self.field.clear(),
+        }
+    }
+
+    fn finish_turn(&mut self) {
+        self.stop_repeaters();
+        self.is_hold_used = false;
+        let tet_index = self.queue.pop();
+        self.spawn_tetromino(tet_index);
+    }
+
+    fn hold_tetromino(&mut self) {
+        if self.is_hold_used || self.filled_lines_animation.is_started() {
+            return;
+        }
+        self.is_hold_used = true;
+
+        let tet_index = match self.hold_tet_index.replace(self.curr_tet_index) {
+            Some(tet_index) => tet_index,
+            None => self.queue.pop(),
+        };
+        self.spawn_tetromino(tet_index);
+    }
+
+    fn fall(&mut self, cells: Number) {
+        if self.is_collide(self.current_frame(), self.tet_pos) {
+            return;
+        }
+
+        for _ in 0..cells {
+            let new_pos = self.tet_pos.add_y(1);
+            if self.is_collide(self.current_frame(), new_pos) {
+                break;
+            }
+            self.tet_pos = new_pos;
+            self.last_kick = None;
+            self.update_lock(false);
+        }
+
+        if self.is_grounded() {
+            if self.lock_resets >= self.max_lock_resets && !self.lock_timer.is_started() {
+                self.lock_tetromino();
+            } else {
+                self.update_lock(false);
+            }
+        }
+    }
+
+    fn hard_drop(&mut self) {
+        if self.filled_lines_animation.is_started() || self.is_collide(self.current_frame(), self.tet_pos) {
+            return;
+        }
+
+        let drop_pos = self.drop_pos();
+        if drop_pos != self.tet_pos {
+            self.scoring.hard_drop(drop_pos.y - self.tet_pos.y);
+            self.tet_pos = drop_pos;
+            self.last_kick = None;
+        }
+        self.lock_tetromino();
+    }
+
+    fn lock_tetromino(&mut self) {
+        self.lock_timer.stop();
+        let t_spin = self.detect_t_spin();
+        let is_locked_out = self.is_in_hidden_rows();
+        self.copy_frame();
+
+        if is_locked_out {
+            self.top_out();
+            if self.is_topped_out {
+                return;
+            }
+        }
+
+        if self.field.is_any_line_filled() {
+            self.pending_t_spin = t_spin;
+            self.filled_lines_animation.start();
+        } else {
+            self.award(0, t_spin);
+            self.raise_garbage();
+            if self.is_topped_out {
+                return;
+            }
+            self.finish_turn();
+        }
+    }
+
+    // Each attack rises with its own hole.
+    fn raise_garbage(&mut self) {
+        for lines in self.garbage.take(MAX_GARBAGE_PER_PIECE) {
+            let hole = self.garbage_hole();
+            if !self.field.insert_garbage(lines, hole) {
+                self.top_out();
+            }
+        }
+    }
+
+    fn garbage_hole(&mut self) -> Number {
+        self.garbage_holes += 1;
+        self.garbage_rng.i32(0..self.field.width())
+    }
+
+    // Locking a piece entirely out of sight tops out like a blocked spawn.
+    fn is_in_hidden_rows(&self) -> bool {
+        let frame = self.current_frame();
+        (0..frame.height()).all(|y| {
+            self.tet_pos.y + y < self.field.hidden_rows() ||
+                (0..frame.width()).all(|x| !frame.is_filled(Point::new(x, y)))
+        })
+    }
+
+    // 3-corner rule: a T locked right after a rotation with three of the
+    // corners around its centre occupied. It is a mini T-spin unless both
+    // corners it points at are occupied or the last kick was used.
+    fn detect_t_spin(&self) -> TSpin {
+        let kick = match self.last_kick {
+            Some(kick) if self.pieces.get(self.curr_tet_index).has_t_spins => kick,
+            _ => return TSpin::None,
+        };
+
+        let corners = [Point::new(0, 0), Point::new(2, 0), Point::new(2, 2), Point::new(0, 2)];
+        let occupied = corners.map(|corner| self.field.is_occupied(self.tet_pos + corner));
+
+        if occupied.iter().filter(|&&x| x).count() < 3 {
+            return TSpin::None;
+        }
+
+        let is_front_occupied = occupied[self.curr_frame] && occupied[(self.curr_frame + 1) % 4];
+        if is_front_occupied || kick == 4 {
+            TSpin::Full
+        } else {
+            TSpin::Mini
+        }
+    }
+
+    // Attacks first cancel garbage still waiting in the queue.
+    fn award(&mut self, lines: Number, t_spin: TSpin) {
+        let award = self.scoring.lock(Clear { lines, t_spin }, self.levelling.level());
+        if self.levelling.update(lines, self.scoring.score()) {
+            self.gravity.set(self.mode.gravity_curve().gravity(self.levelling.level()));
+        }
+
+        let sent = self.garbage.cancel(attack(&award));
+        self.outgoing += sent;
+        self.sent += sent;
+
+        if award.text().is_some() || award.combo > 0 {
+            self.last_award = Some(award);
+            self.award_timer.start();
+        }
+    }
+
+    pub fn handle_input(&mut self, actions: &Actions<Action>) {
+        if actions.is_back_edge(Action::MoveLeft) {
+            self.left_repeater.stop();
+        }
+        if actions.is_back_edge(Action::MoveRight) {
+            self.right_repeater.stop();
+        }
+        if actions.is_back_edge(Action::SoftDrop) {
+            self.down_repeater.stop();
+        }
+
+        if actions.is_front_edge(Action::RotateClockwise) {
+            self.rotate_colliding_tetromino(true);
+        } else if actions.is_front_edge(Action::RotateCounterClockwise) {
+            self.rotate_colliding_tetromino(false);
+        } else if actions.is_front_edge(Action::Hold) {
+            self.hold_tetromino();
+        } else if actions.is_front_edge(Action::HardDrop) {
+            self.hard_drop();
+        } else if actions.is_front_edge(Action::SoftDrop) {
+            self.soft_drop();
+            self.down_repeater.start();
+        } else if actions.is_front_edge(Action::MoveLeft) {
+            let new_pos = self.tet_pos.sub_x(1);
+            self.move_colliding_tetromino(new_pos);
+            self.left_repeater.start();
+            self.right_repeater.stop();
+        } else if actions.is_front_edge(Action::MoveRight) {
+            let new_pos = self.tet_pos.add_x(1);
+            self.move_colliding_tetromino(new_pos);
+            self.right_repeater.start();
+            self.left_repeater.stop();
+        }
+    }
+
+    pub fn tick(&mut self) {
+        self.game_ticks += 1;
+        self.left_repeater.tick();
+        self.right_repeater.tick();
+        self.down_repeater.tick();
+        self.lock_timer.tick();
+        self.award_timer.tick();
+        self.filled_lines_animation.tick();
+
+        if self.left_repeater.is_triggered() {
+            let new_pos = self.tet_pos.sub_x(1);
+            self.move_colliding_tetromino(new_pos);
+        }
+        if self.right_repeater.is_triggered() {
+            let new_pos = self.tet_pos.add_x(1);
+            self.move_colliding_tetromino(new_pos);
+        }
+        if self.down_repeater.is_triggered() {
+            self.soft_drop();
+        }
+        if self.filled_lines_animation.is_triggered() {
+            let filled_lines = self.clean_filled_lines();
+            self.award(filled_lines, self.pending_t_spin);
+            self.finish_turn();
+        }
+        if !self.filled_lines_animation.is_started() {
+            let cells = self.gravity.tick();
+            self.fall(cells);
+        }
+        if self.lock_timer.is_triggered() && self.is_grounded() {
+            self.lock_tetromino();
+        }
+    }
+
+    pub fn to_saved_game(&self) -> SavedGame {
+        let letters = self.pieces.letters();
+        let field = (0..self.field.height())
+            .map(|y| (0..self.field.width()).map(|x| self.field.cell(Point::new(x, y)).token(&letters)).collect::<Vec<_>>().join(" "))
+            .collect();
+
+        SavedGame {
+            mode: self.mode.name().to_string(),
+            pieces: self.pieces.name().to_string(),
+            randomizer: self.randomizer.name().to_string(),
+            seed: self.seed,
+            draws: self.queue.draws(),
+            queue: self.queue.pieces().collect(),
+            field_width: self.field.width() as usize,
+            field_height: self.field.visible_rows() as usize,
+            hidden_rows: self.field.hidden_rows() as usize,
+            field,
+            piece: self.curr_tet_index,
+            rotation: self.curr_frame,
+            x: self.tet_pos.x,
+            y: self.tet_pos.y,
+            hold: self.hold_tet_index,
+            is_hold_used: self.is_hold_used,
+            score: self.scoring.score(),
+            combo: self.scoring.combo(),
+            is_back_to_back_ready: self.scoring.is_back_to_back_ready(),
+            level: self.levelling.level(),
+            lines: self.levelling.lines(),
+            ticks: self.game_ticks,
+            gravity: self.gravity.accumulator(),
+            lock_elapsed: self.lock_timer.elapsed(),
+            lock_resets: self.lock_resets,
+            lowest_y: self.lowest_y,
+            last_kick: self.last_kick,
+            line_clear_elapsed: self.filled_lines_animation.elapsed(),
+            line_clear_changes: self.filled_lines_animation.changes_remain(),
+            pending_t_spin: self.pending_t_spin,
+            garbage: self.garbage.attacks().collect(),
+            garbage_holes: self.garbage_holes,
+            is_modified: false,
+        }
+    }
+
+    /// Picks up a saved game. The player must have been created for the
+    /// saved mode and seed.
+    pub fn restore(&mut self, config: &Config, saved: SavedGame) -> Result<(), String> {
+        let piece_count = self.pieces.len();
+        let letters = self.pieces.letters();
+        let cells = saved.field.iter()
+            .map(|row| row.split_whitespace().map(|token| Cell::from_token(token, &letters)).collect::<Option<Vec<_>>>())
+            .collect::<Option<Vec<_>>>()
+            .ok_or("Malformed field in save")?;
+        let is_field_valid = cells.len() == self.field.height() as usize &&
+            cells.iter().all(|row| row.len() == self.field.width() as usize);
+        let is_pieces_valid = saved.piece < piece_count &&
+            saved.hold.is_none_or(|hold| hold < piece_count) &&
+            saved.queue.iter().all(|&piece| piece < piece_count);
+        if !is_field_valid || !is_pieces_valid || saved.rotation >= self.pieces.get(saved.piece).frames.len() {
+            return Err("Saved game does not fit this field".into());
+        }
+        if saved.queue.len() != self.preview_count {
+            return Err(format!("Saved game was played with {} previews", saved.queue.len()));
+        }
+
+        self.queue = PieceQueue::restore(self.randomizer.create(self.seed, piece_count), self.preview_count, saved.queue, saved.draws);
+
+        self.field.clear();
+        for (y, row) in cells.into_iter().enumerate() {
+            for (x, cell) in row.into_iter().enumerate() {
+                self.field.set_cell(Point::new(x as Number, y as Number), cell);
+            }
+        }
+
+        self.curr_tet_index = saved.piece;
+        self.curr_frame = saved.rotation;
+        self.tet_pos = Point::new(saved.x, saved.y);
+        self.hold_tet_index = saved.hold;
+        self.is_hold_used = saved.is_hold_used;
+
+        self.scoring = Scoring::with_state(saved.score, saved.combo, saved.is_back_to_back_ready);
+        self.levelling = Self::create_levelling(config, self.mode);
+        self.levelling.set_progress(saved.level, saved.lines);
+        self.gravity = Gravity::new(self.mode.gravity_curve().gravity(self.levelling.level()));
+        self.gravity.set_accumulator(saved.gravity);
+        self.game_ticks = saved.ticks;
+
+        self.lock_timer.set_elapsed(saved.lock_elapsed);
+        self.lock_resets = saved.lock_resets;
+        self.lowest_y = saved.lowest_y;
+        self.last_kick = saved.last_kick;
+        self.filled_lines_animation.restore(saved.line_clear_elapsed, saved.line_clear_changes);
+        self.pending_t_spin = saved.pending_t_spin;
+
+        // like the piece queue, the holes drawn so far bring the generator back
+        self.garbage = GarbageQueue::new();
+        for lines in saved.garbage {
+            self.garbage.push(lines);
+        }
+        self.garbage_rng = Rng::with_seed(self.garbage_seed);
+        self.garbage_holes = 0;
+        for _ in 0..saved.garbage_holes {
+            self.garbage_hole();
+        }
+        Ok(())
+    }
+
+    pub fn draw(&self, buf: &mut ScreenBuffer, title: &str) {
+        let field_width = self.field.width();
+        let hidden_rows = self.field.hidden_rows();
+        let visible_rows = self.field.visible_rows();
+        draw_rect(buf, self.field_pos, field_width + 2, visible_rows + 2, '+');
+
+        for y in hidden_rows..self.field.height() {
+            let pos_y = self.field_pos.y + y - hidden_rows + 1;
+            if !self.field.is_line_filled(y) || self.filled_lines_animation.is_show() {
+                for x in 0..field_width {
+                    let pos_x = self.field_pos.x + x + 1;
+                    if let Some((chr, color)) = cell_style(self.pieces, self.field.cell(Point::new(x, y))) {
+                        buf.set_colored_byte(Point::new(pos_x, pos_y), chr, color, Color::Default);
+                    }
+                }
+            }
+        }
+
+        if !self.filled_lines_animation.is_started() {
+            let color = self.pieces.get(self.curr_tet_index).color;
+            self.draw_field_frame(buf, self.current_frame(), self.drop_pos(), 0xb0u8, color);
+            self.draw_field_frame(buf, self.current_frame(), self.tet_pos, 0xb1u8, color);
+        }
+
+        // incoming garbage rises from the bottom of the field
+        let incoming = min(self.garbage.total(), visible_rows);
+        for i in 0..incoming {
+            let pos = Point::new(self.field_pos.x - 1, self.field_pos.y + visible_rows - i);
+            buf.set_colored_byte(pos, 0xb2u8, Color::RED, Color::Default);
+        }
+
+        let (preview_width, preview_height) = self.pieces.preview_size();
+        let hold_pos = Point::new(self.origin.x + 1, self.field_pos.y);
+        draw_rect(buf, hold_pos, preview_width + 2, preview_height + 2, '+');
+        if let Some(hold_tet_index) = self.hold_tet_index {
+            let piece = self.pieces.get(hold_tet_index);
+            let color = if self.is_hold_used {
+                Color::GRAY
+            } else {
+                piece.color
+            };
+            draw_frame(buf, &piece.frames[0], hold_pos + Point::new(1, 1), 0xb1u8, color);
+        }
+
+        let preview_pos = self.field_pos.add_x(field_width + 3);
+        for (i, tet_index) in self.queue.pieces().enumerate() {
+            let piece = self.pieces.get(tet_index);
+            if i == 0 {
+                draw_rect(buf, preview_pos, preview_width + 2, preview_height + 2, '+');
+                draw_frame(buf, &piece.frames[0], preview_pos.add_x(1).add_y(1), 0xb1u8, piece.color);
+            } else {
+                let pos = preview_pos.add_x(1).add_y(preview_height + 3 + (i as Number - 1) * (preview_height + 1));
+                draw_frame(buf, &piece.frames[0], pos, 0xb0u8, piece.color);
+            }
+        }
+
+        let title_pos = self.field_pos.add_x(1).sub_y(1);
+        draw_colored_str(buf, title_pos, title, Color::CYAN, Color::Default);
+
+        let stats_pos = hold_pos.add_y(preview_height + 3);
+        for (i, (label, value)) in self.mode.hud(&self.stats()).iter().enumerate() {
+            let pos = stats_pos.add_y(3 * i as Number);
+            draw_str(buf, pos, label);
+            draw_str(buf, pos.add_y(1), value);
+        }
+
+        if let Some(award) = self.last_award.as_ref().filter(|_| self.award_timer.is_started()) {
+            let award_pos = self.origin + Point::new(1, 1);
+            if let Some(text) = award.text() {
+                draw_colored_str(buf, award_pos, &text, Color::YELLOW, Color::Default);
+            }
+            if award.combo > 0 {
+                draw_colored_str(buf, award_pos.add_y(1), &format!("COMBO {}", award.combo), Color::CYAN, Color::Default);
+            }
+        }
+    }
+
+    // Like `draw_frame`, but takes a field position and leaves out the
+    // squares in the hidden rows.
+    fn draw_field_frame(&self, buf: &mut ScreenBuffer, frame: &Frame, pos: Point, chr: u8, color: Color) {
+        let hidden_rows = self.field.hidden_rows();
+        let origin = self.field_pos + Point::new(1, 1 - hidden_rows);
+        for y in 0..frame.height() {
+            for x in 0..frame.width() {
+                if pos.y + y >= hidden_rows && frame.is_filled(Point::new(x, y)) {
+                    buf.set_colored_byte(origin + pos + Point::new(x, y), chr, color, Color::Default);
+                }
+            }
+        }
+    }
+}
+
+fn cell_style(pieces: &PieceSet, cell: Cell) -> Option<(u8, Color)> {
+    match cell {
+        Cell::Empty => None,
+        Cell::Piece(index) => Some((0xb1u8, pieces.get(index).color)),
+        Cell::Garbage => Some((0xb2u8, Color::GRAY)),
+        Cell::Custom(chr) => Some((chr, Color::WHITE)),
+    }
+}
+
+// Draws the frame trimmed to its squares, so pieces line up in the hold
+// and preview boxes whatever their grid size.
+fn draw_frame(buf: &mut ScreenBuffer, frame: &Frame, pos: Point, chr: u8, color: Color) {
+    let (top_left, width, height) = frame.bounds();
+    for y in 0..height {
+        for x in 0..width {
+            if frame.is_filled(top_left + Point::new(x, y)) {
+                buf.set_colored_byte(pos + Point::new(x, y), chr, color, Color::Default);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mode::Marathon;
+
+    // Detects the T-spin of a T pointing up with its 3x3 box at `pos` and
+    // the given corners of the box filled.
+    fn t_spin(pieces: &PieceSet, pos: Point, filled: &[Point], last_kick: Option<usize>) -> TSpin {
+        let mut player = Player::new(pieces, &Config::default(), GameMode::Marathon(Marathon), 1, 0, Point::new(0, 0));
+        player.curr_tet_index = pieces.letters().iter().position(|&letter| letter == 'T').unwrap();
+        player.curr_frame = 0;
+        player.tet_pos = pos;
+        player.last_kick = last_kick;
+        for &corner in filled {
+            player.field.set_cell(pos + corner, Cell::Garbage);
+        }
+        player.detect_t_spin()
+    }
+
+    const TOP_LEFT: Point = Point { x: 0, y: 0 };
+    const TOP_RIGHT: Point = Point { x: 2, y: 0 };
+    const BOTTOM_RIGHT: Point = Point { x: 2, y: 2 };
+    const BOTTOM_LEFT: Point = Point { x: 0, y: 2 };
+    const POS: Point = Point { x: 3, y: 30 };
+
+    #[test]
+    fn t_spin_front_corners() {
+        let pieces = PieceSet::standard();
+        assert_eq!(t_spin(&pieces, POS, &[TOP_LEFT, TOP_RIGHT, BOTTOM_LEFT], Some(0)), TSpin::Full);
+    }
+
+    #[test]
+    fn t_spin_mini() {
+        let pieces = PieceSet::standard();
+        assert_eq!(t_spin(&pieces, POS, &[TOP_LEFT, BOTTOM_LEFT, BOTTOM_RIGHT], Some(0)), TSpin::Mini);
+    }
+
+    #[test]
+    fn t_spin_last_kick_upgrades_mini() {
+        let pieces = PieceSet::standard();
+        assert_eq!(t_spin(&pieces, POS, &[TOP_LEFT, BOTTOM_LEFT, BOTTOM_RIGHT], Some(4)), TSpin::Full);
+    }
+
+    #[test]
+    fn t_spin_needs_three_corners() {
+        let pieces = PieceSet::standard();
+        assert_eq!(t_spin(&pieces, POS, &[TOP_LEFT, TOP_RIGHT], Some(0)), TSpin::None);
+    }
+
+    #[test]
+    fn t_spin_needs_rotation() {
+        let pieces = PieceSet::standard();
+        assert_eq!(t_spin(&pieces, POS, &[TOP_LEFT, TOP_RIGHT, BOTTOM_LEFT], None), TSpin::None);
+    }
+
+    #[test]
+    fn t_spin_walls_count_as_corners() {
+        let pieces = PieceSet::standard();
+        assert_eq!(t_spin(&pieces, Point::new(-1, 30), &[TOP_RIGHT], Some(0)), TSpin::Full);
+    }
+
+    // A player holding `letter` in rotation state `frame` at `pos`, with
+    // `rows` drawn in `#` and `.` at the bottom of the field.
+    fn holding<'p>(pieces: &'p PieceSet, letter: char, frame: usize, pos: Point, rows: &[&str]) -> Player<'p> {
+        let mut player = Player::new(pieces, &Config::default(), GameMode::Marathon(Marathon), 1, 0, Point::new(0, 0));
+        player.curr_tet_index = pieces.letters().iter().position(|&x| x == letter).unwrap();
+        player.curr_frame = frame;
+        player.tet_pos = pos;
+        let top = player.field.height() - rows.len() as Number;
+        for (y, row) in rows.iter().enumerate() {
+            for (x, square) in row.chars().enumerate() {
+                if square == '#' {
+                    player.field.set_cell(Point::new(x as Number, top + y as Number), Cell::Garbage);
+                }
+            }
+        }
+        player
+    }
+
+    #[test]
+    fn rotation_kicks_off_walls() {
+        let pieces = PieceSet::standard();
+        // piece, state, position, clockwise, then position and kick after
+        let cases = [
+            ('T', 1, Point::new(-1, 30), true, Point::new(0, 30), 1),
+            ('T', 3, Point::new(8, 30), false, Point::new(7, 30), 1),
+            ('J', 1, Point::new(-1, 30), true, Point::new(0, 30), 1),
+            ('I', 1, Point::new(7, 30), true, Point::new(6, 30), 1),
+            ('I', 3, Point::new(-1, 30), true, Point::new(0, 30), 1),
+            ('I', 3, Point::new(-1, 30), false, Point::new(0, 30), 2),
+        ];
+        for &(letter, frame, pos, is_clockwise, kicked_pos, kick) in cases.iter() {
+            let mut player = holding(&pieces, letter, frame, pos, &[]);
+            player.rotate_colliding_tetromino(is_clockwise);
+            assert_eq!(player.curr_frame, (frame + if is_clockwise { 1 } else { 3 }) % 4);
+            assert!(player.tet_pos == kicked_pos, "{} from {}: ({}, {})", letter, frame, player.tet_pos.x, player.tet_pos.y);
+            assert_eq!(player.last_kick, Some(kick), "{} from {}", letter, frame);
+        }
+    }
+
+    #[test]
+    fn t_spin_triple() {
+        let pieces = PieceSet::standard();
+        let rows = [
+            "##........",
+            "..........",
+            "#.########",
+            "#..#######",
+            "#.########",
+        ];
+        let mut player = holding(&pieces, 'T', 0, Point::new(1, 35), &rows);
+
+        // only the last kick gets the T under the overhang
+        player.rotate_colliding_tetromino(true);
+        assert_eq!(player.curr_frame, 1);
+        assert!(player.tet_pos == Point::new(0, 37));
+        assert_eq!(player.last_kick, Some(4));
+        assert_eq!(player.detect_t_spin(), TSpin::Full);
+
+        player.lock_tetromino();
+        assert_eq!(player.clean_filled_lines(), 3);
+    }
+
+    #[test]
+    fn blocked_rotation_keeps_piece() {
+        let pieces = PieceSet::standard();
+        // everything but the T itself is filled
+        let rows = vec!["##########"; 10];
+        let mut player = holding(&pieces, 'T', 0, Point::new(4, 36), &rows);
+        for y in 0..2 {
+            for x in 0..3 {
+                if player.current_frame().is_filled(Point::new(x, y)) {
+                    player.field.set_cell(Point::new(4 + x, 36 + y), Cell::Empty);
+                }
+            }
+        }
+
+        for &is_clockwise in &[true, false] {
+            player.rotate_colliding_tetromino(is_clockwise);
+            assert_eq!(player.curr_frame, 0);
+            assert!(player.tet_pos == Point::new(4, 36));
+            assert_eq!(player.last_kick, None);
+        }
+    }
+
+    #[test]
+    fn restore_continues_the_queue() {
+        let pieces = PieceSet::standard();
+        let config = Config::default();
+        let mut player = Player::new(&pieces, &config, GameMode::Marathon(Marathon), 1, 0, Point::new(0, 0));
+        for _ in 0..10 {
+            player.hard_drop();
+        }
+
+        let mut restored = Player::new(&pieces, &config, GameMode::Marathon(Marathon), 1, 0, Point::new(0, 0));
+        restored.restore(&config, player.to_saved_game()).unwrap();
+        for _ in 0..20 {
+            assert_eq!(restored.queue.pop(), player.queue.pop());
+        }
+    }
+
+    #[test]
+    fn restore_needs_same_preview_count() {
+        let pieces = PieceSet::standard();
+        let config = Config::default();
+        let player = Player::new(&pieces, &config, GameMode::Marathon(Marathon), 1, 0, Point::new(0, 0));
+        let mut saved = player.to_saved_game();
+        saved.queue.pop();
+
+        let mut restored = Player::new(&pieces, &config, GameMode::Marathon(Marathon), 1, 0, Point::new(0, 0));
+        assert_eq!(restored.restore(&config, saved).err().as_deref(), Some("Saved game was played with 4 previews"));
+    }
+
+    #[test]
+    fn restore_keeps_garbage() {
+        let pieces = PieceSet::standard();
+        let config = Config::default();
+        let mut player = Player::new(&pieces, &config, GameMode::Marathon(Marathon), 1, 0, Point::new(0, 0));
+        player.receive_garbage(3);
+        player.receive_garbage(10);
+        player.hard_drop();
+        let saved = player.to_saved_game();
+        assert_eq!((saved.garbage.clone(), saved.garbage_holes), (vec![5], 2));
+
+        let mut restored = Player::new(&pieces, &config, GameMode::Marathon(Marathon), 1, 0, Point::new(0, 0));
+        restored.restore(&config, saved).unwrap();
+        assert_eq!(restored.garbage.total(), 5);
+        for _ in 0..10 {
+            assert_eq!(restored.garbage_hole(), player.garbage_hole());
+        }
+    }
+}
@@ -0,0 +1,285 @@
+use std::collections::VecDeque;
+use std::str::FromStr;
+
+use fastrand::Rng;
+
+pub trait Randomizer {
+    fn next(&mut self) -> usize;
+}
+
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum RandomizerKind {
+    Random,
+    Bag7,
+    Bag14,
+    Nes,
+    Tgm,
+}
+
+impl RandomizerKind {
+    pub fn create(self, seed: u64, piece_count: usize) -> Box<dyn Randomizer> {
+        match self {
+            RandomizerKind::Random => Box::new(PureRandom::new(seed, piece_count)),
+            RandomizerKind::Bag7 => Box::new(Bag::new(seed, piece_count, 1)),
+            RandomizerKind::Bag14 => Box::new(Bag::new(seed, piece_count, 2)),
+            RandomizerKind::Nes => Box::new(History::new(seed, piece_count, 1, 2)),
+            RandomizerKind::Tgm => Box::new(History::new(seed, piece_count, 4, 6)),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            RandomizerKind::Random => "random",
+            RandomizerKind::Bag7 => "bag7",
+            RandomizerKind::Bag14 => "bag14",
+            RandomizerKind::Nes => "nes",
+            RandomizerKind::Tgm => "tgm",
+        }
+    }
+}
+
+impl FromStr for RandomizerKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "random" => Ok(RandomizerKind::Random),
+            "bag7" => Ok(RandomizerKind::Bag7),
+            "bag14" => Ok(RandomizerKind::Bag14),
+            "nes" => Ok(RandomizerKind::Nes),
+            "tgm" => Ok(RandomizerKind::Tgm),
+            _ => Err(format!("Unknown randomizer: {}", s)),
+        }
+    }
+}
+
+pub struct PureRandom {
+    rng: Rng,
+    piece_count: usize,
+}
+
+impl PureRandom {
+    pub fn new(seed: u64, piece_count: usize) -> PureRandom {
+        PureRandom {
+            rng: Rng::with_seed(seed),
+            piece_count,
+        }
+    }
+}
+
+impl Randomizer for PureRandom {
+    fn next(&mut self) -> usize {
+        self.rng.usize(0..self.piece_count)
+    }
+}
+
+/// Deals every piece `copies` times in shuffled order before refilling.
+pub struct Bag {
+    rng: Rng,
+    piece_count: usize,
+    copies: usize,
+    bag: Vec<usize>,
+}
+
+impl Bag {
+    pub fn new(seed: u64, piece_count: usize, copies: usize) -> Bag {
+        Bag {
+            rng: Rng::with_seed(seed),
+            piece_count,
+            copies,
+            bag: Vec::new(),
+        }
+    }
+}
+
+impl Randomizer for Bag {
+    fn next(&mut self) -> usize {
+        if self.bag.is_empty() {
+            for _ in 0..self.copies {
+                self.bag.extend(0..self.piece_count);
+            }
+            self.rng.shuffle(&mut self.bag);
+        }
+        self.bag.pop().unwrap_or(0)
+    }
+}
+
+/// Rolls up to `rolls` times while the piece is among the last
+/// `history_len` dealt ones, keeping the last roll otherwise.
+pub struct History {
+    rng: Rng,
+    piece_count: usize,
+    history_len: usize,
+    rolls: usize,
+    history: VecDeque<usize>,
+}
+
+impl History {
+    pub fn new(seed: u64, piece_count: usize, history_len: usize, rolls: usize) -> History {
+        History {
+            rng: Rng::with_seed(seed),
+            piece_count,
+            history_len,
+            rolls,
+            history: VecDeque::new(),
+        }
+    }
+}
+
+impl Randomizer for History {
+    fn next(&mut self) -> usize {
+        let mut piece = self.rng.usize(0..self.piece_count);
+        for _ in 1..self.rolls {
+            if !self.history.contains(&piece) {
+                break;
+            }
+            piece = self.rng.usize(0..self.piece_count);
+        }
+
+        self.history.push_back(piece);
+        if self.history.len() > self.history_len {
+            self.history.pop_front();
+        }
+        piece
+    }
+}
+
+/// Upcoming pieces drawn ahead of time from a randomizer.
+pub struct PieceQueue {
+    randomizer: Box<dyn Randomizer>,
+    pieces: VecDeque<usize>,
+    len: usize,
+    draws: u64,
+}
+
+impl PieceQueue {
+    pub fn new(randomizer: Box<dyn Randomizer>, len: usize) -> PieceQueue {
+        let mut queue = PieceQueue {
+            randomizer,
+            pieces: VecDeque::new(),
+            len,
+            draws: 0,
+        };
+        queue.fill();
+        queue
+    }
+
+    /// Rebuilds a saved queue. Randomizers are deterministic for a seed, so
+    /// replaying the same number of draws brings back their inner state.
+    pub fn restore(mut randomizer: Box<dyn Randomizer>, len: usize, pieces: Vec<usize>, draws: u64) -> PieceQueue {
+        for _ in 0..draws {
+            randomizer.next();
+        }
+        let mut queue = PieceQueue {
+            randomizer,
+            pieces: pieces.into(),
+            len,
+            draws,
+        };
+        queue.fill();
+        queue
+    }
+
+    fn draw(&mut self) -> usize {
+        self.draws += 1;
+        self.randomizer.next()
+    }
+
+    fn fill(&mut self) {
+        while self.pieces.len() < self.len {
+            let piece = self.draw();
+            self.pieces.push_back(piece);
+        }
+    }
+
+    pub fn pop(&mut self) -> usize {
+        let piece = match self.pieces.pop_front() {
+            Some(piece) => piece,
+            None => self.draw(),
+        };
+        self.fill();
+        piece
+    }
+
+    pub fn get(&self, index: usize) -> Option<usize> {
+        self.pieces.get(index).copied()
+    }
+
+    pub fn pieces(&self) -> impl Iterator<Item = usize> + '_ {
+        self.pieces.iter().copied()
+    }
+
+    /// How many pieces the randomizer has produced so far.
+    pub fn draws(&self) -> u64 {
+        self.draws
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn deal(randomizer: &mut dyn Randomizer, count: usize) -> Vec<usize> {
+        (0..count).map(|_| randomizer.next()).collect()
+    }
+
+    #[test]
+    fn same_seed_same_pieces() {
+        for &kind in &[RandomizerKind::Random, RandomizerKind::Bag7, RandomizerKind::Bag14, RandomizerKind::Nes, RandomizerKind::Tgm] {
+            let pieces = deal(&mut *kind.create(42, 7), 100);
+            assert_eq!(deal(&mut *kind.create(42, 7), 100), pieces, "{}", kind.name());
+            assert_ne!(deal(&mut *kind.create(43, 7), 100), pieces, "{}", kind.name());
+            assert!(pieces.iter().all(|&piece| piece < 7));
+        }
+    }
+
+    #[test]
+    fn bag_deals_every_piece_once() {
+        let pieces = deal(&mut Bag::new(1, 7, 1), 70);
+        for bag in pieces.chunks(7) {
+            let mut bag = bag.to_vec();
+            bag.sort_unstable();
+            assert_eq!(bag, (0..7).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn double_bag_deals_every_piece_twice() {
+        let pieces = deal(&mut Bag::new(1, 7, 2), 70);
+        for bag in pieces.chunks(14) {
+            for piece in 0..7 {
+                assert_eq!(bag.iter().filter(|&&p| p == piece).count(), 2);
+            }
+        }
+    }
+
+    #[test]
+    fn history_avoids_recent_pieces() {
+        // with enough rolls a recent piece is never kept
+        let pieces = deal(&mut History::new(1, 7, 4, 100), 200);
+        for (i, piece) in pieces.iter().enumerate() {
+            assert!(!pieces[i.saturating_sub(4)..i].contains(piece), "{:?}", &pieces[i.saturating_sub(4)..=i]);
+        }
+
+        // a single roll keeps whatever comes
+        let pieces = deal(&mut History::new(1, 7, 4, 1), 200);
+        assert!(pieces.windows(2).any(|pair| pair[0] == pair[1]));
+    }
+
+    #[test]
+    fn queue_restores() {
+        let mut queue = PieceQueue::new(RandomizerKind::Tgm.create(7, 7), 5);
+        for _ in 0..12 {
+            queue.pop();
+        }
+        let pieces: Vec<usize> = queue.pieces().collect();
+        assert_eq!(pieces.len(), 5);
+        assert_eq!(queue.draws(), 17);
+
+        let mut restored = PieceQueue::restore(RandomizerKind::Tgm.create(7, 7), 5, pieces, queue.draws());
+        assert_eq!(restored.draws(), queue.draws());
+        for _ in 0..50 {
+            assert_eq!(restored.pop(), queue.pop());
+        }
+    }
+}
@@ -0,0 +1,395 @@
+use std::io::ErrorKind;
+use std::net::{SocketAddr, TcpListener};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::net::{Connection, Message, PROTOCOL_VERSION};
+
+// How long a new client gets to introduce itself.
+const HELLO_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long a player may go without sending anything before they lose. A
+/// game sends a tick every tick, so only a hung client gets near this.
+pub const IDLE_TIMEOUT: Duration = Duration::from_secs(10);
+
+// A client that has connected but not said hello yet.
+struct Newcomer {
+    connection: Connection,
+    peer: SocketAddr,
+    deadline: Instant,
+}
+
+/// Waits for two clients and pairs them in the order their hellos arrive.
+/// One whose rules differ from the first player's is turned away. Clients
+/// are polled rather than waited on, so a silent one holds nobody up, and
+/// a first player who leaves before the match is replaced.
+pub fn accept_players(listener: &TcpListener) -> Result<Vec<Connection>, String> {
+    listener.set_nonblocking(true).map_err(|e| e.to_string())?;
+
+    let mut newcomers: Vec<Newcomer> = Vec::new();
+    let mut players: Vec<(Connection, String)> = Vec::new();
+
+    loop {
+        // nothing is sent before the match starts, so only a closed
+        // connection shows up here
+        players.retain_mut(|(connection, _)| connection.receive().is_ok());
+        if players.len() == 2 {
+            break;
+        }
+
+        let mut is_idle = true;
+        match listener.accept() {
+            Ok((stream, peer)) => {
+                is_idle = false;
+                let connection = stream
+                    .set_nonblocking(false)
+                    .map_err(|e| e.to_string())
+                    .and_then(|_| Connection::new(stream));
+                match connection {
+                    Ok(connection) => newcomers.push(Newcomer {
+                        connection,
+                        peer,
+                        deadline: Instant::now() + HELLO_TIMEOUT,
+                    }),
+                    Err(e) => println!("{}: {}", peer, e),
+                }
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock => {}
+            Err(e) => return Err(e.to_string()),
+        }
+
+        let mut i = 0;
+        while i < newcomers.len() && players.len() < 2 {
+            let newcomer = &mut newcomers[i];
+            let rules = match newcomer.connection.receive() {
+                Ok(Some(Message::Hello { version, rules })) if version == PROTOCOL_VERSION => rules,
+                Ok(Some(Message::Hello { version, .. })) => {
+                    let _ = newcomer.connection.send(&Message::Refused {
+                        reason: format!("Relay speaks protocol {}, not {}", PROTOCOL_VERSION, version),
+                    });
+                    newcomers.remove(i);
+                    continue;
+                }
+                Ok(None) if Instant::now() < newcomer.deadline => {
+                    i += 1;
+                    continue;
+                }
+                Ok(_) => {
+                    println!("{}: no hello", newcomer.peer);
+                    newcomers.remove(i);
+                    continue;
+                }
+                Err(e) => {
+                    println!("{}: {}", newcomer.peer, e);
+                    newcomers.remove(i);
+                    continue;
+                }
+            };
+            is_idle = false;
+
+            let newcomer = newcomers.remove(i);
+            let mut connection = newcomer.connection;
+            match players.first() {
+                Some((_, first_rules)) if *first_rules != rules => {
+                    let _ = connection.send(&Message::Refused {
+                        reason: format!("Opponent plays with {}", first_rules),
+                    });
+                    continue;
+                }
+                _ => {}
+            }
+
+            println!("{} joined as player {}", newcomer.peer, players.len() + 1);
+            players.push((connection, rules));
+        }
+
+        if is_idle {
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    // rather than waiting out a whole match, late clients can try again
+    for mut newcomer in newcomers {
+        let _ = newcomer.connection.send(&Message::Refused {
+            reason: "Relay is busy with a match".to_string(),
+        });
+    }
+
+    Ok(players.into_iter().map(|(connection, _)| connection).collect())
+}
+
+/// Passes ticks and attacks between the players until one of them loses,
+/// and returns the winner. A player who tops out, gives up, drops the
+/// connection or stays silent for `idle_timeout` loses.
+pub fn play_match(players: &mut [Connection], seed: u64, idle_timeout: Duration) -> usize {
+    for (player, connection) in players.iter_mut().enumerate() {
+        if connection.send(&Message::Start { seed, player }).is_err() {
+            return finish_match(players, 1 - player);
+        }
+    }
+
+    let mut last_heard = [Instant::now(); 2];
+    loop {
+        let mut is_idle = true;
+        for player in 0..players.len() {
+            let opponent = 1 - player;
+            let message = match players[player].receive() {
+                Ok(Some(message)) => message,
+                Ok(None) if last_heard[player].elapsed() > idle_timeout => return finish_match(players, opponent),
+                Ok(None) => continue,
+                Err(_) => return finish_match(players, opponent),
+            };
+            last_heard[player] = Instant::now();
+            is_idle = false;
+
+            match message {
+                Message::Tick { .. } | Message::Attack { .. } if players[opponent].send(&message).is_err() => {
+                    return finish_match(players, player);
+                }
+                Message::GameOver => return finish_match(players, opponent),
+                _ => {}
+            }
+        }
+
+        if is_idle {
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+}
+
+fn finish_match(players: &mut [Connection], winner: usize) -> usize {
+    for connection in players.iter_mut() {
+        let _ = connection.send(&Message::End { winner: Some(winner) });
+    }
+    winner
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread::JoinHandle;
+
+    use engine::base::Number;
+    use engine::geometry::Point;
+    use engine::input::Actions;
+
+    use crate::config::Config;
+    use crate::controls::Action;
+    use crate::mode::{GameMode, Versus};
+    use crate::net::rules;
+    use crate::online::Online;
+    use crate::piece::PieceSet;
+    use crate::player::Player;
+
+    const SEED: u64 = 42;
+
+    // A field as wide as the only piece, so that every drop is a tetris.
+    fn config() -> Config {
+        Config {
+            field_width: 4,
+            data_dir: None,
+            ..Config::default()
+        }
+    }
+
+    fn pieces() -> PieceSet {
+        PieceSet::parse("block", "piece B red no-kicks\n####\n####\n####\n####\n").unwrap()
+    }
+
+    fn start_relay(idle_timeout: Duration) -> (String, JoinHandle<usize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let relay = thread::spawn(move || {
+            let mut players = accept_players(&listener).unwrap();
+            play_match(&mut players, SEED, idle_timeout)
+        });
+        (address, relay)
+    }
+
+    struct Client<'p> {
+        online: Online,
+        players: Vec<Player<'p>>,
+        actions: Actions<Action>,
+    }
+
+    impl<'p> Client<'p> {
+        fn connect(address: &str, pieces: &PieceSet) -> Online {
+            Online::connect(address, rules(&config(), pieces)).unwrap()
+        }
+
+        fn start(mut online: Online, pieces: &'p PieceSet) -> Client<'p> {
+            let (seed, _) = loop {
+                if let Some(start) = online.wait_for_start().unwrap() {
+                    break start;
+                }
+            };
+            let players = (0..2)
+                .map(|i| Player::new(pieces, &config(), GameMode::Versus(Versus), seed, i, Point::new(0, 0)))
+                .collect();
+            Client {
+                online,
+                players,
+                actions: Actions::new(),
+            }
+        }
+
+        // One tick of the game, like `GameScreen` runs it.
+        fn step(&mut self, is_hard_drop: bool) {
+            self.actions.tick();
+            self.actions.set(Action::HardDrop, is_hard_drop);
+            self.online.handle_input(&mut self.players, &self.actions).unwrap();
+            self.online.tick(&mut self.players).unwrap();
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        fn local(&self) -> &Player<'p> {
+            &self.players[self.online.player()]
+        }
+    }
+
+    fn snapshot(player: &Player) -> (Vec<String>, usize, Number, Number, Number) {
+        let saved = player.to_saved_game();
+        (saved.field, saved.piece, saved.x, saved.y, saved.score)
+    }
+
+    fn wait_for_winner(client: &mut Client) -> Option<usize> {
+        for _ in 0..1000 {
+            if let Some(winner) = client.online.winner() {
+                return winner;
+            }
+            client.step(false);
+        }
+        panic!("No winner");
+    }
+
+    #[test]
+    fn match_over_relay() {
+        let pieces = pieces();
+        let (address, relay) = start_relay(IDLE_TIMEOUT);
+        let a = Client::connect(&address, &pieces);
+        let b = Client::connect(&address, &pieces);
+        let mut a = Client::start(a, &pieces);
+        let mut b = Client::start(b, &pieces);
+        assert_eq!((a.online.seed(), a.online.player()), (Some(SEED), 0));
+        assert_eq!((b.online.seed(), b.online.player()), (Some(SEED), 1));
+
+        // a's tetris reaches b as an attack, and b's own tetris cancels it
+        a.step(true);
+        b.step(false);
+        for _ in 0..100 {
+            a.step(false);
+            b.step(false);
+        }
+        assert_eq!(a.local().stats().sent, 4);
+        b.step(true);
+        for _ in 0..100 {
+            a.step(false);
+            b.step(false);
+        }
+        assert_eq!(b.local().stats().sent, 0);
+        assert_eq!(b.local().stats().lines, 4);
+        assert!(b.local().to_saved_game().field.iter().all(|row| !row.contains('#')));
+
+        // each game's copy of the opponent catches up with the real one
+        thread::sleep(Duration::from_millis(50));
+        a.step(false);
+        assert_eq!(snapshot(&a.players[1]), snapshot(&b.players[1]));
+        thread::sleep(Duration::from_millis(50));
+        b.step(false);
+        assert_eq!(snapshot(&b.players[0]), snapshot(&a.players[0]));
+
+        a.online.resign().unwrap();
+        assert_eq!(wait_for_winner(&mut a), Some(1));
+        assert_eq!(wait_for_winner(&mut b), Some(1));
+        assert_eq!(relay.join().unwrap(), 1);
+    }
+
+    #[test]
+    fn disconnect_loses() {
+        let pieces = pieces();
+        let (address, relay) = start_relay(IDLE_TIMEOUT);
+        let a = Client::connect(&address, &pieces);
+        let b = Client::connect(&address, &pieces);
+        let mut a = Client::start(a, &pieces);
+        let mut b = Client::start(b, &pieces);
+        a.step(false);
+        b.step(false);
+
+        drop(b);
+        assert_eq!(wait_for_winner(&mut a), Some(0));
+        assert_eq!(relay.join().unwrap(), 0);
+    }
+
+    #[test]
+    fn silent_player_loses() {
+        let pieces = pieces();
+        let (address, relay) = start_relay(Duration::from_millis(100));
+        let a = Client::connect(&address, &pieces);
+        let b = Client::connect(&address, &pieces);
+        let mut a = Client::start(a, &pieces);
+        let _b = Client::start(b, &pieces);
+
+        assert_eq!(wait_for_winner(&mut a), Some(0));
+        assert_eq!(relay.join().unwrap(), 0);
+    }
+
+    #[test]
+    fn silent_client_holds_nobody_up() {
+        let pieces = pieces();
+        let started = Instant::now();
+        let (address, relay) = start_relay(IDLE_TIMEOUT);
+        let _silent = std::net::TcpStream::connect(&address).unwrap();
+        let a = Client::connect(&address, &pieces);
+        let b = Client::connect(&address, &pieces);
+        let mut a = Client::start(a, &pieces);
+        let _b = Client::start(b, &pieces);
+        assert!(started.elapsed() < HELLO_TIMEOUT);
+
+        a.online.resign().unwrap();
+        assert_eq!(wait_for_winner(&mut a), Some(1));
+        assert_eq!(relay.join().unwrap(), 1);
+    }
+
+    #[test]
+    fn first_player_who_leaves_is_replaced() {
+        let pieces = pieces();
+        let (address, relay) = start_relay(IDLE_TIMEOUT);
+        let gone = Client::connect(&address, &pieces);
+        thread::sleep(Duration::from_millis(50));
+        drop(gone);
+        thread::sleep(Duration::from_millis(50));
+
+        let a = Client::connect(&address, &pieces);
+        let b = Client::connect(&address, &pieces);
+        let mut a = Client::start(a, &pieces);
+        let b = Client::start(b, &pieces);
+        assert_eq!((a.online.player(), b.online.player()), (0, 1));
+
+        a.online.resign().unwrap();
+        assert_eq!(wait_for_winner(&mut a), Some(1));
+        assert_eq!(relay.join().unwrap(), 1);
+    }
+
+    #[test]
+    fn bad_start_is_rejected() {
+        let pieces = pieces();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let relay = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut connection = Connection::new(stream).unwrap();
+            connection.wait(HELLO_TIMEOUT).unwrap();
+            connection.send(&Message::Start { seed: SEED, player: 2 }).unwrap();
+        });
+
+        let mut online = Client::connect(&address, &pieces);
+        let result = loop {
+            match online.wait_for_start() {
+                Ok(None) => {}
+                result => break result,
+            }
+        };
+        assert_eq!(result, Err("Relay started us as player 3".to_string()));
+        relay.join().unwrap();
+    }
+}
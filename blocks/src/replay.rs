@@ -0,0 +1,138 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::str::FromStr;
+
+use crate::config::{Config, MAX_FIELD_HEIGHT, MAX_FIELD_WIDTH, MAX_PREVIEW_COUNT, MIN_FIELD_HEIGHT, MIN_FIELD_WIDTH};
+use crate::piece::PieceSet;
+
+/// Everything besides the input that is needed to play a recorded game
+/// again: the config and the piece set, written like a save file with the
+/// piece set text after a blank line.
+pub fn replay_header(config: &Config, pieces: &PieceSet) -> Vec<u8> {
+    let lines = [
+        format!("seed {}", config.seed.map_or_else(|| "-".to_string(), |seed| seed.to_string())),
+        format!("randomizer {}", config.randomizer.name()),
+        format!("field_width {}", config.field_width),
+        format!("field_height {}", config.field_height),
+        format!("hidden_rows {}", config.hidden_rows),
+        format!("previews {}", config.preview_count),
+        format!("start_level {}", config.start_level),
+        format!("lock_delay {}", config.lock_delay),
+        format!("lock_resets {}", config.max_lock_resets),
+        format!("debug {}", config.debug),
+        format!("pieces {}", pieces.name()),
+    ];
+
+    format!("{}\n\n{}", lines.join("\n"), pieces.text()).into_bytes()
+}
+
+/// Reads a header written by `replay_header`. The config keeps nothing on
+/// disk. Headers of older replays only hold the seed.
+pub fn parse_replay_header(header: &[u8]) -> Result<(Config, PieceSet), String> {
+    if let Ok(bytes) = <[u8; 8]>::try_from(header) {
+        let config = Config {
+            seed: Some(u64::from_le_bytes(bytes)),
+            data_dir: None,
+            ..Config::default()
+        };
+        return Ok((config, PieceSet::standard()));
+    }
+
+    let text = std::str::from_utf8(header).map_err(|_| "Malformed replay header")?;
+    let (settings, piece_text) = text.split_once("\n\n").ok_or("Replay header has no piece set")?;
+
+    let mut values = HashMap::new();
+    for line in settings.lines() {
+        let (key, value) = line.split_once(' ').ok_or_else(|| format!("Malformed replay header line: {}", line))?;
+        values.insert(key, value);
+    }
+
+    let config = Config {
+        seed: match value(&values, "seed")? {
+            "-" => None,
+            _ => Some(parse(&values, "seed")?),
+        },
+        randomizer: value(&values, "randomizer")?.parse()?,
+        field_width: parse(&values, "field_width")?,
+        field_height: parse(&values, "field_height")?,
+        hidden_rows: parse(&values, "hidden_rows")?,
+        preview_count: parse(&values, "previews")?,
+        start_level: parse(&values, "start_level")?,
+        lock_delay: parse(&values, "lock_delay")?,
+        max_lock_resets: parse(&values, "lock_resets")?,
+        data_dir: None,
+        debug: parse(&values, "debug")?,
+    };
+
+    if !(MIN_FIELD_WIDTH..=MAX_FIELD_WIDTH).contains(&config.field_width)
+        || !(MIN_FIELD_HEIGHT..=MAX_FIELD_HEIGHT).contains(&config.field_height)
+        || config.hidden_rows > MAX_FIELD_HEIGHT
+        || config.preview_count > MAX_PREVIEW_COUNT
+    {
+        return Err("Replay has an unsupported field or preview size".into());
+    }
+
+    let pieces = PieceSet::parse(value(&values, "pieces")?, piece_text)?;
+
+    Ok((config, pieces))
+}
+
+fn value<'a>(values: &HashMap<&str, &'a str>, key: &str) -> Result<&'a str, String> {
+    values.get(key).copied().ok_or_else(|| format!("Missing {} in replay header", key))
+}
+
+fn parse<T: FromStr>(values: &HashMap<&str, &str>, key: &str) -> Result<T, String> {
+    value(values, key)?.parse().map_err(|_| format!("Malformed {} in replay header", key))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::randomizer::RandomizerKind;
+
+    #[test]
+    fn header_round_trip() {
+        let config = Config {
+            seed: Some(42),
+            randomizer: RandomizerKind::Tgm,
+            field_width: 12,
+            field_height: 24,
+            preview_count: 3,
+            start_level: 5,
+            debug: true,
+            ..Config::default()
+        };
+        let pieces = PieceSet::parse("domino", "piece D red\n##\n..\n").unwrap();
+
+        let (read_config, read_pieces) = parse_replay_header(&replay_header(&config, &pieces)).unwrap();
+        assert_eq!(read_config.seed, Some(42));
+        assert_eq!(read_config.randomizer, RandomizerKind::Tgm);
+        assert_eq!((read_config.field_width, read_config.field_height), (12, 24));
+        assert_eq!(read_config.hidden_rows, config.hidden_rows);
+        assert_eq!(read_config.preview_count, 3);
+        assert_eq!(read_config.start_level, 5);
+        assert_eq!(read_config.lock_delay, config.lock_delay);
+        assert_eq!(read_config.max_lock_resets, config.max_lock_resets);
+        assert!(read_config.data_dir.is_none());
+        assert!(read_config.debug);
+        assert_eq!(read_pieces.name(), "domino");
+        assert_eq!(read_pieces.letters(), vec!['D']);
+    }
+
+    #[test]
+    fn header_seed_only() {
+        let (config, pieces) = parse_replay_header(&7u64.to_le_bytes()).unwrap();
+        assert_eq!(config.seed, Some(7));
+        assert!(config.data_dir.is_none());
+        assert_eq!(pieces.name(), PieceSet::standard().name());
+    }
+
+    #[test]
+    fn header_malformed() {
+        let header = String::from_utf8(replay_header(&Config::default(), &PieceSet::standard())).unwrap();
+        assert!(parse_replay_header(b"").is_err());
+        assert!(parse_replay_header(header.replace("field_width 10", "field_width 1000").as_bytes()).is_err());
+        assert!(parse_replay_header(header.replace("randomizer bag7", "randomizer dice").as_bytes()).is_err());
+        assert!(parse_replay_header(header.split("\n\n").next().unwrap().as_bytes()).is_err());
+    }
+}
@@ -0,0 +1,329 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+use engine::base::Number;
+
+use crate::scoring::TSpin;
+use crate::storage::{read_optional, write_atomic};
+
+const HEADER: &str = "r0t0blocks save";
+// versions before 3 did not record the field size, which used to be fixed;
+// version 3 dropped garbage still waiting to rise
+const VERSION: u32 = 4;
+// Resuming draws this many pieces or garbage holes again to catch the randomizer up, far more
+// than any real game gets through.
+const MAX_DRAWS: u64 = 10_000_000;
+
+/// Everything needed to continue an interrupted game.
+pub struct SavedGame {
+    pub mode: String,
+    /// Name of the piece set the game was played with.
+    pub pieces: String,
+    pub randomizer: String,
+    pub seed: u64,
+    /// Pieces drawn from the randomizer, used to bring back its state.
+    pub draws: u64,
+    pub queue: Vec<usize>,
+    /// Field size the game was played with, as in `Config`.
+    pub field_width: usize,
+    pub field_height: usize,
+    pub hidden_rows: usize,
+    /// Field rows from the top, as space separated cell tokens.
+    pub field: Vec<String>,
+    pub piece: usize,
+    pub rotation: usize,
+    pub x: Number,
+    pub y: Number,
+    pub hold: Option<usize>,
+    pub is_hold_used: bool,
+    pub score: Number,
+    pub combo: Number,
+    pub is_back_to_back_ready: bool,
+    pub level: Number,
+    pub lines: Number,
+    pub ticks: Number,
+    pub gravity: Number,
+    pub lock_elapsed: Number,
+    pub lock_resets: Number,
+    pub lowest_y: Number,
+    pub last_kick: Option<usize>,
+    pub line_clear_elapsed: Number,
+    pub line_clear_changes: Number,
+    pub pending_t_spin: TSpin,
+    /// Garbage attacks waiting to rise, oldest first.
+    pub garbage: Vec<Number>,
+    /// Garbage holes drawn so far, used to bring back their generator.
+    pub garbage_holes: u64,
+    /// Changed from the debug console, which keeps it off the high scores.
+    pub is_modified: bool,
+}
+
+impl SavedGame {
+    /// Returns `None` when there is no saved game.
+    pub fn load(path: &Path) -> Result<Option<SavedGame>, String> {
+        match read_optional(path)? {
+            Some(contents) => Self::parse(&contents).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        write_atomic(path, &self.to_text())
+    }
+
+    pub fn remove(path: &Path) -> Result<(), String> {
+        fs::remove_file(path).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    fn to_text(&self) -> String {
+
+        let mut lines = vec![
+            format!("{} {}", HEADER, VERSION),
+            format!("mode {}", self.mode),
+            format!("pieces {}", self.pieces),
+            format!("randomizer {}", self.randomizer),
+            format!("seed {}", self.seed),
+            format!("draws {}", self.draws),
+            format!("queue {}", list_to_text(&self.queue)),
+            format!("field_width {}", self.field_width),
+            format!("field_height {}", self.field_height),
+            format!("hidden_rows {}", self.hidden_rows),
+            format!("piece {}", self.piece),
+            format!("rotation {}", self.rotation),
+            format!("x {}", self.x),
+            format!("y {}", self.y),
+            format!("hold {}", optional_to_text(self.hold)),
+            format!("hold_used {}", self.is_hold_used),
+            format!("score {}", self.score),
+            format!("combo {}", self.combo),
+            format!("back_to_back {}", self.is_back_to_back_ready),
+            format!("level {}", self.level),
+            format!("lines {}", self.lines),
+            format!("ticks {}", self.ticks),
+            format!("gravity {}", self.gravity),
+            format!("lock_elapsed {}", self.lock_elapsed),
+            format!("lock_resets {}", self.lock_resets),
+            format!("lowest_y {}", self.lowest_y),
+            format!("last_kick {}", optional_to_text(self.last_kick)),
+            format!("line_clear_elapsed {}", self.line_clear_elapsed),
+            format!("line_clear_changes {}", self.line_clear_changes),
+            format!("t_spin {}", t_spin_to_text(self.pending_t_spin)),
+            format!("garbage {}", list_to_text(&self.garbage)),
+            format!("garbage_holes {}", self.garbage_holes),
+            format!("modified {}", self.is_modified),
+        ];
+        lines.extend(self.field.iter().map(|row| format!("row {}", row)));
+
+        lines.join("\n") + "\n"
+    }
+
+    fn parse(contents: &str) -> Result<SavedGame, String> {
+        let mut lines = contents.lines();
+
+        let version = lines.next()
+            .and_then(|line| line.strip_prefix(HEADER))
+            .and_then(|version| version.trim().parse::<u32>().ok())
+            .ok_or("Not a saved game")?;
+        if !(3..=VERSION).contains(&version) {
+            return Err(format!("Unsupported save version {}", version));
+        }
+
+        let mut values = HashMap::new();
+        let mut field = Vec::new();
+        for line in lines.filter(|line| !line.is_empty()) {
+            let (key, value) = line.split_once(' ').ok_or_else(|| format!("Malformed save line: {}", line))?;
+            if key == "row" {
+                field.push(value.to_string());
+            } else {
+                values.insert(key, value);
+            }
+        }
+
+        let draws = parse(&values, "draws")?;
+        if draws > MAX_DRAWS {
+            return Err("Malformed draws in save".into());
+        }
+        let (garbage, garbage_holes) = if version >= 4 {
+            (parse_list(&values, "garbage")?, parse(&values, "garbage_holes")?)
+        } else {
+            (Vec::new(), 0)
+        };
+        if garbage_holes > MAX_DRAWS {
+            return Err("Malformed garbage_holes in save".into());
+        }
+
+        Ok(SavedGame {
+            mode: value(&values, "mode")?.to_string(),
+            pieces: value(&values, "pieces")?.to_string(),
+            randomizer: value(&values, "randomizer")?.to_string(),
+            seed: parse(&values, "seed")?,
+            draws,
+            queue: parse_list(&values, "queue")?,
+            field_width: parse(&values, "field_width")?,
+            field_height: parse(&values, "field_height")?,
+            hidden_rows: parse(&values, "hidden_rows")?,
+            field,
+            piece: parse(&values, "piece")?,
+            rotation: parse(&values, "rotation")?,
+            x: parse(&values, "x")?,
+            y: parse(&values, "y")?,
+            hold: parse_optional(&values, "hold")?,
+            is_hold_used: parse(&values, "hold_used")?,
+            score: parse(&values, "score")?,
+            combo: parse(&values, "combo")?,
+            is_back_to_back_ready: parse(&values, "back_to_back")?,
+            level: parse(&values, "level")?,
+            lines: parse(&values, "lines")?,
+            ticks: parse(&values, "ticks")?,
+            gravity: parse(&values, "gravity")?,
+            lock_elapsed: parse(&values, "lock_elapsed")?,
+            lock_resets: parse(&values, "lock_resets")?,
+            lowest_y: parse(&values, "lowest_y")?,
+            last_kick: parse_optional(&values, "last_kick")?,
+            line_clear_elapsed: parse(&values, "line_clear_elapsed")?,
+            line_clear_changes: parse(&values, "line_clear_changes")?,
+            pending_t_spin: t_spin_from_text(value(&values, "t_spin")?)?,
+            garbage,
+            garbage_holes,
+            is_modified: parse(&values, "modified")?,
+        })
+    }
+}
+
+fn value<'a>(values: &HashMap<&str, &'a str>, key: &str) -> Result<&'a str, String> {
+    values.get(key).copied().ok_or_else(|| format!("Missing {} in save", key))
+}
+
+fn parse<T: FromStr>(values: &HashMap<&str, &str>, key: &str) -> Result<T, String> {
+    value(values, key)?.parse().map_err(|_| format!("Malformed {} in save", key))
+}
+
+fn parse_optional<T: FromStr>(values: &HashMap<&str, &str>, key: &str) -> Result<Option<T>, String> {
+    match value(values, key)? {
+        "-" => Ok(None),
+        _ => parse(values, key).map(Some),
+    }
+}
+
+// Comma separated, empty for no items.
+fn parse_list<T: FromStr>(values: &HashMap<&str, &str>, key: &str) -> Result<Vec<T>, String> {
+    value(values, key)?.split(',')
+        .filter(|item| !item.is_empty())
+        .map(|item| item.parse().map_err(|_| format!("Malformed {} in save", key)))
+        .collect()
+}
+
+fn list_to_text<T: ToString>(items: &[T]) -> String {
+    items.iter().map(|item| item.to_string()).collect::<Vec<_>>().join(",")
+}
+
+fn optional_to_text(value: Option<usize>) -> String {
+    value.map_or_else(|| "-".to_string(), |value| value.to_string())
+}
+
+fn t_spin_to_text(t_spin: TSpin) -> &'static str {
+    match t_spin {
+        TSpin::None => "none",
+        TSpin::Mini => "mini",
+        TSpin::Full => "full",
+    }
+}
+
+fn t_spin_from_text(text: &str) -> Result<TSpin, String> {
+    match text {
+        "none" => Ok(TSpin::None),
+        "mini" => Ok(TSpin::Mini),
+        "full" => Ok(TSpin::Full),
+        _ => Err(format!("Unknown T-spin in save: {}", text)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn saved_game() -> SavedGame {
+        SavedGame {
+            mode: "Marathon".to_string(),
+            pieces: "tetromino".to_string(),
+            randomizer: "bag7".to_string(),
+            seed: 42,
+            draws: 12,
+            queue: vec![3, 1, 4],
+            field_width: 4,
+            field_height: 2,
+            hidden_rows: 1,
+            field: vec![". . . .".to_string(), ". T . .".to_string(), "# # . #".to_string()],
+            piece: 2,
+            rotation: 1,
+            x: 1,
+            y: -1,
+            hold: Some(5),
+            is_hold_used: true,
+            score: 1200,
+            combo: 2,
+            is_back_to_back_ready: true,
+            level: 3,
+            lines: 25,
+            ticks: 9000,
+            gravity: 100,
+            lock_elapsed: 7,
+            lock_resets: 4,
+            lowest_y: 1,
+            last_kick: Some(4),
+            line_clear_elapsed: 3,
+            line_clear_changes: 5,
+            pending_t_spin: TSpin::Mini,
+            garbage: vec![2, 4],
+            garbage_holes: 3,
+            is_modified: true,
+        }
+    }
+
+    #[test]
+    fn save_round_trip() {
+        let text = saved_game().to_text();
+        let saved = SavedGame::parse(&text).unwrap();
+        assert_eq!(saved.to_text(), text);
+        assert_eq!((saved.draws, saved.queue), (12, vec![3, 1, 4]));
+        assert_eq!(saved.pending_t_spin, TSpin::Mini);
+        assert_eq!((saved.garbage, saved.garbage_holes), (vec![2, 4], 3));
+    }
+
+    #[test]
+    fn version_3_has_no_garbage() {
+        let text = saved_game().to_text()
+            .replace("r0t0blocks save 4", "r0t0blocks save 3")
+            .replace("garbage 2,4\n", "")
+            .replace("garbage_holes 3\n", "");
+        let saved = SavedGame::parse(&text).unwrap();
+        assert!(saved.garbage.is_empty());
+        assert_eq!(saved.garbage_holes, 0);
+    }
+
+    #[test]
+    fn draws_are_bounded() {
+        let saved = SavedGame {
+            draws: MAX_DRAWS,
+            ..saved_game()
+        };
+        assert!(SavedGame::parse(&saved.to_text()).is_ok());
+        let saved = SavedGame {
+            draws: MAX_DRAWS + 1,
+            ..saved_game()
+        };
+        assert!(SavedGame::parse(&saved.to_text()).is_err());
+    }
+
+    #[test]
+    fn older_versions_are_rejected() {
+        assert!(SavedGame::parse("r0t0blocks save 2\nmode Marathon\n").is_err());
+    }
+
+    #[test]
+    fn missing_values() {
+        assert!(SavedGame::parse("r0t0blocks save 4\nmode Marathon\n").is_err());
+    }
+}
@@ -0,0 +1,255 @@
+use std::cmp::min;
+
+use engine::base::Number;
+
+const MAX_SCORE: Number = 9999999;
+
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum TSpin {
+    None,
+    Mini,
+    Full,
+}
+
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub struct Clear {
+    pub lines: Number,
+    pub t_spin: TSpin,
+}
+
+impl Clear {
+    /// Tetrises and T-spins that clear lines keep a back-to-back chain going.
+    pub fn is_difficult(&self) -> bool {
+        self.lines >= 4 || (self.lines > 0 && self.t_spin != TSpin::None)
+    }
+
+    fn base_points(&self) -> Number {
+        match (self.t_spin, self.lines) {
+            (TSpin::None, 0) => 0,
+            (TSpin::None, 1) => 100,
+            (TSpin::None, 2) => 300,
+            (TSpin::None, 3) => 500,
+            (TSpin::None, _) => 800,
+            (TSpin::Mini, 0) => 100,
+            (TSpin::Mini, 1) => 200,
+            (TSpin::Mini, _) => 400,
+            (TSpin::Full, 0) => 400,
+            (TSpin::Full, 1) => 800,
+            (TSpin::Full, 2) => 1200,
+            (TSpin::Full, _) => 1600,
+        }
+    }
+
+    fn name(&self) -> Option<String> {
+        let lines = match self.lines {
+            0 => "",
+            1 => "SINGLE",
+            2 => "DOUBLE",
+            3 => "TRIPLE",
+            _ => "TETRIS",
+        };
+        let name = match self.t_spin {
+            TSpin::None if self.lines == 0 => return None,
+            TSpin::None => lines.to_string(),
+            TSpin::Mini => format!("MINI T-SPIN {}", lines),
+            TSpin::Full => format!("T-SPIN {}", lines),
+        };
+        Some(name.trim_end().to_string())
+    }
+}
+
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub struct Award {
+    pub clear: Clear,
+    pub points: Number,
+    pub is_back_to_back: bool,
+    pub combo: Number,
+}
+
+impl Award {
+    /// Clear type as shown to the player, e.g. "B2B T-SPIN DOUBLE".
+    pub fn text(&self) -> Option<String> {
+        let name = self.clear.name()?;
+        if self.is_back_to_back {
+            Some(format!("B2B {}", name))
+        } else {
+            Some(name)
+        }
+    }
+}
+
+pub struct Scoring {
+    score: Number,
+    combo: Number,
+    is_back_to_back_ready: bool,
+}
+
+impl Scoring {
+    pub fn new() -> Scoring {
+        Scoring {
+            score: 0,
+            combo: -1,
+            is_back_to_back_ready: false,
+        }
+    }
+
+    /// Continues from a saved game.
+    pub fn with_state(score: Number, combo: Number, is_back_to_back_ready: bool) -> Scoring {
+        Scoring {
+            score,
+            combo,
+            is_back_to_back_ready,
+        }
+    }
+
+    pub fn score(&self) -> Number {
+        self.score
+    }
+
+    pub fn is_back_to_back_ready(&self) -> bool {
+        self.is_back_to_back_ready
+    }
+
+    /// Consecutive line clears minus one, or -1 when there is no chain.
+    pub fn combo(&self) -> Number {
+        self.combo
+    }
+
+    pub fn soft_drop(&mut self, cells: Number) {
+        self.add(cells);
+    }
+
+    pub fn hard_drop(&mut self, cells: Number) {
+        self.add(2 * cells);
+    }
+
+    pub fn lock(&mut self, clear: Clear, level: Number) -> Award {
+        let mut points = clear.base_points() * level;
+
+        let is_back_to_back = clear.is_difficult() && self.is_back_to_back_ready;
+        if is_back_to_back {
+            points = points * 3 / 2;
+        }
+        if clear.lines > 0 {
+            self.is_back_to_back_ready = clear.is_difficult();
+            self.combo += 1;
+        } else {
+            self.combo = -1;
+        }
+        if self.combo > 0 {
+            points += 50 * self.combo * level;
+        }
+
+        self.add(points);
+
+        Award {
+            clear,
+            points,
+            is_back_to_back,
+            combo: self.combo,
+        }
+    }
+
+    fn add(&mut self, points: Number) {
+        self.score = min(self.score + points, MAX_SCORE);
+    }
+}
+
+impl Default for Scoring {
+    fn default() -> Scoring {
+        Scoring::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clear(lines: Number, t_spin: TSpin) -> Clear {
+        Clear { lines, t_spin }
+    }
+
+    #[test]
+    fn line_clears() {
+        for &(lines, points) in &[(1, 100), (2, 300), (3, 500), (4, 800)] {
+            let mut scoring = Scoring::new();
+            let award = scoring.lock(clear(lines, TSpin::None), 2);
+            assert_eq!(award.points, 2 * points);
+            assert_eq!(scoring.score(), 2 * points);
+        }
+    }
+
+    #[test]
+    fn t_spins() {
+        let mut scoring = Scoring::new();
+        assert_eq!(scoring.lock(clear(0, TSpin::Full), 1).points, 400);
+        assert_eq!(scoring.lock(clear(0, TSpin::Mini), 1).points, 100);
+
+        let award = Scoring::new().lock(clear(2, TSpin::Full), 1);
+        assert_eq!(award.points, 1200);
+        assert_eq!(award.text().as_deref(), Some("T-SPIN DOUBLE"));
+
+        let award = Scoring::new().lock(clear(1, TSpin::Mini), 1);
+        assert_eq!(award.points, 200);
+        assert_eq!(award.text().as_deref(), Some("MINI T-SPIN SINGLE"));
+    }
+
+    #[test]
+    fn back_to_back() {
+        let mut scoring = Scoring::new();
+        assert!(!scoring.lock(clear(4, TSpin::None), 1).is_back_to_back);
+        assert!(scoring.is_back_to_back_ready());
+
+        // a T-spin without lines neither breaks nor continues the chain
+        scoring.lock(clear(0, TSpin::Full), 1);
+        let award = scoring.lock(clear(1, TSpin::Full), 1);
+        assert!(award.is_back_to_back);
+        assert_eq!(award.points, 800 * 3 / 2);
+        assert_eq!(award.text().as_deref(), Some("B2B T-SPIN SINGLE"));
+    }
+
+    #[test]
+    fn back_to_back_broken() {
+        let mut scoring = Scoring::new();
+        scoring.lock(clear(4, TSpin::None), 1);
+        scoring.lock(clear(2, TSpin::None), 1);
+        assert!(!scoring.is_back_to_back_ready());
+
+        let award = scoring.lock(clear(4, TSpin::None), 1);
+        assert!(!award.is_back_to_back);
+        assert_eq!(award.text().as_deref(), Some("TETRIS"));
+    }
+
+    #[test]
+    fn combo() {
+        let mut scoring = Scoring::new();
+        assert_eq!(scoring.lock(clear(1, TSpin::None), 1).points, 100);
+        assert_eq!(scoring.lock(clear(1, TSpin::None), 1).points, 150);
+        let award = scoring.lock(clear(2, TSpin::None), 2);
+        assert_eq!(award.combo, 2);
+        assert_eq!(award.points, 600 + 2 * 50 * 2);
+
+        // a piece that clears nothing ends the run
+        let award = scoring.lock(clear(0, TSpin::None), 1);
+        assert_eq!(award.combo, -1);
+        assert_eq!(award.points, 0);
+        assert_eq!(scoring.lock(clear(1, TSpin::None), 1).points, 100);
+        assert_eq!(scoring.score(), 100 + 150 + 800 + 100);
+    }
+
+    #[test]
+    fn drops() {
+        let mut scoring = Scoring::new();
+        scoring.soft_drop(3);
+        assert_eq!(scoring.score(), 3);
+        scoring.hard_drop(10);
+        assert_eq!(scoring.score(), 23);
+    }
+
+    #[test]
+    fn score_is_capped() {
+        let mut scoring = Scoring::with_state(MAX_SCORE - 10, -1, false);
+        scoring.hard_drop(20);
+        assert_eq!(scoring.score(), MAX_SCORE);
+    }
+}
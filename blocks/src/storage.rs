@@ -0,0 +1,51 @@
+use std::env;
+use std::fs::{self, File};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+const APP_DIR: &str = "r0t0blocks";
+
+/// Per-user directory for persistent game data: `$XDG_DATA_HOME`,
+/// `%APPDATA%` or `~/.local/share`, in that order.
+pub fn data_dir() -> Option<PathBuf> {
+    let base = if let Some(dir) = env::var_os("XDG_DATA_HOME").filter(|dir| !dir.is_empty()) {
+        PathBuf::from(dir)
+    } else if let Some(dir) = env::var_os("APPDATA").filter(|dir| !dir.is_empty()) {
+        PathBuf::from(dir)
+    } else {
+        PathBuf::from(env::var_os("HOME")?).join(".local").join("share")
+    };
+    Some(base.join(APP_DIR))
+}
+
+/// Reads a whole file, treating a missing one as empty.
+pub fn read_optional(path: &Path) -> Result<Option<String>, String> {
+    match fs::read_to_string(path) {
+        Ok(contents) => Ok(Some(contents)),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(format!("{}: {}", path.display(), e)),
+    }
+}
+
+/// Writes into a temporary file next to `path` and renames it over, so a
+/// crash never leaves a half-written file behind.
+pub fn write_atomic(path: &Path, contents: &str) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("{}: {}", parent.display(), e))?;
+    }
+
+    let tmp_path = path.with_extension("tmp");
+    let mut file = File::create(&tmp_path).map_err(|e| format!("{}: {}", tmp_path.display(), e))?;
+    file.write_all(contents.as_bytes()).map_err(|e| e.to_string())?;
+    file.sync_all().map_err(|e| e.to_string())?;
+    drop(file);
+
+    fs::rename(&tmp_path, path).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// Moves an unreadable file out of the way so that it is not overwritten.
+pub fn set_aside(path: &Path) -> Result<PathBuf, String> {
+    let corrupt_path = path.with_extension("corrupt");
+    fs::rename(path, &corrupt_path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    Ok(corrupt_path)
+}
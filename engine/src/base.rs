@@ -7,4 +7,13 @@ pub trait App {
     fn handle_input(&mut self, input: &Input);
     fn tick(&mut self);
     fn draw(&self, buf: &mut ScreenBuffer);
+
+    /// Checked after every tick; backends stop running once it is `true`.
+    fn is_quit_requested(&self) -> bool {
+        false
+    }
+}
+
+pub trait Backend {
+    fn run<A: App>(&mut self, app: &mut A) -> Result<(), String>;
 }
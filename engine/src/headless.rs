@@ -0,0 +1,157 @@
+use crate::base::{App, Backend};
+use crate::input::{Input, Key};
+use crate::video::ScreenBuffer;
+use crate::{render, update};
+
+pub trait InputSource {
+    fn feed(&mut self, tick: u64, input: &mut Input);
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct ScriptEvent {
+    pub tick: u64,
+    pub key: Key,
+    pub is_pressed: bool,
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct ScriptText {
+    pub tick: u64,
+    pub text: String,
+}
+
+#[derive(Clone, Default)]
+pub struct Script {
+    events: Vec<ScriptEvent>,
+    next_event: usize,
+    texts: Vec<ScriptText>,
+    next_text: usize,
+}
+
+impl Script {
+    pub fn new() -> Script {
+        Script {
+            events: Vec::new(),
+            next_event: 0,
+            texts: Vec::new(),
+            next_text: 0,
+        }
+    }
+
+    pub fn press(mut self, tick: u64, key: Key) -> Script {
+        self.push(tick, key, true);
+        self
+    }
+
+    pub fn release(mut self, tick: u64, key: Key) -> Script {
+        self.push(tick, key, false);
+        self
+    }
+
+    pub fn tap(self, tick: u64, key: Key) -> Script {
+        self.press(tick, key).release(tick + 1, key)
+    }
+
+    /// Text typed at the given tick, without the keys that would type it.
+    pub fn type_text(mut self, tick: u64, text: &str) -> Script {
+        self.push_text(tick, text);
+        self
+    }
+
+    pub fn events(&self) -> &[ScriptEvent] {
+        &self.events
+    }
+
+    pub fn texts(&self) -> &[ScriptText] {
+        &self.texts
+    }
+
+    pub(crate) fn push(&mut self, tick: u64, key: Key, is_pressed: bool) {
+        let index = self.events.partition_point(|e| e.tick <= tick);
+        self.events.insert(index, ScriptEvent { tick, key, is_pressed });
+    }
+
+    pub(crate) fn push_text(&mut self, tick: u64, text: &str) {
+        let index = self.texts.partition_point(|t| t.tick <= tick);
+        self.texts.insert(index, ScriptText { tick, text: text.to_string() });
+    }
+}
+
+impl InputSource for Script {
+    fn feed(&mut self, tick: u64, input: &mut Input) {
+        while let Some(event) = self.events.get(self.next_event) {
+            if event.tick > tick {
+                break;
+            }
+            input.set(event.key, event.is_pressed);
+            self.next_event += 1;
+        }
+        while let Some(text) = self.texts.get(self.next_text) {
+            if text.tick > tick {
+                break;
+            }
+            input.push_text(&text.text);
+            self.next_text += 1;
+        }
+    }
+}
+
+pub struct HeadlessParams {
+    pub width_in_tiles: u32,
+    pub height_in_tiles: u32,
+    pub tick_count: u64,
+    pub ticks_per_frame: u64,
+}
+
+pub struct Headless<S> {
+    params: HeadlessParams,
+    source: S,
+    frames: Vec<ScreenBuffer>,
+}
+
+impl<S: InputSource> Headless<S> {
+    pub fn new(params: HeadlessParams, source: S) -> Headless<S> {
+        Headless {
+            params,
+            source,
+            frames: Vec::new(),
+        }
+    }
+
+    pub fn frames(&self) -> &[ScreenBuffer] {
+        &self.frames
+    }
+
+    pub fn into_frames(self) -> Vec<ScreenBuffer> {
+        self.frames
+    }
+}
+
+impl<S: InputSource> Backend for Headless<S> {
+    fn run<A: App>(&mut self, app: &mut A) -> Result<(), String> {
+        if self.params.ticks_per_frame == 0 {
+            return Err("Ticks per frame must be positive".into());
+        }
+
+        let mut screen_buffer = ScreenBuffer::new(self.params.width_in_tiles as usize, self.params.height_in_tiles as usize);
+
+        let mut input = Input::new();
+
+        for tick in 0..self.params.tick_count {
+            self.source.feed(tick, &mut input);
+
+            update(app, &mut input);
+
+            if app.is_quit_requested() {
+                break;
+            }
+
+            if (tick + 1) % self.params.ticks_per_frame == 0 {
+                render(app, &mut screen_buffer);
+                self.frames.push(screen_buffer.clone());
+            }
+        }
+
+        Ok(())
+    }
+}
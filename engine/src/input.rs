@@ -1,11 +1,15 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+use std::str::FromStr;
 
+use sdl2::controller::{Axis, Button};
 use sdl2::event::Event;
 use sdl2::keyboard::Scancode;
 
 use strum::IntoEnumIterator;
-use strum_macros::EnumIter;
+use strum_macros::{EnumIter, EnumString, IntoStaticStr};
 
+#[derive(Clone)]
 struct Latch {
     prev: bool,
     curr: bool,
@@ -23,6 +27,10 @@ impl Latch {
         self.curr = value;
     }
 
+    fn is_set(&self) -> bool {
+        self.curr
+    }
+
     fn is_front_edge(&self) -> bool {
         self.curr && !self.prev
     }
@@ -36,7 +44,7 @@ impl Latch {
     }
 }
 
-#[derive(Eq, PartialEq, Copy, Clone, Debug, EnumIter)]
+#[derive(Eq, PartialEq, Hash, Copy, Clone, Debug, EnumIter, EnumString, IntoStaticStr)]
 pub enum Key {
     Up,
     Down,
@@ -83,114 +91,778 @@ pub enum Key {
     Num0,
     Minus,
     Equals,
+    Backspace,
+    Tab,
+    CapsLock,
+    LeftShift,
+    RightShift,
+    LeftCtrl,
+    RightCtrl,
+    LeftAlt,
+    RightAlt,
+    LeftGui,
+    RightGui,
+    Application,
+    Insert,
+    Delete,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+    PrintScreen,
+    ScrollLock,
+    Pause,
+    F1,
+    F2,
+    F3,
+    F4,
+    F5,
+    F6,
+    F7,
+    F8,
+    F9,
+    F10,
+    F11,
+    F12,
+    Grave,
+    LeftBracket,
+    RightBracket,
+    Backslash,
+    NonUsBackslash,
+    Semicolon,
+    Apostrophe,
+    Comma,
+    Period,
+    Slash,
+    NumLock,
+    Keypad1,
+    Keypad2,
+    Keypad3,
+    Keypad4,
+    Keypad5,
+    Keypad6,
+    Keypad7,
+    Keypad8,
+    Keypad9,
+    Keypad0,
+    KeypadPeriod,
+    KeypadPlus,
+    KeypadMinus,
+    KeypadMultiply,
+    KeypadDivide,
+    KeypadEquals,
+    KeypadEnter,
+    PadA,
+    PadB,
+    PadX,
+    PadY,
+    PadBack,
+    PadGuide,
+    PadStart,
+    PadLeftStick,
+    PadRightStick,
+    PadLeftShoulder,
+    PadRightShoulder,
+    PadUp,
+    PadDown,
+    PadLeft,
+    PadRight,
+    PadLeftTrigger,
+    PadRightTrigger,
+    PadStickUp,
+    PadStickDown,
+    PadStickLeft,
+    PadStickRight,
+}
+
+/// Keys that come from game controllers rather than the keyboard, which
+/// are the ones without a scancode. Buttons of all connected pads are
+/// merged, the left stick and the triggers press keys of their own once they
+/// move far enough. Replays refer to pad keys by their order in `Key`, so
+/// new ones go after the existing ones.
+pub fn pad_keys() -> impl Iterator<Item = Key> {
+    Key::iter().filter(|key| key.scancode().is_none())
 }
 
-impl From<Key> for Scancode {
-    fn from(key: Key) -> Self {
-        match key {
-            Key::Up => Scancode::Up,
-            Key::Down => Scancode::Down,
-            Key::Left => Scancode::Left,
-            Key::Right => Scancode::Right,
-            Key::Escape => Scancode::Escape,
-            Key::Return => Scancode::Return,
-            Key::Space => Scancode::Space,
-            Key::A => Scancode::A,
-            Key::B => Scancode::B,
-            Key::C => Scancode::C,
-            Key::D => Scancode::D,
-            Key::E => Scancode::E,
-            Key::F => Scancode::F,
-            Key::G => Scancode::G,
-            Key::H => Scancode::H,
-            Key::I => Scancode::I,
-            Key::J => Scancode::J,
-            Key::K => Scancode::K,
-            Key::L => Scancode::L,
-            Key::M => Scancode::M,
-            Key::N => Scancode::N,
-            Key::O => Scancode::O,
-            Key::P => Scancode::P,
-            Key::Q => Scancode::Q,
-            Key::R => Scancode::R,
-            Key::S => Scancode::S,
-            Key::T => Scancode::T,
-            Key::U => Scancode::U,
-            Key::V => Scancode::V,
-            Key::W => Scancode::W,
-            Key::X => Scancode::X,
-            Key::Y => Scancode::Y,
-            Key::Z => Scancode::Z,
-            Key::Num1 => Scancode::Num1,
-            Key::Num2 => Scancode::Num2,
-            Key::Num3 => Scancode::Num3,
-            Key::Num4 => Scancode::Num4,
-            Key::Num5 => Scancode::Num5,
-            Key::Num6 => Scancode::Num6,
-            Key::Num7 => Scancode::Num7,
-            Key::Num8 => Scancode::Num8,
-            Key::Num9 => Scancode::Num9,
-            Key::Num0 => Scancode::Num0,
-            Key::Minus => Scancode::Minus,
-            Key::Equals => Scancode::Equals,
+impl Key {
+    /// Name of the key in bindings files, same as the variant.
+    pub fn name(self) -> &'static str {
+        self.into()
+    }
+
+    pub fn by_name(name: &str) -> Option<Key> {
+        Key::from_str(name).ok()
+    }
+
+    /// Keyboard keys have a scancode, pad keys do not.
+    pub fn scancode(self) -> Option<Scancode> {
+        match self {
+            Key::Up => Some(Scancode::Up),
+            Key::Down => Some(Scancode::Down),
+            Key::Left => Some(Scancode::Left),
+            Key::Right => Some(Scancode::Right),
+            Key::Escape => Some(Scancode::Escape),
+            Key::Return => Some(Scancode::Return),
+            Key::Space => Some(Scancode::Space),
+            Key::A => Some(Scancode::A),
+            Key::B => Some(Scancode::B),
+            Key::C => Some(Scancode::C),
+            Key::D => Some(Scancode::D),
+            Key::E => Some(Scancode::E),
+            Key::F => Some(Scancode::F),
+            Key::G => Some(Scancode::G),
+            Key::H => Some(Scancode::H),
+            Key::I => Some(Scancode::I),
+            Key::J => Some(Scancode::J),
+            Key::K => Some(Scancode::K),
+            Key::L => Some(Scancode::L),
+            Key::M => Some(Scancode::M),
+            Key::N => Some(Scancode::N),
+            Key::O => Some(Scancode::O),
+            Key::P => Some(Scancode::P),
+            Key::Q => Some(Scancode::Q),
+            Key::R => Some(Scancode::R),
+            Key::S => Some(Scancode::S),
+            Key::T => Some(Scancode::T),
+            Key::U => Some(Scancode::U),
+            Key::V => Some(Scancode::V),
+            Key::W => Some(Scancode::W),
+            Key::X => Some(Scancode::X),
+            Key::Y => Some(Scancode::Y),
+            Key::Z => Some(Scancode::Z),
+            Key::Num1 => Some(Scancode::Num1),
+            Key::Num2 => Some(Scancode::Num2),
+            Key::Num3 => Some(Scancode::Num3),
+            Key::Num4 => Some(Scancode::Num4),
+            Key::Num5 => Some(Scancode::Num5),
+            Key::Num6 => Some(Scancode::Num6),
+            Key::Num7 => Some(Scancode::Num7),
+            Key::Num8 => Some(Scancode::Num8),
+            Key::Num9 => Some(Scancode::Num9),
+            Key::Num0 => Some(Scancode::Num0),
+            Key::Minus => Some(Scancode::Minus),
+            Key::Equals => Some(Scancode::Equals),
+            Key::Backspace => Some(Scancode::Backspace),
+            Key::Tab => Some(Scancode::Tab),
+            Key::CapsLock => Some(Scancode::CapsLock),
+            Key::LeftShift => Some(Scancode::LShift),
+            Key::RightShift => Some(Scancode::RShift),
+            Key::LeftCtrl => Some(Scancode::LCtrl),
+            Key::RightCtrl => Some(Scancode::RCtrl),
+            Key::LeftAlt => Some(Scancode::LAlt),
+            Key::RightAlt => Some(Scancode::RAlt),
+            Key::LeftGui => Some(Scancode::LGui),
+            Key::RightGui => Some(Scancode::RGui),
+            Key::Application => Some(Scancode::Application),
+            Key::Insert => Some(Scancode::Insert),
+            Key::Delete => Some(Scancode::Delete),
+            Key::Home => Some(Scancode::Home),
+            Key::End => Some(Scancode::End),
+            Key::PageUp => Some(Scancode::PageUp),
+            Key::PageDown => Some(Scancode::PageDown),
+            Key::PrintScreen => Some(Scancode::PrintScreen),
+            Key::ScrollLock => Some(Scancode::ScrollLock),
+            Key::Pause => Some(Scancode::Pause),
+            Key::F1 => Some(Scancode::F1),
+            Key::F2 => Some(Scancode::F2),
+            Key::F3 => Some(Scancode::F3),
+            Key::F4 => Some(Scancode::F4),
+            Key::F5 => Some(Scancode::F5),
+            Key::F6 => Some(Scancode::F6),
+            Key::F7 => Some(Scancode::F7),
+            Key::F8 => Some(Scancode::F8),
+            Key::F9 => Some(Scancode::F9),
+            Key::F10 => Some(Scancode::F10),
+            Key::F11 => Some(Scancode::F11),
+            Key::F12 => Some(Scancode::F12),
+            Key::Grave => Some(Scancode::Grave),
+            Key::LeftBracket => Some(Scancode::LeftBracket),
+            Key::RightBracket => Some(Scancode::RightBracket),
+            Key::Backslash => Some(Scancode::Backslash),
+            Key::NonUsBackslash => Some(Scancode::NonUsBackslash),
+            Key::Semicolon => Some(Scancode::Semicolon),
+            Key::Apostrophe => Some(Scancode::Apostrophe),
+            Key::Comma => Some(Scancode::Comma),
+            Key::Period => Some(Scancode::Period),
+            Key::Slash => Some(Scancode::Slash),
+            Key::NumLock => Some(Scancode::NumLockClear),
+            Key::Keypad1 => Some(Scancode::Kp1),
+            Key::Keypad2 => Some(Scancode::Kp2),
+            Key::Keypad3 => Some(Scancode::Kp3),
+            Key::Keypad4 => Some(Scancode::Kp4),
+            Key::Keypad5 => Some(Scancode::Kp5),
+            Key::Keypad6 => Some(Scancode::Kp6),
+            Key::Keypad7 => Some(Scancode::Kp7),
+            Key::Keypad8 => Some(Scancode::Kp8),
+            Key::Keypad9 => Some(Scancode::Kp9),
+            Key::Keypad0 => Some(Scancode::Kp0),
+            Key::KeypadPeriod => Some(Scancode::KpPeriod),
+            Key::KeypadPlus => Some(Scancode::KpPlus),
+            Key::KeypadMinus => Some(Scancode::KpMinus),
+            Key::KeypadMultiply => Some(Scancode::KpMultiply),
+            Key::KeypadDivide => Some(Scancode::KpDivide),
+            Key::KeypadEquals => Some(Scancode::KpEquals),
+            Key::KeypadEnter => Some(Scancode::KpEnter),
+            _ => None,
+        }
+    }
+
+    fn by_pad_button(button: Button) -> Key {
+        match button {
+            Button::A => Key::PadA,
+            Button::B => Key::PadB,
+            Button::X => Key::PadX,
+            Button::Y => Key::PadY,
+            Button::Back => Key::PadBack,
+            Button::Guide => Key::PadGuide,
+            Button::Start => Key::PadStart,
+            Button::LeftStick => Key::PadLeftStick,
+            Button::RightStick => Key::PadRightStick,
+            Button::LeftShoulder => Key::PadLeftShoulder,
+            Button::RightShoulder => Key::PadRightShoulder,
+            Button::DPadUp => Key::PadUp,
+            Button::DPadDown => Key::PadDown,
+            Button::DPadLeft => Key::PadLeft,
+            Button::DPadRight => Key::PadRight,
         }
     }
 }
 
+/// Modifier keys held, on either side of the keyboard.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub struct Modifiers {
+    pub shift: bool,
+    pub ctrl: bool,
+    pub alt: bool,
+    pub gui: bool,
+}
+
+/// How far sticks and triggers have to move to press their keys, as
+/// fractions of their full range.
+#[derive(Copy, Clone, Debug)]
+pub struct AxisThresholds {
+    /// Sticks and triggers closer to rest than this count as at rest, the
+    /// range past it is scaled to start from zero.
+    pub deadzone: f32,
+    /// A direction presses its key once it goes past this.
+    pub press: f32,
+    /// A pressed direction is released once it falls back under this, so a
+    /// stick held near the threshold does not chatter.
+    pub release: f32,
+}
+
+impl Default for AxisThresholds {
+    fn default() -> AxisThresholds {
+        AxisThresholds {
+            deadzone: 0.2,
+            press: 0.5,
+            release: 0.35,
+        }
+    }
+}
+
+// What one connected game controller is holding.
+#[derive(Default)]
+struct Pad {
+    buttons: HashSet<Key>,
+    stick: (f32, f32),
+    triggers: (f32, f32),
+    axis_keys: HashSet<Key>,
+}
+
+impl Pad {
+    fn update_axis_keys(&mut self, thresholds: &AxisThresholds) {
+        // the deadzone is round, so diagonals need as much push as the sides
+        let (x, y) = self.stick;
+        let length = x.hypot(y);
+        let scale = if length > 0.0 {
+            past_deadzone(length, thresholds.deadzone) / length
+        } else {
+            0.0
+        };
+        let directions = [
+            (Key::PadStickLeft, -x * scale),
+            (Key::PadStickRight, x * scale),
+            (Key::PadStickUp, -y * scale),
+            (Key::PadStickDown, y * scale),
+            (Key::PadLeftTrigger, past_deadzone(self.triggers.0, thresholds.deadzone)),
+            (Key::PadRightTrigger, past_deadzone(self.triggers.1, thresholds.deadzone)),
+        ];
+
+        for (key, amount) in directions.iter().copied() {
+            let threshold = if self.axis_keys.contains(&key) {
+                thresholds.release
+            } else {
+                thresholds.press
+            };
+            if amount >= threshold {
+                self.axis_keys.insert(key);
+            } else {
+                self.axis_keys.remove(&key);
+            }
+        }
+    }
+
+    fn is_pressed(&self, key: Key) -> bool {
+        self.buttons.contains(&key) || self.axis_keys.contains(&key)
+    }
+}
+
+fn past_deadzone(amount: f32, deadzone: f32) -> f32 {
+    ((amount - deadzone) / (1.0 - deadzone)).clamp(0.0, 1.0)
+}
+
 pub struct Input {
-    keys: HashMap<Scancode, Latch>,
+    keys: HashMap<Key, Latch>,
+    scancodes: HashMap<Scancode, Key>,
+    pads: HashMap<u32, Pad>,
+    axis_thresholds: AxisThresholds,
+    text: String,
 }
 
 impl Input {
     pub fn new() -> Input {
         Input {
-            keys: Key::iter().map(|x| (x.into(), Latch::new())).collect(),
+            keys: Key::iter().map(|key| (key, Latch::new())).collect(),
+            scancodes: Key::iter().filter_map(|key| Some((key.scancode()?, key))).collect(),
+            pads: HashMap::new(),
+            axis_thresholds: AxisThresholds::default(),
+            text: String::new(),
         }
     }
 
+    /// Takes effect the next time a stick or trigger moves.
+    pub fn set_axis_thresholds(&mut self, thresholds: AxisThresholds) {
+        self.axis_thresholds = thresholds;
+    }
+
+    /// Pads are told apart by their joystick instance id. One that is
+    /// unplugged lets go of everything it was holding.
     pub fn on_event(&mut self, event: Event) {
         match event {
             Event::KeyDown {
                 scancode: Some(scancode),
                 ..
             } => {
-                if let Some(latch) = self.keys.get_mut(&scancode) {
-                    latch.set(true);
+                if let Some(&key) = self.scancodes.get(&scancode) {
+                    self.set(key, true);
                 }
             }
             Event::KeyUp {
                 scancode: Some(scancode),
                 ..
             } => {
-                if let Some(latch) = self.keys.get_mut(&scancode) {
-                    latch.set(false);
+                if let Some(&key) = self.scancodes.get(&scancode) {
+                    self.set(key, false);
+                }
+            }
+            Event::TextInput { text, .. } => self.push_text(&text),
+            Event::ControllerButtonDown { which, button, .. } => {
+                self.pads.entry(which).or_default().buttons.insert(Key::by_pad_button(button));
+                self.update_pad_keys();
+            }
+            Event::ControllerButtonUp { which, button, .. } => {
+                self.pads.entry(which).or_default().buttons.remove(&Key::by_pad_button(button));
+                self.update_pad_keys();
+            }
+            Event::ControllerAxisMotion { which, axis, value, .. } => {
+                let pad = self.pads.entry(which).or_default();
+                let value = (value as f32 / i16::MAX as f32).max(-1.0);
+                match axis {
+                    Axis::LeftX => pad.stick.0 = value,
+                    Axis::LeftY => pad.stick.1 = value,
+                    Axis::TriggerLeft => pad.triggers.0 = value,
+                    Axis::TriggerRight => pad.triggers.1 = value,
+                    Axis::RightX | Axis::RightY => return,
                 }
+                pad.update_axis_keys(&self.axis_thresholds);
+                self.update_pad_keys();
+            }
+            Event::ControllerDeviceRemoved { which, .. } => {
+                self.pads.remove(&which);
+                self.update_pad_keys();
             }
             _ => {}
         }
     }
 
+    // A pad key is held while it is held on any pad.
+    fn update_pad_keys(&mut self) {
+        for key in pad_keys() {
+            let is_pressed = self.pads.values().any(|pad| pad.is_pressed(key));
+            if let Some(latch) = self.keys.get_mut(&key) {
+                latch.set(is_pressed);
+            }
+        }
+    }
+
+    pub fn set(&mut self, key: Key, is_pressed: bool) {
+        if let Some(latch) = self.keys.get_mut(&key) {
+            latch.set(is_pressed);
+        }
+    }
+
+    /// Adds typed text, as the keyboard layout produced it.
+    pub fn push_text(&mut self, text: &str) {
+        self.text.push_str(text);
+    }
+
     pub fn tick(&mut self) {
         for latch in self.keys.values_mut() {
             latch.tick();
         }
+        self.text.clear();
+    }
+
+    /// Text typed since the last tick. The keys typing it report their
+    /// edges as usual.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn modifiers(&self) -> Modifiers {
+        Modifiers {
+            shift: self.is_pressed(Key::LeftShift) || self.is_pressed(Key::RightShift),
+            ctrl: self.is_pressed(Key::LeftCtrl) || self.is_pressed(Key::RightCtrl),
+            alt: self.is_pressed(Key::LeftAlt) || self.is_pressed(Key::RightAlt),
+            gui: self.is_pressed(Key::LeftGui) || self.is_pressed(Key::RightGui),
+        }
+    }
+
+    pub fn is_pressed(&self, key: Key) -> bool {
+        if let Some(latch) = self.keys.get(&key) {
+            latch.is_set()
+        } else {
+            false
+        }
     }
 
     pub fn is_front_edge(&self, key: Key) -> bool {
-        let scancode = key.into();
-        if let Some(latch) = self.keys.get(&scancode) {
+        if let Some(latch) = self.keys.get(&key) {
             latch.is_front_edge()
         } else {
             false
         }
     }
 
+    pub fn is_any_front_edge(&self) -> bool {
+        self.keys.values().any(|latch| latch.is_front_edge())
+    }
+
+    /// Keys pressed (`true`) or released (`false`) since the last tick.
+    pub fn edges(&self) -> impl Iterator<Item = (Key, bool)> + '_ {
+        Key::iter().filter_map(move |key| {
+            if self.is_front_edge(key) {
+                Some((key, true))
+            } else if self.is_back_edge(key) {
+                Some((key, false))
+            } else {
+                None
+            }
+        })
+    }
+
     pub fn is_back_edge(&self, key: Key) -> bool {
-        let scancode = key.into();
-        if let Some(latch) = self.keys.get(&scancode) {
+        if let Some(latch) = self.keys.get(&key) {
             latch.is_back_edge()
         } else {
             false
         }
     }
 }
+
+/// Something a game lets the player do, such as moving a piece left, that
+/// keys are bound to.
+pub trait Action: Copy + Eq + Hash + 'static {
+    /// Every action, in a fixed order.
+    fn all() -> &'static [Self];
+
+    /// Name of the action in bindings files.
+    fn name(self) -> &'static str;
+
+    fn by_name(name: &str) -> Option<Self> {
+        Self::all().iter().copied().find(|action| action.name() == name)
+    }
+}
+
+/// Keys bound to each action. An action can have several keys, and it is
+/// held while any of them is.
+#[derive(Clone)]
+pub struct Bindings<A> {
+    keys: HashMap<A, Vec<Key>>,
+}
+
+impl<A: Action> Bindings<A> {
+    /// Bindings with no keys at all.
+    pub fn new() -> Bindings<A> {
+        Bindings {
+            keys: A::all().iter().map(|&action| (action, Vec::new())).collect(),
+        }
+    }
+
+    pub fn with(mut self, action: A, keys: &[Key]) -> Bindings<A> {
+        self.set(action, keys);
+        self
+    }
+
+    pub fn keys(&self, action: A) -> &[Key] {
+        self.keys.get(&action).map_or(&[], |keys| keys.as_slice())
+    }
+
+    pub fn set(&mut self, action: A, keys: &[Key]) {
+        self.keys.insert(action, keys.to_vec());
+    }
+
+    /// Adds a key to an action, taking it away from any other action.
+    pub fn bind(&mut self, action: A, key: Key) {
+        for (&other, keys) in self.keys.iter_mut() {
+            if other != action {
+                keys.retain(|&k| k != key);
+            } else if !keys.contains(&key) {
+                keys.push(key);
+            }
+        }
+    }
+
+    pub fn is_pressed(&self, input: &Input, action: A) -> bool {
+        self.keys(action).iter().any(|&key| input.is_pressed(key))
+    }
+
+    /// Rebinds the actions given in the text, one per line as
+    /// `action: key key ...`. Actions the text leaves out keep their keys,
+    /// except the ones it binds to something else.
+    pub fn apply(&mut self, text: &str) -> Result<(), String> {
+        for line in text.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#')) {
+            let (name, keys) = line.split_once(':').ok_or_else(|| format!("Malformed binding: {}", line))?;
+            let action = A::by_name(name.trim()).ok_or_else(|| format!("Unknown action: {}", name.trim()))?;
+            let keys = keys.split_whitespace()
+                .map(|key| Key::by_name(key).ok_or_else(|| format!("Unknown key: {}", key)))
+                .collect::<Result<Vec<_>, _>>()?;
+            self.set(action, &[]);
+            for key in keys {
+                self.bind(action, key);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        for &action in A::all() {
+            text.push_str(action.name());
+            text.push(':');
+            for key in self.keys(action) {
+                text.push(' ');
+                text.push_str(key.name());
+            }
+            text.push('\n');
+        }
+        text
+    }
+}
+
+impl<A: Action> Default for Bindings<A> {
+    fn default() -> Bindings<A> {
+        Bindings::new()
+    }
+}
+
+/// Actions held and their edges, read through bindings. Works like `Input`
+/// does for keys.
+#[derive(Clone)]
+pub struct Actions<A> {
+    latches: HashMap<A, Latch>,
+}
+
+impl<A: Action> Actions<A> {
+    pub fn new() -> Actions<A> {
+        Actions {
+            latches: A::all().iter().map(|&action| (action, Latch::new())).collect(),
+        }
+    }
+
+    /// Moves on to the next tick and reads every action from its keys.
+    /// Meant to be called once a tick, the edges are since the last call.
+    pub fn update(&mut self, input: &Input, bindings: &Bindings<A>) {
+        for (&action, latch) in self.latches.iter_mut() {
+            latch.tick();
+            latch.set(bindings.is_pressed(input, action));
+        }
+    }
+
+    pub fn set(&mut self, action: A, is_pressed: bool) {
+        if let Some(latch) = self.latches.get_mut(&action) {
+            latch.set(is_pressed);
+        }
+    }
+
+    pub fn tick(&mut self) {
+        for latch in self.latches.values_mut() {
+            latch.tick();
+        }
+    }
+
+    pub fn is_pressed(&self, action: A) -> bool {
+        self.latches.get(&action).is_some_and(|latch| latch.is_set())
+    }
+
+    pub fn is_front_edge(&self, action: A) -> bool {
+        self.latches.get(&action).is_some_and(|latch| latch.is_front_edge())
+    }
+
+    pub fn is_back_edge(&self, action: A) -> bool {
+        self.latches.get(&action).is_some_and(|latch| latch.is_back_edge())
+    }
+
+    /// Actions started (`true`) or stopped (`false`) since the last tick.
+    pub fn edges(&self) -> impl Iterator<Item = (A, bool)> + '_ {
+        A::all().iter().copied().filter_map(move |action| {
+            if self.is_front_edge(action) {
+                Some((action, true))
+            } else if self.is_back_edge(action) {
+                Some((action, false))
+            } else {
+                None
+            }
+        })
+    }
+}
+
+impl<A: Action> Default for Actions<A> {
+    fn default() -> Actions<A> {
+        Actions::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+    enum Move {
+        Left,
+        Right,
+        Jump,
+    }
+
+    impl Action for Move {
+        fn all() -> &'static [Move] {
+            &[Move::Left, Move::Right, Move::Jump]
+        }
+
+        fn name(self) -> &'static str {
+            match self {
+                Move::Left => "left",
+                Move::Right => "right",
+                Move::Jump => "jump",
+            }
+        }
+    }
+
+    fn bindings() -> Bindings<Move> {
+        Bindings::new()
+            .with(Move::Left, &[Key::Left, Key::A])
+            .with(Move::Right, &[Key::Right, Key::D])
+            .with(Move::Jump, &[Key::Space])
+    }
+
+    #[test]
+    fn apply_rebinds_named_actions() {
+        let mut bindings = bindings();
+        bindings.apply("# arrows only\n\n  left: Left\njump: Up Space\n").unwrap();
+        assert_eq!(bindings.keys(Move::Left), &[Key::Left]);
+        assert_eq!(bindings.keys(Move::Right), &[Key::Right, Key::D]);
+        assert_eq!(bindings.keys(Move::Jump), &[Key::Up, Key::Space]);
+    }
+
+    #[test]
+    fn apply_takes_keys_from_other_actions() {
+        let mut bindings = bindings();
+        bindings.apply("jump: D A").unwrap();
+        assert_eq!(bindings.keys(Move::Left), &[Key::Left]);
+        assert_eq!(bindings.keys(Move::Right), &[Key::Right]);
+        assert_eq!(bindings.keys(Move::Jump), &[Key::D, Key::A]);
+
+        bindings.apply("left: A\nright: A A").unwrap();
+        assert_eq!(bindings.keys(Move::Left), &[]);
+        assert_eq!(bindings.keys(Move::Right), &[Key::A]);
+    }
+
+    #[test]
+    fn apply_errors() {
+        let mut bindings = bindings();
+        assert_eq!(bindings.apply("left Left"), Err("Malformed binding: left Left".to_string()));
+        assert_eq!(bindings.apply("crouch: Down"), Err("Unknown action: crouch".to_string()));
+        assert_eq!(bindings.apply("left: Leftt"), Err("Unknown key: Leftt".to_string()));
+    }
+
+    #[test]
+    fn text_round_trip() {
+        let text = bindings().to_text();
+        assert_eq!(text, "left: Left A\nright: Right D\njump: Space\n");
+
+        let mut restored = Bindings::new();
+        restored.apply(&text).unwrap();
+        for &action in Move::all() {
+            assert_eq!(restored.keys(action), bindings().keys(action));
+        }
+        assert_eq!(restored.to_text(), text);
+    }
+
+    // Pushes the stick to `x` and returns whether it holds left and right.
+    fn stick_x(pad: &mut Pad, x: f32) -> (bool, bool) {
+        pad.stick = (x, 0.0);
+        pad.update_axis_keys(&AxisThresholds::default());
+        (pad.is_pressed(Key::PadStickLeft), pad.is_pressed(Key::PadStickRight))
+    }
+
+    // With the default deadzone of 0.2, a press at 0.5 past it is a stick
+    // at 0.6 and a release under 0.35 past it a stick under 0.48.
+    #[test]
+    fn stick_presses_past_threshold() {
+        let mut pad = Pad::default();
+        assert_eq!(stick_x(&mut pad, 0.55), (false, false));
+        assert_eq!(stick_x(&mut pad, 0.65), (false, true));
+        assert_eq!(stick_x(&mut pad, -0.65), (true, false));
+    }
+
+    #[test]
+    fn stick_holds_between_thresholds() {
+        let mut pad = Pad::default();
+        assert_eq!(stick_x(&mut pad, 0.55), (false, false));
+        assert_eq!(stick_x(&mut pad, 0.7), (false, true));
+        assert_eq!(stick_x(&mut pad, 0.55), (false, true));
+        assert_eq!(stick_x(&mut pad, 0.5), (false, true));
+        assert_eq!(stick_x(&mut pad, 0.45), (false, false));
+        assert_eq!(stick_x(&mut pad, 0.55), (false, false));
+    }
+
+    #[test]
+    fn full_diagonal_presses_both() {
+        let mut pad = Pad {
+            stick: (0.9, 0.9),
+            ..Pad::default()
+        };
+        pad.update_axis_keys(&AxisThresholds::default());
+        assert!(pad.is_pressed(Key::PadStickRight) && pad.is_pressed(Key::PadStickDown));
+    }
+
+    #[test]
+    fn triggers() {
+        let thresholds = AxisThresholds::default();
+        let mut pad = Pad {
+            triggers: (0.65, 0.1),
+            ..Pad::default()
+        };
+        pad.update_axis_keys(&thresholds);
+        assert!(pad.is_pressed(Key::PadLeftTrigger) && !pad.is_pressed(Key::PadRightTrigger));
+
+        pad.triggers = (0.5, 0.1);
+        pad.update_axis_keys(&thresholds);
+        assert!(pad.is_pressed(Key::PadLeftTrigger));
+
+        pad.triggers = (0.4, 0.1);
+        pad.update_axis_keys(&thresholds);
+        assert!(!pad.is_pressed(Key::PadLeftTrigger));
+    }
+}
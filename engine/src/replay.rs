@@ -0,0 +1,457 @@
+use std::convert::TryFrom;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use strum::IntoEnumIterator;
+
+use crate::base::App;
+use crate::headless::{InputSource, Script};
+use crate::input::{pad_keys, Input, Key};
+use crate::video::ScreenBuffer;
+
+const MAGIC: &[u8; 4] = b"R0RP";
+// version 1 replays have no typed text
+const VERSION: u8 = 2;
+// lengths are read before the data, so a corrupt file could ask for anything
+const MAX_HEADER_LEN: usize = 1 << 20;
+const MAX_TEXT_LEN: usize = 1 << 16;
+
+/// Key edges and typed text delivered to an app, tick by tick, plus an
+/// app-defined header (e.g. an RNG seed) needed to reproduce the session.
+#[derive(Clone)]
+pub struct Replay {
+    header: Vec<u8>,
+    tick_count: u64,
+    script: Script,
+}
+
+impl Replay {
+    pub fn new(header: Vec<u8>) -> Replay {
+        Replay {
+            header,
+            tick_count: 0,
+            script: Script::new(),
+        }
+    }
+
+    pub fn header(&self) -> &[u8] {
+        &self.header
+    }
+
+    pub fn tick_count(&self) -> u64 {
+        self.tick_count
+    }
+
+    pub fn script(&self) -> Script {
+        self.script.clone()
+    }
+
+    pub fn load(path: &Path) -> Result<Replay, String> {
+        let file = File::open(path).map_err(|e| e.to_string())?;
+        Self::read(&mut BufReader::new(file))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let file = File::create(path).map_err(|e| e.to_string())?;
+        let mut writer = BufWriter::new(file);
+        self.write(&mut writer)?;
+        writer.flush().map_err(|e| e.to_string())
+    }
+
+    pub fn read<R: Read>(reader: &mut R) -> Result<Replay, String> {
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic).map_err(|e| e.to_string())?;
+        if &magic != MAGIC {
+            return Err("Not a replay file".into());
+        }
+        let version = read_u8(reader)?;
+        if version == 0 || version > VERSION {
+            return Err(format!("Unsupported replay version {}", version));
+        }
+
+        let header = read_bytes(reader, MAX_HEADER_LEN)?;
+
+        let mut replay = Replay::new(header);
+        replay.tick_count = read_varint(reader)?;
+
+        let event_count = read_varint(reader)?;
+        let mut tick = 0u64;
+        for _ in 0..event_count {
+            tick = tick.checked_add(read_varint(reader)?).ok_or("Replay tick out of range")?;
+            let (key, is_pressed) = read_key_event(reader)?;
+            replay.script.push(tick, key, is_pressed);
+        }
+
+        if version >= 2 {
+            let text_count = read_varint(reader)?;
+            let mut tick = 0u64;
+            for _ in 0..text_count {
+                tick = tick.checked_add(read_varint(reader)?).ok_or("Replay tick out of range")?;
+                let text = read_text(reader)?;
+                replay.script.push_text(tick, &text);
+            }
+        }
+
+        Ok(replay)
+    }
+
+    pub fn write<W: Write>(&self, writer: &mut W) -> Result<(), String> {
+        writer.write_all(MAGIC).map_err(|e| e.to_string())?;
+        writer.write_all(&[VERSION]).map_err(|e| e.to_string())?;
+
+        write_varint(writer, self.header.len() as u64)?;
+        writer.write_all(&self.header).map_err(|e| e.to_string())?;
+
+        write_varint(writer, self.tick_count)?;
+
+        let events = self.script.events();
+        write_varint(writer, events.len() as u64)?;
+        let mut tick = 0;
+        for event in events {
+            write_varint(writer, event.tick - tick)?;
+            write_key_event(writer, event.key, event.is_pressed)?;
+            tick = event.tick;
+        }
+
+        let texts = self.script.texts();
+        write_varint(writer, texts.len() as u64)?;
+        let mut tick = 0;
+        for text in texts {
+            write_varint(writer, text.tick - tick)?;
+            write_text(writer, &text.text)?;
+            tick = text.tick;
+        }
+
+        Ok(())
+    }
+}
+
+/// Wraps an app and records the key edges and text it receives.
+pub struct Recorder<'app, A> {
+    app: &'app mut A,
+    pressed_keys: Vec<Key>,
+    replay: Replay,
+}
+
+impl<'app, A: App> Recorder<'app, A> {
+    pub fn new(app: &'app mut A, header: Vec<u8>) -> Recorder<'app, A> {
+        Recorder {
+            app,
+            pressed_keys: Vec::new(),
+            replay: Replay::new(header),
+        }
+    }
+
+    pub fn into_replay(self) -> Replay {
+        self.replay
+    }
+}
+
+impl<'app, A: App> App for Recorder<'app, A> {
+    fn handle_input(&mut self, input: &Input) {
+        let tick = self.replay.tick_count;
+        for key in Key::iter() {
+            let is_pressed = input.is_pressed(key);
+            if is_pressed != self.pressed_keys.contains(&key) {
+                self.replay.script.push(tick, key, is_pressed);
+                if is_pressed {
+                    self.pressed_keys.push(key);
+                } else {
+                    self.pressed_keys.retain(|&k| k != key);
+                }
+            }
+        }
+        if !input.text().is_empty() {
+            self.replay.script.push_text(tick, input.text());
+        }
+        self.replay.tick_count += 1;
+
+        self.app.handle_input(input);
+    }
+
+    fn tick(&mut self) {
+        self.app.tick();
+    }
+
+    fn draw(&self, buf: &mut ScreenBuffer) {
+        self.app.draw(buf);
+    }
+
+    fn is_quit_requested(&self) -> bool {
+        self.app.is_quit_requested()
+    }
+}
+
+/// Wraps an app and feeds it a replay instead of the live input.
+pub struct Player<'app, A> {
+    app: &'app mut A,
+    input: Input,
+    script: Script,
+    tick: u64,
+    tick_count: u64,
+}
+
+impl<'app, A: App> Player<'app, A> {
+    pub fn new(app: &'app mut A, replay: &Replay) -> Player<'app, A> {
+        Player {
+            app,
+            input: Input::new(),
+            script: replay.script(),
+            tick: 0,
+            tick_count: replay.tick_count(),
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.tick >= self.tick_count
+    }
+}
+
+impl<'app, A: App> App for Player<'app, A> {
+    fn handle_input(&mut self, _input: &Input) {
+        if self.is_finished() {
+            return;
+        }
+        self.script.feed(self.tick, &mut self.input);
+        self.app.handle_input(&self.input);
+    }
+
+    fn tick(&mut self) {
+        if self.is_finished() {
+            return;
+        }
+        self.input.tick();
+        self.app.tick();
+        self.tick += 1;
+    }
+
+    fn draw(&self, buf: &mut ScreenBuffer) {
+        self.app.draw(buf);
+    }
+
+    fn is_quit_requested(&self) -> bool {
+        self.app.is_quit_requested()
+    }
+}
+
+// Keyboard keys are written as their scancodes, which stay stable when keys
+// are added to `Key`. Pad keys have none and are numbered after the last
+// SDL scancode.
+const PAD_KEY_CODES: u64 = 512;
+
+fn key_code(key: Key) -> u64 {
+    match key.scancode() {
+        Some(scancode) => scancode as u64,
+        None => PAD_KEY_CODES + pad_keys().take_while(|&pad_key| pad_key != key).count() as u64,
+    }
+}
+
+fn key_by_code(code: u64) -> Option<Key> {
+    match code.checked_sub(PAD_KEY_CODES) {
+        Some(index) => usize::try_from(index).ok().and_then(|index| pad_keys().nth(index)),
+        None => Key::iter().find(|&key| key.scancode().is_some_and(|scancode| scancode as u64 == code)),
+    }
+}
+
+fn read_u8<R: Read>(reader: &mut R) -> Result<u8, String> {
+    let mut byte = [0u8; 1];
+    reader.read_exact(&mut byte).map_err(|e| e.to_string())?;
+    Ok(byte[0])
+}
+
+fn read_bytes<R: Read>(reader: &mut R, max_len: usize) -> Result<Vec<u8>, String> {
+    let len = usize::try_from(read_varint(reader)?).map_err(|e| e.to_string())?;
+    if len > max_len {
+        return Err(format!("Replay data of {} bytes is too long", len));
+    }
+    let mut bytes = vec![0u8; len];
+    reader.read_exact(&mut bytes).map_err(|e| e.to_string())?;
+    Ok(bytes)
+}
+
+fn read_text<R: Read>(reader: &mut R) -> Result<String, String> {
+    let bytes = read_bytes(reader, MAX_TEXT_LEN)?;
+    String::from_utf8(bytes).map_err(|e| e.to_string())
+}
+
+fn write_text<W: Write>(writer: &mut W, text: &str) -> Result<(), String> {
+    write_varint(writer, text.len() as u64)?;
+    writer.write_all(text.as_bytes()).map_err(|e| e.to_string())
+}
+
+/// Reads a key edge written by `write_key_event`.
+pub fn read_key_event<R: Read>(reader: &mut R) -> Result<(Key, bool), String> {
+    let code = read_varint(reader)?;
+    let key = key_by_code(code >> 1).ok_or("Unknown key")?;
+    Ok((key, code & 1 != 0))
+}
+
+pub fn write_key_event<W: Write>(writer: &mut W, key: Key, is_pressed: bool) -> Result<(), String> {
+    write_varint(writer, key_code(key) << 1 | is_pressed as u64)
+}
+
+pub fn read_varint<R: Read>(reader: &mut R) -> Result<u64, String> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let byte = read_u8(reader)?;
+        value |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err("Malformed varint".into())
+}
+
+pub fn write_varint<W: Write>(writer: &mut W, mut value: u64) -> Result<(), String> {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            return writer.write_all(&[byte]).map_err(|e| e.to_string());
+        }
+        writer.write_all(&[byte | 0x80]).map_err(|e| e.to_string())?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn round_trip(replay: &Replay) -> Result<Replay, String> {
+        let mut bytes = Vec::new();
+        replay.write(&mut bytes)?;
+        Replay::read(&mut Cursor::new(bytes))
+    }
+
+    #[test]
+    fn replay_round_trip() {
+        let mut replay = Replay::new(b"header".to_vec());
+        replay.tick_count = 100;
+        replay.script = Script::new()
+            .tap(0, Key::Left)
+            .press(10, Key::Space)
+            .press(10, Key::PadA)
+            .release(90, Key::Space)
+            .type_text(20, "hi");
+
+        let read = round_trip(&replay).unwrap();
+        assert_eq!(read.header(), b"header");
+        assert_eq!(read.tick_count(), 100);
+        assert_eq!(read.script().events(), replay.script.events());
+        assert_eq!(read.script().texts(), replay.script.texts());
+    }
+
+    #[test]
+    fn replay_not_a_replay() {
+        assert!(Replay::read(&mut Cursor::new(b"RIFF\x02\x00".to_vec())).is_err());
+    }
+
+    #[test]
+    fn replay_unsupported_version() {
+        assert!(Replay::read(&mut Cursor::new(b"R0RP\x03\x00".to_vec())).is_err());
+    }
+
+    #[test]
+    fn replay_truncated() {
+        let mut bytes = Vec::new();
+        Replay::new(b"header".to_vec()).write(&mut bytes).unwrap();
+        for len in 0..bytes.len() {
+            assert!(Replay::read(&mut Cursor::new(&bytes[..len])).is_err(), "{} bytes", len);
+        }
+    }
+
+    #[test]
+    fn replay_header_too_long() {
+        let mut bytes = b"R0RP\x02".to_vec();
+        write_varint(&mut bytes, u64::MAX).unwrap();
+        assert!(Replay::read(&mut Cursor::new(bytes)).is_err());
+    }
+
+    #[test]
+    fn replay_event_tick_out_of_range() {
+        let mut bytes = b"R0RP\x02\x00\x01\x02".to_vec();
+        for &delta in &[u64::MAX, 1] {
+            write_varint(&mut bytes, delta).unwrap();
+            write_key_event(&mut bytes, Key::Left, true).unwrap();
+        }
+        bytes.push(0);
+        assert!(Replay::read(&mut Cursor::new(bytes)).is_err());
+    }
+
+    #[test]
+    fn replay_text_tick_out_of_range() {
+        let mut bytes = b"R0RP\x02\x00\x01\x00\x02".to_vec();
+        for &delta in &[u64::MAX, 1] {
+            write_varint(&mut bytes, delta).unwrap();
+            write_text(&mut bytes, "a").unwrap();
+        }
+        assert!(Replay::read(&mut Cursor::new(bytes)).is_err());
+    }
+
+    #[test]
+    fn replay_text_too_long() {
+        let mut bytes = b"R0RP\x02\x00\x01\x00\x01\x00".to_vec();
+        write_varint(&mut bytes, MAX_TEXT_LEN as u64 + 1).unwrap();
+        bytes.resize(bytes.len() + MAX_TEXT_LEN + 1, b'a');
+        assert!(Replay::read(&mut Cursor::new(bytes)).is_err());
+    }
+
+    #[test]
+    fn replay_text_not_utf8() {
+        let bytes = b"R0RP\x02\x00\x01\x00\x01\x00\x01\xff".to_vec();
+        assert!(Replay::read(&mut Cursor::new(bytes)).is_err());
+    }
+
+    #[test]
+    fn replay_version_1_has_no_text() {
+        let bytes = b"R0RP\x01\x00\x01\x00".to_vec();
+        let replay = Replay::read(&mut Cursor::new(bytes)).unwrap();
+        assert_eq!(replay.tick_count(), 1);
+        assert!(replay.script().texts().is_empty());
+    }
+
+    #[test]
+    fn replay_unknown_key() {
+        let mut bytes = b"R0RP\x02\x00\x01\x01\x00".to_vec();
+        write_varint(&mut bytes, 511 << 1).unwrap();
+        assert!(Replay::read(&mut Cursor::new(bytes)).is_err());
+    }
+
+    #[test]
+    fn key_codes_round_trip() {
+        for key in Key::iter() {
+            assert_eq!(key_by_code(key_code(key)), Some(key));
+        }
+        assert_eq!(key_code(Key::PadA), PAD_KEY_CODES);
+        assert_eq!(key_by_code(PAD_KEY_CODES + pad_keys().count() as u64), None);
+    }
+
+    #[test]
+    fn varint_round_trip() {
+        for &value in &[0, 1, 0x7f, 0x80, 0x3fff, 0x4000, u32::MAX as u64, u64::MAX] {
+            let mut bytes = Vec::new();
+            write_varint(&mut bytes, value).unwrap();
+            assert_eq!(read_varint(&mut Cursor::new(bytes)).unwrap(), value);
+        }
+    }
+
+    #[test]
+    fn varint_encoding() {
+        let mut bytes = Vec::new();
+        write_varint(&mut bytes, 300).unwrap();
+        assert_eq!(bytes, [0xac, 0x02]);
+    }
+
+    #[test]
+    fn varint_truncated() {
+        assert!(read_varint(&mut Cursor::new([0x80])).is_err());
+        assert!(read_varint(&mut Cursor::new([])).is_err());
+    }
+
+    #[test]
+    fn varint_too_long() {
+        assert!(read_varint(&mut Cursor::new([0xff; 10])).is_err());
+    }
+}
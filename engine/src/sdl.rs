@@ -0,0 +1,294 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::Instant;
+
+use sdl2::audio::{AudioSpecDesired, AudioCallback, AudioSpec};
+use sdl2::controller::GameController;
+use sdl2::GameControllerSubsystem;
+use sdl2::event::Event;
+use sdl2::pixels::PixelFormatEnum;
+use sdl2::rect::Rect;
+use sdl2::render::BlendMode;
+use sdl2::surface::Surface;
+
+use crate::base::{App, Backend};
+use crate::geometry::Point;
+use crate::input::Input;
+use crate::time::{FixedTimestep, RateLimiter};
+use crate::video::{draw_str, Color, ScreenBuffer};
+use crate::{render, update, RunParams};
+
+pub struct Sdl<'str, F> {
+    params: RunParams<'str>,
+    audio: Option<F>,
+}
+
+impl<'str, F> Sdl<'str, F> {
+    pub fn new(params: RunParams<'str>, audio: F) -> Sdl<'str, F> {
+        Sdl {
+            params,
+            audio: Some(audio),
+        }
+    }
+}
+
+impl<'str, F, C> Backend for Sdl<'str, F>
+    where
+        C: AudioCallback,
+        F: FnOnce(AudioSpec) -> C,
+{
+    fn run<A: App>(&mut self, app: &mut A) -> Result<(), String> {
+        let params = &self.params;
+        let audio = self.audio.take().ok_or("SDL backend can only be run once")?;
+
+        run(app, params, audio)
+    }
+}
+
+fn run<A, F, C>(app: &mut A, params: &RunParams, audio: F) -> Result<(), String>
+    where
+        A: App,
+        C: AudioCallback,
+        F: FnOnce(AudioSpec) -> C,
+{
+    let scale = params.scale;
+    let tile_count = (params.width_in_tiles, params.height_in_tiles);
+
+    sdl2::hint::set("SDL_VIDEO_X11_NET_WM_BYPASS_COMPOSITOR", "0");
+
+    let sdl_context = sdl2::init()?;
+
+    let tileset_surface = sdl2::surface::Surface::load_bmp(Path::new(params.tileset_path))?;
+
+    if tileset_surface.width() % 16 != 0 {
+        return Err("Tileset width must be multiple of 16".into());
+    }
+    if tileset_surface.height() % 16 != 0 {
+        return Err("Tileset height must be multiple of 16".into());
+    }
+
+    let tile_size = (tileset_surface.width() / 16, tileset_surface.height() / 16);
+
+    let tileset_surface = into_glyph_mask(tileset_surface)?;
+
+    let video_subsystem = sdl_context.video()?;
+
+    let window = video_subsystem
+        .window(
+            params.app_name,
+            scale * tile_count.0 * tile_size.0,
+            scale * tile_count.1 * tile_size.1,
+        )
+        .position_centered()
+        .build()
+        .map_err(|e| e.to_string())?;
+
+    // typed text arrives as its own events next to the key presses
+    video_subsystem.text_input().start();
+
+    let canvas_builder = window
+        .into_canvas()
+        .accelerated();
+    let canvas_builder = if params.vsync {
+        canvas_builder.present_vsync()
+    } else {
+        canvas_builder
+    };
+    let mut canvas = canvas_builder
+        .build()
+        .map_err(|e| e.to_string())?;
+    let texture_creator = canvas.texture_creator();
+
+    let clear_color = sdl2::pixels::Color::RGBA(0, 0, 0, 255);
+    let glyph_color = sdl2::pixels::Color::RGB(255, 255, 255);
+
+    let mut event_pump = sdl_context.event_pump()?;
+
+    let controller_subsystem = sdl_context.game_controller()?;
+    let mut controllers = HashMap::new();
+
+    let audio_subsystem = sdl_context.audio()?;
+
+    let desired_spec = AudioSpecDesired {
+        freq: Some(44100),
+        channels: Some(1),  // mono
+        samples: None       // default sample size
+    };
+
+    let device = audio_subsystem.open_playback(None, &desired_spec, move|spec| {
+        audio(spec)
+    })?;
+
+    device.resume();
+
+    let mut tileset_texture = texture_creator
+        .create_texture_from_surface(&tileset_surface)
+        .map_err(|e| e.to_string())?;
+    tileset_texture.set_blend_mode(BlendMode::Blend);
+
+    let mut tileset_src_rect = Rect::new(0, 0, tile_size.0, tile_size.1);
+    let mut tileset_dst_rect = Rect::new(0, 0, tile_size.0 * scale, tile_size.1 * scale);
+
+    let mut screen_buffer: ScreenBuffer = ScreenBuffer::new(tile_count.0 as usize, tile_count.1 as usize);
+
+    let mut input = Input::new();
+
+    let mut is_quit = false;
+
+    let mut fps = 0;
+    let mut fps_counter = 0;
+    let mut ticks_prev = Instant::now();
+
+    let mut timestep = FixedTimestep::new(params.tick_rate, params.max_ticks_per_frame, ticks_prev);
+    let mut render_limiter = params.render_rate.map(|rate| RateLimiter::new(rate, ticks_prev));
+
+    let mut is_outdated = true;
+
+    while !is_quit {
+        for event in event_pump.poll_iter() {
+            is_quit |= handle_event(event, &mut input, &controller_subsystem, &mut controllers);
+        }
+
+        // update world
+        let now = Instant::now();
+        for _ in 0..timestep.update(now) {
+            update(app, &mut input);
+            is_outdated = true;
+
+            if app.is_quit_requested() {
+                is_quit = true;
+                break;
+            }
+        }
+
+        let is_drawing_frame = is_outdated && render_limiter.as_mut().is_none_or(|limiter| limiter.try_acquire(now));
+
+        if is_drawing_frame {
+            is_outdated = false;
+
+            fps_counter += 1;
+            let delta = (now - ticks_prev).as_secs_f64();
+            if delta >= 1.0 {
+                fps = ((fps_counter as f64) / delta) as i32;
+                fps_counter = 0;
+                ticks_prev = now;
+            }
+
+            // render chars
+            render(app, &mut screen_buffer);
+
+            draw_str(&mut screen_buffer, Point::new(0, 0), &fps.to_string());
+
+            canvas.set_draw_color(clear_color);
+            canvas.clear();
+            for y in 0..tile_count.1 {
+                for x in 0..tile_count.0 {
+                    let cell = screen_buffer.cell_at(x as usize, y as usize);
+                    let chr = cell.glyph;
+
+                    tileset_src_rect.set_x(((chr as usize % 16) * tile_size.0 as usize) as i32);
+                    tileset_src_rect.set_y(((chr as usize / 16) * tile_size.1 as usize) as i32);
+
+                    tileset_dst_rect.set_x((x * tile_size.0 * scale) as i32);
+                    tileset_dst_rect.set_y((y * tile_size.1 * scale) as i32);
+
+                    if cell.bg != Color::Default {
+                        canvas.set_draw_color(sdl_color(cell.bg, clear_color));
+                        canvas.fill_rect(tileset_dst_rect)?;
+                    }
+
+                    let fg = sdl_color(cell.fg, glyph_color);
+                    tileset_texture.set_color_mod(fg.r, fg.g, fg.b);
+
+                    canvas.copy_ex(
+                        &tileset_texture,
+                        Some(tileset_src_rect),
+                        Some(tileset_dst_rect),
+                        0.0,
+                        None,
+                        false,
+                        false,
+                    )?;
+                }
+            }
+            canvas.present();
+        }
+
+        // with vsync presenting already waits for the display
+        if !(is_drawing_frame && params.vsync) {
+            let now = Instant::now();
+            let mut timeout = timestep.until_next_tick(now);
+            if is_outdated {
+                if let Some(limiter) = render_limiter.as_ref() {
+                    timeout = timeout.min(limiter.until_next(now));
+                }
+            }
+            if let Some(event) = event_pump.wait_event_timeout(timeout.as_millis() as u32) {
+                is_quit |= handle_event(event, &mut input, &controller_subsystem, &mut controllers);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// Returns whether the window was closed. Game controllers are opened as they
+// are plugged in, SDL reports the ones connected at startup the same way.
+fn handle_event(
+    event: Event,
+    input: &mut Input,
+    controller_subsystem: &GameControllerSubsystem,
+    controllers: &mut HashMap<u32, GameController>,
+) -> bool {
+    match event {
+        Event::Quit { .. } => return true,
+        Event::ControllerDeviceAdded { which, .. } => {
+            // a pad that fails to open is left alone
+            if let Ok(controller) = controller_subsystem.open(which) {
+                controllers.insert(controller.instance_id(), controller);
+            }
+        }
+        Event::ControllerDeviceRemoved { which, .. } => {
+            controllers.remove(&which);
+        }
+        _ => {}
+    }
+    input.on_event(event);
+    false
+}
+
+fn sdl_color(color: Color, default: sdl2::pixels::Color) -> sdl2::pixels::Color {
+    match color {
+        Color::Default => default,
+        Color::Rgb(r, g, b) => sdl2::pixels::Color::RGB(r, g, b),
+    }
+}
+
+// Turns the tileset into white glyphs on a transparent background so that
+// colour modulation can tint them. The brightness of each pixel becomes its
+// alpha, which keeps antialiased edges.
+fn into_glyph_mask(surface: Surface) -> Result<Surface, String> {
+    let mut surface = surface.convert_format(PixelFormatEnum::ARGB8888)?;
+
+    let width = surface.width() as usize;
+    let height = surface.height() as usize;
+    let pitch = surface.pitch() as usize;
+
+    surface.with_lock_mut(|pixels| {
+        for y in 0..height {
+            for pixel in pixels[y * pitch..y * pitch + width * 4].chunks_exact_mut(4) {
+                let argb = u32::from_ne_bytes([pixel[0], pixel[1], pixel[2], pixel[3]]);
+                let alpha = luminance((argb >> 16) as u8, (argb >> 8) as u8, argb as u8);
+                let mask = (alpha as u32) << 24 | 0x00ff_ffff;
+                pixel.copy_from_slice(&mask.to_ne_bytes());
+            }
+        }
+    });
+
+    Ok(surface)
+}
+
+// Rec. 601 weights, scaled by 256.
+fn luminance(r: u8, g: u8, b: u8) -> u8 {
+    ((77 * r as u32 + 150 * g as u32 + 29 * b as u32) >> 8) as u8
+}
@@ -0,0 +1,391 @@
+use std::collections::HashMap;
+use std::io::{self, Stdout, Write};
+use std::time::{Duration, Instant};
+
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyEventState, KeyModifiers, ModifierKeyCode, KeyboardEnhancementFlags, PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags};
+use crossterm::style::{Print, ResetColor, SetBackgroundColor, SetForegroundColor};
+use crossterm::{cursor, queue, terminal};
+
+use crate::base::{App, Backend};
+use crate::input::{Input, Key};
+use crate::time::{FixedTimestep, RateLimiter};
+use crate::video::{Color, ScreenBuffer};
+use crate::{render, update, RunParams};
+
+// Without key release reporting a held key is seen as a press, a pause of
+// the terminal's repeat delay and then a stream of repeats. A key counts as
+// released when nothing arrived for as long as the gap between repeats, so a
+// tap is over before the DAS delay and a held key is seen as a tap followed,
+// from the first repeat on, by a hold. A press that comes later than that
+// gap while the key is still held is a new tap, not a repeat.
+const KEY_RELEASE_DELAY: Duration = Duration::from_millis(100);
+
+const DEFAULT_RENDER_RATE: u32 = 60;
+
+pub struct Terminal<'str> {
+    params: RunParams<'str>,
+}
+
+impl<'str> Terminal<'str> {
+    pub fn new(params: RunParams<'str>) -> Terminal<'str> {
+        Terminal {
+            params,
+        }
+    }
+}
+
+impl<'str> Backend for Terminal<'str> {
+    fn run<A: App>(&mut self, app: &mut A) -> Result<(), String> {
+        let mut session = Session::open(self.params.app_name).map_err(|e| e.to_string())?;
+
+        let result = run(app, &self.params, &mut session).map_err(|e| e.to_string());
+
+        session.close().map_err(|e| e.to_string())?;
+
+        result
+    }
+}
+
+struct Session {
+    stdout: Stdout,
+    has_key_release: bool,
+    is_open: bool,
+}
+
+impl Session {
+    fn open(title: &str) -> io::Result<Session> {
+        let mut stdout = io::stdout();
+
+        terminal::enable_raw_mode()?;
+
+        // with every key sent as an escape code, modifiers arrive as keys
+        // of their own and get their releases reported like the rest
+        let has_key_release = terminal::supports_keyboard_enhancement().unwrap_or(false);
+        if has_key_release {
+            let flags = KeyboardEnhancementFlags::DISAMBIGUATE_ESCAPE_CODES
+                | KeyboardEnhancementFlags::REPORT_EVENT_TYPES
+                | KeyboardEnhancementFlags::REPORT_ALL_KEYS_AS_ESCAPE_CODES;
+            queue!(stdout, PushKeyboardEnhancementFlags(flags))?;
+        }
+
+        queue!(
+            stdout,
+            terminal::SetTitle(title),
+            terminal::EnterAlternateScreen,
+            terminal::Clear(terminal::ClearType::All),
+            cursor::Hide,
+        )?;
+        stdout.flush()?;
+
+        Ok(Session {
+            stdout,
+            has_key_release,
+            is_open: true,
+        })
+    }
+
+    fn close(&mut self) -> io::Result<()> {
+        if !self.is_open {
+            return Ok(());
+        }
+        self.is_open = false;
+
+        if self.has_key_release {
+            queue!(self.stdout, PopKeyboardEnhancementFlags)?;
+        }
+        queue!(self.stdout, ResetColor, cursor::Show, terminal::LeaveAlternateScreen)?;
+        self.stdout.flush()?;
+
+        terminal::disable_raw_mode()
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        let _ = self.close();
+    }
+}
+
+fn run<A: App>(app: &mut A, params: &RunParams, session: &mut Session) -> io::Result<()> {
+    let tile_count = (params.width_in_tiles as usize, params.height_in_tiles as usize);
+
+    let mut screen_buffer = ScreenBuffer::new(tile_count.0, tile_count.1);
+    let mut shown_buffer: Option<ScreenBuffer> = None;
+
+    let mut input = Input::new();
+    let mut release_deadlines: HashMap<Key, Instant> = HashMap::new();
+    // keys tapped again while held, pressed once a tick has seen the release
+    let mut tapped_keys: Vec<Key> = Vec::new();
+
+    let mut is_quit = false;
+
+    let now = Instant::now();
+    let mut timestep = FixedTimestep::new(params.tick_rate, params.max_ticks_per_frame, now);
+    // terminals have no vsync, so the render rate is always capped
+    let mut render_limiter = RateLimiter::new(params.render_rate.unwrap_or(DEFAULT_RENDER_RATE), now);
+
+    let mut is_outdated = true;
+
+    while !is_quit {
+        let now = Instant::now();
+
+        release_deadlines.retain(|&key, &mut deadline| {
+            if deadline <= now {
+                input.set(key, false);
+                false
+            } else {
+                true
+            }
+        });
+
+        for _ in 0..timestep.update(now) {
+            update(app, &mut input);
+            is_outdated = true;
+
+            for key in tapped_keys.drain(..) {
+                if release_deadlines.contains_key(&key) {
+                    input.set(key, true);
+                }
+            }
+
+            if app.is_quit_requested() {
+                is_quit = true;
+                break;
+            }
+        }
+
+        if is_outdated && render_limiter.try_acquire(now) {
+            is_outdated = false;
+
+            render(app, &mut screen_buffer);
+            draw(&mut session.stdout, &screen_buffer, shown_buffer.as_ref())?;
+            shown_buffer = Some(screen_buffer.clone());
+        }
+
+        let mut timeout = timestep.until_next_tick(now);
+        if is_outdated {
+            timeout = timeout.min(render_limiter.until_next(now));
+        }
+
+        while event::poll(timeout)? {
+            timeout = Duration::ZERO;
+            match event::read()? {
+                Event::Key(KeyEvent { code: KeyCode::Char('c'), modifiers, .. }) if modifiers.contains(KeyModifiers::CONTROL) => {
+                    is_quit = true;
+                }
+                Event::Key(KeyEvent { code, modifiers, kind, state }) => {
+                    let is_pressed = kind != KeyEventKind::Release;
+                    // without release reporting modifiers only come along with
+                    // other keys, so they are read from presses and let go on
+                    // their deadline or with the next press that lacks them
+                    let held_modifiers = MODIFIER_KEYS.iter()
+                        .filter(|_| is_pressed && !session.has_key_release)
+                        .map(|&(modifier, key)| (key, modifiers.contains(modifier)));
+                    let keys = key_by_code(code).map(|key| (key, is_pressed));
+                    for (key, is_pressed) in held_modifiers.chain(keys) {
+                        if session.has_key_release {
+                            input.set(key, is_pressed);
+                        } else if !is_pressed {
+                            input.set(key, false);
+                            release_deadlines.remove(&key);
+                        } else {
+                            let now = Instant::now();
+                            match release_deadlines.insert(key, now + KEY_RELEASE_DELAY) {
+                                // a repeat of the held key
+                                Some(deadline) if deadline > now => {}
+                                // a new tap of a key not yet seen released
+                                Some(_) => {
+                                    input.set(key, false);
+                                    tapped_keys.push(key);
+                                }
+                                None => input.set(key, true),
+                            }
+                        }
+                    }
+
+                    if let KeyCode::Char(chr) = code {
+                        if is_pressed && !modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) {
+                            // escape codes carry the unshifted letter
+                            let chr = if modifiers.contains(KeyModifiers::SHIFT) || state.contains(KeyEventState::CAPS_LOCK) {
+                                chr.to_ascii_uppercase()
+                            } else {
+                                chr
+                            };
+                            input.push_text(chr.encode_utf8(&mut [0u8; 4]));
+                        }
+                    }
+                }
+                Event::Resize(..) => {
+                    queue!(session.stdout, terminal::Clear(terminal::ClearType::All))?;
+                    shown_buffer = None;
+                    is_outdated = true;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn draw(stdout: &mut Stdout, buf: &ScreenBuffer, shown: Option<&ScreenBuffer>) -> io::Result<()> {
+    let mut colors = None;
+    for y in 0..buf.height() {
+        let mut cursor_x = None;
+        for x in 0..buf.width() {
+            let cell = buf.cell_at(x, y);
+            if shown.is_some_and(|shown| shown.cell_at(x, y) == cell) {
+                continue;
+            }
+
+            if cursor_x != Some(x) {
+                queue!(stdout, cursor::MoveTo(x as u16, y as u16))?;
+            }
+            if colors != Some((cell.fg, cell.bg)) {
+                queue!(stdout, SetForegroundColor(term_color(cell.fg)), SetBackgroundColor(term_color(cell.bg)))?;
+                colors = Some((cell.fg, cell.bg));
+            }
+            queue!(stdout, Print(cp437_to_char(cell.glyph)))?;
+            cursor_x = Some(x + 1);
+        }
+    }
+    stdout.flush()
+}
+
+fn term_color(color: Color) -> crossterm::style::Color {
+    match color {
+        Color::Default => crossterm::style::Color::Reset,
+        Color::Rgb(r, g, b) => crossterm::style::Color::Rgb { r, g, b },
+    }
+}
+
+const MODIFIER_KEYS: [(KeyModifiers, Key); 4] = [
+    (KeyModifiers::SHIFT, Key::LeftShift),
+    (KeyModifiers::CONTROL, Key::LeftCtrl),
+    (KeyModifiers::ALT, Key::LeftAlt),
+    (KeyModifiers::SUPER, Key::LeftGui),
+];
+
+fn key_by_code(code: KeyCode) -> Option<Key> {
+    let key = match code {
+        KeyCode::Up => Key::Up,
+        KeyCode::Down => Key::Down,
+        KeyCode::Left => Key::Left,
+        KeyCode::Right => Key::Right,
+        KeyCode::Esc => Key::Escape,
+        KeyCode::Enter => Key::Return,
+        KeyCode::Backspace => Key::Backspace,
+        KeyCode::Tab | KeyCode::BackTab => Key::Tab,
+        KeyCode::Insert => Key::Insert,
+        KeyCode::Delete => Key::Delete,
+        KeyCode::Home => Key::Home,
+        KeyCode::End => Key::End,
+        KeyCode::PageUp => Key::PageUp,
+        KeyCode::PageDown => Key::PageDown,
+        KeyCode::CapsLock => Key::CapsLock,
+        KeyCode::ScrollLock => Key::ScrollLock,
+        KeyCode::NumLock => Key::NumLock,
+        KeyCode::PrintScreen => Key::PrintScreen,
+        KeyCode::Pause => Key::Pause,
+        KeyCode::Menu => Key::Application,
+        KeyCode::F(1) => Key::F1,
+        KeyCode::F(2) => Key::F2,
+        KeyCode::F(3) => Key::F3,
+        KeyCode::F(4) => Key::F4,
+        KeyCode::F(5) => Key::F5,
+        KeyCode::F(6) => Key::F6,
+        KeyCode::F(7) => Key::F7,
+        KeyCode::F(8) => Key::F8,
+        KeyCode::F(9) => Key::F9,
+        KeyCode::F(10) => Key::F10,
+        KeyCode::F(11) => Key::F11,
+        KeyCode::F(12) => Key::F12,
+        KeyCode::Modifier(modifier) => match modifier {
+            ModifierKeyCode::LeftShift => Key::LeftShift,
+            ModifierKeyCode::RightShift => Key::RightShift,
+            ModifierKeyCode::LeftControl => Key::LeftCtrl,
+            ModifierKeyCode::RightControl => Key::RightCtrl,
+            ModifierKeyCode::LeftAlt => Key::LeftAlt,
+            ModifierKeyCode::RightAlt => Key::RightAlt,
+            ModifierKeyCode::LeftSuper => Key::LeftGui,
+            ModifierKeyCode::RightSuper => Key::RightGui,
+            _ => return None,
+        },
+        KeyCode::Char(chr) => match chr.to_ascii_uppercase() {
+            ' ' => Key::Space,
+            'A' => Key::A,
+            'B' => Key::B,
+            'C' => Key::C,
+            'D' => Key::D,
+            'E' => Key::E,
+            'F' => Key::F,
+            'G' => Key::G,
+            'H' => Key::H,
+            'I' => Key::I,
+            'J' => Key::J,
+            'K' => Key::K,
+            'L' => Key::L,
+            'M' => Key::M,
+            'N' => Key::N,
+            'O' => Key::O,
+            'P' => Key::P,
+            'Q' => Key::Q,
+            'R' => Key::R,
+            'S' => Key::S,
+            'T' => Key::T,
+            'U' => Key::U,
+            'V' => Key::V,
+            'W' => Key::W,
+            'X' => Key::X,
+            'Y' => Key::Y,
+            'Z' => Key::Z,
+            '1' => Key::Num1,
+            '2' => Key::Num2,
+            '3' => Key::Num3,
+            '4' => Key::Num4,
+            '5' => Key::Num5,
+            '6' => Key::Num6,
+            '7' => Key::Num7,
+            '8' => Key::Num8,
+            '9' => Key::Num9,
+            '0' => Key::Num0,
+            '-' => Key::Minus,
+            '=' => Key::Equals,
+            '`' => Key::Grave,
+            '[' => Key::LeftBracket,
+            ']' => Key::RightBracket,
+            '\\' => Key::Backslash,
+            ';' => Key::Semicolon,
+            '\'' => Key::Apostrophe,
+            ',' => Key::Comma,
+            '.' => Key::Period,
+            '/' => Key::Slash,
+            _ => return None,
+        },
+        _ => return None,
+    };
+    Some(key)
+}
+
+static CP437: [char; 256] = [
+    ' ', '☺', '☻', '♥', '♦', '♣', '♠', '•', '◘', '○', '◙', '♂', '♀', '♪', '♫', '☼',
+    '►', '◄', '↕', '‼', '¶', '§', '▬', '↨', '↑', '↓', '→', '←', '∟', '↔', '▲', '▼',
+    ' ', '!', '"', '#', '$', '%', '&', '\'', '(', ')', '*', '+', ',', '-', '.', '/',
+    '0', '1', '2', '3', '4', '5', '6', '7', '8', '9', ':', ';', '<', '=', '>', '?',
+    '@', 'A', 'B', 'C', 'D', 'E', 'F', 'G', 'H', 'I', 'J', 'K', 'L', 'M', 'N', 'O',
+    'P', 'Q', 'R', 'S', 'T', 'U', 'V', 'W', 'X', 'Y', 'Z', '[', '\\', ']', '^', '_',
+    '`', 'a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'i', 'j', 'k', 'l', 'm', 'n', 'o',
+    'p', 'q', 'r', 's', 't', 'u', 'v', 'w', 'x', 'y', 'z', '{', '|', '}', '~', '⌂',
+    'Ç', 'ü', 'é', 'â', 'ä', 'à', 'å', 'ç', 'ê', 'ë', 'è', 'ï', 'î', 'ì', 'Ä', 'Å',
+    'É', 'æ', 'Æ', 'ô', 'ö', 'ò', 'û', 'ù', 'ÿ', 'Ö', 'Ü', '¢', '£', '¥', '₧', 'ƒ',
+    'á', 'í', 'ó', 'ú', 'ñ', 'Ñ', 'ª', 'º', '¿', '⌐', '¬', '½', '¼', '¡', '«', '»',
+    '░', '▒', '▓', '│', '┤', '╡', '╢', '╖', '╕', '╣', '║', '╗', '╝', '╜', '╛', '┐',
+    '└', '┴', '┬', '├', '─', '┼', '╞', '╟', '╚', '╔', '╩', '╦', '╠', '═', '╬', '╧',
+    '╨', '╤', '╥', '╙', '╘', '╒', '╓', '╫', '╪', '┘', '┌', '█', '▄', '▌', '▐', '▀',
+    'α', 'ß', 'Γ', 'π', 'Σ', 'σ', 'µ', 'τ', 'Φ', 'Θ', 'Ω', 'δ', '∞', 'φ', 'ε', '∩',
+    '≡', '±', '≥', '≤', '⌠', '⌡', '÷', '≈', '°', '∙', '·', '√', 'ⁿ', '²', '■', ' ',
+];
+
+fn cp437_to_char(b: u8) -> char {
+    CP437[b as usize]
+}
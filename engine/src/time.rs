@@ -1,3 +1,5 @@
+use std::time::{Duration, Instant};
+
 use crate::base::Number;
 
 pub struct Timer {
@@ -44,6 +46,16 @@ impl Timer {
     pub fn stop(&mut self) {
         self.next_current = Some(self.period + 1);
     }
+
+    /// Ticks since the last start, or more than the period when stopped.
+    pub fn elapsed(&self) -> Number {
+        self.next_current.unwrap_or(self.current)
+    }
+
+    pub fn set_elapsed(&mut self, elapsed: Number) {
+        self.current = elapsed.clamp(0, self.period + 1);
+        self.next_current = None;
+    }
 }
 
 pub struct BlinkAnimation {
@@ -92,6 +104,21 @@ impl BlinkAnimation {
         self.show
     }
 
+    pub fn elapsed(&self) -> Number {
+        self.timer.elapsed()
+    }
+
+    pub fn changes_remain(&self) -> Number {
+        self.changes_remain
+    }
+
+    pub fn restore(&mut self, elapsed: Number, changes_remain: Number) {
+        self.timer.set_elapsed(elapsed);
+        self.changes_remain = changes_remain.max(0);
+        // every change flips visibility, starting hidden with an even count
+        self.show = self.changes_remain == 0 || self.changes_remain % 2 == 1;
+    }
+
     pub fn is_triggered(&self) -> bool {
         self.changes_remain == 0 && self.timer.is_triggered()
     }
@@ -154,3 +181,129 @@ impl TimeAware for DelayedRepeat {
         self.delay.is_started() || self.repeat.is_started()
     }
 }
+
+pub struct FixedTimestep {
+    tick_period: Duration,
+    max_ticks_per_update: u32,
+    accumulator: Duration,
+    last_update: Instant,
+}
+
+impl FixedTimestep {
+    pub fn new(tick_rate: u32, max_ticks_per_update: u32, now: Instant) -> FixedTimestep {
+        FixedTimestep {
+            tick_period: Duration::from_secs(1) / tick_rate.max(1),
+            max_ticks_per_update: max_ticks_per_update.max(1),
+            accumulator: Duration::ZERO,
+            last_update: now,
+        }
+    }
+
+    /// Returns the number of ticks to simulate since the previous update.
+    /// Time beyond `max_ticks_per_update` ticks is dropped, so the simulation
+    /// slows down instead of spiralling when frames are late.
+    pub fn update(&mut self, now: Instant) -> u32 {
+        self.accumulator += now.saturating_duration_since(self.last_update);
+        self.last_update = now;
+
+        let max_accumulator = self.tick_period * self.max_ticks_per_update;
+        if self.accumulator > max_accumulator {
+            self.accumulator = max_accumulator;
+        }
+
+        let mut ticks = 0;
+        while self.accumulator >= self.tick_period {
+            self.accumulator -= self.tick_period;
+            ticks += 1;
+        }
+        ticks
+    }
+
+    pub fn until_next_tick(&self, now: Instant) -> Duration {
+        let next_tick = self.last_update + (self.tick_period - self.accumulator);
+        next_tick.saturating_duration_since(now)
+    }
+}
+
+pub struct RateLimiter {
+    period: Duration,
+    next: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(rate: u32, now: Instant) -> RateLimiter {
+        RateLimiter {
+            period: Duration::from_secs(1) / rate.max(1),
+            next: now,
+        }
+    }
+
+    pub fn try_acquire(&mut self, now: Instant) -> bool {
+        if now < self.next {
+            return false;
+        }
+
+        // after a pause the next frame is a period away, not due at once
+        self.next += self.period;
+        if self.next <= now {
+            self.next = now + self.period;
+        }
+        true
+    }
+
+    pub fn until_next(&self, now: Instant) -> Duration {
+        self.next.saturating_duration_since(now)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(milliseconds: u64) -> Duration {
+        Duration::from_millis(milliseconds)
+    }
+
+    #[test]
+    fn ticks_from_elapsed_time() {
+        let start = Instant::now();
+        let mut timestep = FixedTimestep::new(100, 10, start);
+        assert_eq!(timestep.update(start), 0);
+        assert_eq!(timestep.update(start + ms(10)), 1);
+        assert_eq!(timestep.update(start + ms(40)), 3);
+    }
+
+    #[test]
+    fn remainder_carries_over() {
+        let start = Instant::now();
+        let mut timestep = FixedTimestep::new(100, 10, start);
+        assert_eq!(timestep.update(start + ms(15)), 1);
+        assert_eq!(timestep.until_next_tick(start + ms(15)), ms(5));
+        assert_eq!(timestep.update(start + ms(19)), 0);
+        assert_eq!(timestep.update(start + ms(20)), 1);
+    }
+
+    #[test]
+    fn late_frames_are_clamped() {
+        let start = Instant::now();
+        let mut timestep = FixedTimestep::new(100, 4, start);
+        assert_eq!(timestep.update(start + ms(1000)), 4);
+        // the dropped time does not come back later
+        assert_eq!(timestep.update(start + ms(1005)), 0);
+        assert_eq!(timestep.update(start + ms(1010)), 1);
+    }
+
+    #[test]
+    fn rate_limiter() {
+        let start = Instant::now();
+        let mut limiter = RateLimiter::new(100, start);
+        assert!(limiter.try_acquire(start));
+        assert!(!limiter.try_acquire(start + ms(5)));
+        assert_eq!(limiter.until_next(start + ms(5)), ms(5));
+        assert!(limiter.try_acquire(start + ms(10)));
+
+        // a long pause does not build up a burst
+        assert!(limiter.try_acquire(start + ms(100)));
+        assert!(!limiter.try_acquire(start + ms(100)));
+    }
+}
@@ -4,8 +4,47 @@ use std::iter;
 use crate::base::Number;
 use crate::geometry::Point;
 
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Color {
+    Default,
+    Rgb(u8, u8, u8),
+}
+
+impl Color {
+    pub const BLACK: Color = Color::Rgb(0, 0, 0);
+    pub const WHITE: Color = Color::Rgb(255, 255, 255);
+    pub const GRAY: Color = Color::Rgb(128, 128, 128);
+    pub const RED: Color = Color::Rgb(230, 40, 40);
+    pub const GREEN: Color = Color::Rgb(75, 213, 13);
+    pub const BLUE: Color = Color::Rgb(40, 80, 240);
+    pub const YELLOW: Color = Color::Rgb(240, 220, 30);
+    pub const CYAN: Color = Color::Rgb(30, 220, 230);
+    pub const MAGENTA: Color = Color::Rgb(180, 50, 220);
+    pub const ORANGE: Color = Color::Rgb(245, 140, 20);
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct Cell {
+    pub glyph: u8,
+    pub fg: Color,
+    pub bg: Color,
+}
+
+impl Cell {
+    pub const EMPTY: Cell = Cell::new(0, Color::Default, Color::Default);
+
+    pub const fn new(glyph: u8, fg: Color, bg: Color) -> Cell {
+        Cell {
+            glyph,
+            fg,
+            bg,
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct ScreenBuffer {
-    chars: Vec<u8>,
+    cells: Vec<Cell>,
     width: usize,
     height: usize,
 }
@@ -13,35 +52,59 @@ pub struct ScreenBuffer {
 impl ScreenBuffer {
     pub fn new(width: usize, height: usize) -> ScreenBuffer {
         ScreenBuffer {
-            chars: vec![0; width * height],
+            cells: vec![Cell::EMPTY; width * height],
             width,
             height,
         }
     }
 
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
     fn index(&self, x: usize, y: usize) -> usize {
         y * self.width + x
     }
 
     pub fn byte_at(&self, x: usize, y: usize) -> u8 {
-        self.chars[self.index(x, y)]
+        self.cells[self.index(x, y)].glyph
+    }
+
+    pub fn cell_at(&self, x: usize, y: usize) -> Cell {
+        self.cells[self.index(x, y)]
     }
 
     pub fn clear(&mut self) {
-        self.chars.fill(0);
+        self.cells.fill(Cell::EMPTY);
     }
 
     pub fn set_byte(&mut self, p: Point, b: u8) {
+        self.set_cell(p, Cell::new(b, Color::Default, Color::Default));
+    }
+
+    pub fn set_colored_byte(&mut self, p: Point, b: u8, fg: Color, bg: Color) {
+        self.set_cell(p, Cell::new(b, fg, bg));
+    }
+
+    pub fn set_cell(&mut self, p: Point, cell: Cell) {
         let Point { x, y } = p;
         if y >= 0 && y < self.height as Number {
             if x >= 0 && x < self.width as Number {
                 let index = self.index(x as usize, y as usize);
-                self.chars[index] = b;
+                self.cells[index] = cell;
             }
         }
     }
 
     pub fn set_bytes(&mut self, p: Point, s: &[u8]) {
+        self.set_colored_bytes(p, s, Color::Default, Color::Default);
+    }
+
+    pub fn set_colored_bytes(&mut self, p: Point, s: &[u8], fg: Color, bg: Color) {
         let Point { x, y } = p;
         if y >= 0 && y < self.height as Number {
             if x < self.width as Number && x + s.len() as Number >= 0 {
@@ -51,7 +114,10 @@ impl ScreenBuffer {
                 let slice_end = clipped_end_x - x;
                 let index = self.index(clipped_start_x as usize, y as usize);
 
-                self.chars[index..(index + (clipped_end_x - clipped_start_x) as usize)].copy_from_slice(&s[slice_start as usize..slice_end as usize]);
+                let cells = &mut self.cells[index..(index + (clipped_end_x - clipped_start_x) as usize)];
+                for (cell, &b) in cells.iter_mut().zip(&s[slice_start as usize..slice_end as usize]) {
+                    *cell = Cell::new(b, fg, bg);
+                }
             }
         }
     }
@@ -61,15 +127,23 @@ pub fn draw_str(buf: &mut ScreenBuffer, p: Point, str: &str) {
     buf.set_bytes(p, str.as_bytes());
 }
 
+pub fn draw_colored_str(buf: &mut ScreenBuffer, p: Point, str: &str, fg: Color, bg: Color) {
+    buf.set_colored_bytes(p, str.as_bytes(), fg, bg);
+}
+
 pub fn draw_rect(buf: &mut ScreenBuffer, p: Point, width: Number, height: Number, chr: char) {
+    draw_colored_rect(buf, p, width, height, chr, Color::Default, Color::Default);
+}
+
+pub fn draw_colored_rect(buf: &mut ScreenBuffer, p: Point, width: Number, height: Number, chr: char, fg: Color, bg: Color) {
     let chr = [chr as u8];
     if width >= 2 && height >= 2 {
         let horizontal_line = iter::repeat(chr[0]).take(width as usize).collect::<Vec<_>>();
-        buf.set_bytes(p, &horizontal_line);
-        buf.set_bytes(p.add_y(height as Number - 1), &horizontal_line);
+        buf.set_colored_bytes(p, &horizontal_line, fg, bg);
+        buf.set_colored_bytes(p.add_y(height as Number - 1), &horizontal_line, fg, bg);
         for j in p.y + 1..p.y + height as Number - 1 {
-            buf.set_bytes(p.with_y(j), &chr);
-            buf.set_bytes(p.with_y(j).add_x(width as Number - 1), &chr);
+            buf.set_colored_bytes(p.with_y(j), &chr, fg, bg);
+            buf.set_colored_bytes(p.with_y(j).add_x(width as Number - 1), &chr, fg, bg);
         }
     }
 }
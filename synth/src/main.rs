@@ -1,12 +1,16 @@
 use engine::{run, RunParams};
 use engine::base::App;
-use engine::input::{Input, Key};
+use engine::input::{Action, Actions, Bindings, Input, Key};
 use engine::video::ScreenBuffer;
 use std::sync::mpsc;
 use sdl2::audio::AudioCallback;
 use std::f32::consts::PI;
 use std::time::Instant;
-use std::cmp::min;
+use std::cmp::{max, min};
+use std::fs;
+
+const MIN_OCTAVE: i32 = -2;
+const MAX_OCTAVE: i32 = 2;
 
 pub trait Sound {
     fn render(&self, tick: i64) -> f32;
@@ -119,7 +123,7 @@ impl Audio {
             major_tick: 0,
             rx,
 
-            oscillators: vec![Sine::new(sample_rate as f32); 12],
+            oscillators: vec![Sine::new(sample_rate as f32); Note::ALL.len()],
         }
     }
 
@@ -137,6 +141,12 @@ impl Audio {
             Note::A => 9,
             Note::Asharp => 10,
             Note::B => 11,
+            Note::HighC => 12,
+            Note::HighCsharp => 13,
+            Note::HighD => 14,
+            Note::HighDsharp => 15,
+            Note::HighE => 16,
+            Note::HighF => 17,
         }
     }
 }
@@ -150,12 +160,12 @@ impl AudioCallback for Audio {
 
         for msg in self.rx.try_iter() {
             match msg {
-                SoundMessage::Key{is_pressed, elapsed_milliseconds, note} => {
+                SoundMessage::Key{is_pressed, elapsed_milliseconds, note, octave} => {
                     let elapsed_ticks = elapsed_milliseconds * self.sample_rate / 1000;
                     let audio_tick = min(next_major_tick - 1, previous_tick.map_or(self.major_tick, |x| x + elapsed_ticks));
 
                     if is_pressed {
-                        self.oscillators[Self::index(note)].start_at(audio_tick, frequency(note));
+                        self.oscillators[Self::index(note)].start_at(audio_tick, frequency(note) * 2f32.powi(octave));
                     } else {
                         self.oscillators[Self::index(note)].stop_at(audio_tick);
                     }
@@ -178,7 +188,7 @@ impl AudioCallback for Audio {
     }
 }
 
-#[derive(Copy, Clone)]
+#[derive(Eq, PartialEq, Hash, Copy, Clone)]
 pub enum Note {
     A,
     Asharp,
@@ -192,6 +202,133 @@ pub enum Note {
     Fsharp,
     G,
     Gsharp,
+    HighC,
+    HighCsharp,
+    HighD,
+    HighDsharp,
+    HighE,
+    HighF,
+}
+
+impl Note {
+    const ALL: [Note; 18] = [
+        Note::C,
+        Note::Csharp,
+        Note::D,
+        Note::Dsharp,
+        Note::E,
+        Note::F,
+        Note::Fsharp,
+        Note::G,
+        Note::Gsharp,
+        Note::A,
+        Note::Asharp,
+        Note::B,
+        Note::HighC,
+        Note::HighCsharp,
+        Note::HighD,
+        Note::HighDsharp,
+        Note::HighE,
+        Note::HighF,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Note::C => "c",
+            Note::Csharp => "c_sharp",
+            Note::D => "d",
+            Note::Dsharp => "d_sharp",
+            Note::E => "e",
+            Note::F => "f",
+            Note::Fsharp => "f_sharp",
+            Note::G => "g",
+            Note::Gsharp => "g_sharp",
+            Note::A => "a",
+            Note::Asharp => "a_sharp",
+            Note::B => "b",
+            Note::HighC => "high_c",
+            Note::HighCsharp => "high_c_sharp",
+            Note::HighD => "high_d",
+            Note::HighDsharp => "high_d_sharp",
+            Note::HighE => "high_e",
+            Note::HighF => "high_f",
+        }
+    }
+}
+
+/// What the keys do: play a note or move the keyboard between octaves.
+#[derive(Eq, PartialEq, Hash, Copy, Clone)]
+pub enum Control {
+    Play(Note),
+    OctaveDown,
+    OctaveUp,
+    /// Plays one octave higher while held.
+    OctaveShift,
+}
+
+impl Action for Control {
+    fn all() -> &'static [Control] {
+        &[
+            Control::Play(Note::C),
+            Control::Play(Note::Csharp),
+            Control::Play(Note::D),
+            Control::Play(Note::Dsharp),
+            Control::Play(Note::E),
+            Control::Play(Note::F),
+            Control::Play(Note::Fsharp),
+            Control::Play(Note::G),
+            Control::Play(Note::Gsharp),
+            Control::Play(Note::A),
+            Control::Play(Note::Asharp),
+            Control::Play(Note::B),
+            Control::Play(Note::HighC),
+            Control::Play(Note::HighCsharp),
+            Control::Play(Note::HighD),
+            Control::Play(Note::HighDsharp),
+            Control::Play(Note::HighE),
+            Control::Play(Note::HighF),
+            Control::OctaveDown,
+            Control::OctaveUp,
+            Control::OctaveShift,
+        ]
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Control::Play(note) => note.name(),
+            Control::OctaveDown => "octave_down",
+            Control::OctaveUp => "octave_up",
+            Control::OctaveShift => "octave_shift",
+        }
+    }
+}
+
+// White keys on the home row, black keys on the row above, like a piano.
+// The home row goes on into the next octave up to the apostrophe. Z and X
+// move the keyboard an octave down and up.
+fn default_bindings() -> Bindings<Control> {
+    Bindings::new()
+        .with(Control::Play(Note::C), &[Key::A])
+        .with(Control::Play(Note::Csharp), &[Key::W])
+        .with(Control::Play(Note::D), &[Key::S])
+        .with(Control::Play(Note::Dsharp), &[Key::E])
+        .with(Control::Play(Note::E), &[Key::D])
+        .with(Control::Play(Note::F), &[Key::F])
+        .with(Control::Play(Note::Fsharp), &[Key::T])
+        .with(Control::Play(Note::G), &[Key::G])
+        .with(Control::Play(Note::Gsharp), &[Key::Y])
+        .with(Control::Play(Note::A), &[Key::H])
+        .with(Control::Play(Note::Asharp), &[Key::U])
+        .with(Control::Play(Note::B), &[Key::J])
+        .with(Control::Play(Note::HighC), &[Key::K])
+        .with(Control::Play(Note::HighCsharp), &[Key::O])
+        .with(Control::Play(Note::HighD), &[Key::L])
+        .with(Control::Play(Note::HighDsharp), &[Key::P])
+        .with(Control::Play(Note::HighE), &[Key::Semicolon])
+        .with(Control::Play(Note::HighF), &[Key::Apostrophe])
+        .with(Control::OctaveDown, &[Key::Z])
+        .with(Control::OctaveUp, &[Key::X])
+        .with(Control::OctaveShift, &[Key::LeftShift, Key::RightShift])
 }
 
 fn frequency(note: Note) -> f32 {
@@ -208,6 +345,12 @@ fn frequency(note: Note) -> f32 {
         Note::A => 440.00,
         Note::Asharp => 466.16,
         Note::B => 493.88,
+        Note::HighC => 523.25,
+        Note::HighCsharp => 554.37,
+        Note::HighD => 587.33,
+        Note::HighDsharp => 622.25,
+        Note::HighE => 659.25,
+        Note::HighF => 698.46,
     }
 }
 
@@ -216,31 +359,40 @@ pub enum SoundMessage {
         is_pressed: bool,
         elapsed_milliseconds: i64,
         note: Note,
+        /// Octaves above or below the note's own, only used when it starts.
+        octave: i32,
     },
 }
 
 struct State {
     tx: mpsc::Sender<SoundMessage>,
     last_sound_instant: Option<Instant>,
+    bindings: Bindings<Control>,
+    controls: Actions<Control>,
+    octave: i32,
 }
 
 impl State {
-    fn new(tx: mpsc::Sender<SoundMessage>) -> State {
+    fn new(tx: mpsc::Sender<SoundMessage>, bindings: Bindings<Control>) -> State {
         State {
             tx,
             last_sound_instant: None,
+            bindings,
+            controls: Actions::new(),
+            octave: 0,
         }
     }
 }
 
 impl State {
-    fn hold_key(&mut self, note: Note) {
+    fn hold_key(&mut self, note: Note, octave: i32) {
         let now = Instant::now();
         let elapsed_milliseconds = self.last_sound_instant.map_or(0, |x| (now - x).as_millis() as i64);
         let _ = self.tx.send(SoundMessage::Key {
             is_pressed: true,
             elapsed_milliseconds,
             note,
+            octave,
         });
         self.last_sound_instant = Some(now);
     }
@@ -252,55 +404,31 @@ impl State {
             is_pressed: false,
             elapsed_milliseconds,
             note,
+            octave: 0,
         });
         self.last_sound_instant = Some(now);
     }
-
-    fn note_by_key(key: Key) -> Option<Note> {
-        match key {
-            Key::A => Some(Note::C),
-            Key::W => Some(Note::Csharp),
-            Key::S => Some(Note::D),
-            Key::E => Some(Note::Dsharp),
-            Key::D => Some(Note::E),
-            Key::F => Some(Note::F),
-            Key::T => Some(Note::Fsharp),
-            Key::G => Some(Note::G),
-            Key::Y => Some(Note::Gsharp),
-            Key::H => Some(Note::A),
-            Key::U => Some(Note::Asharp),
-            Key::J => Some(Note::B),
-            _ => None,
-        }
-    }
 }
 
 impl App for State {
     fn handle_input(&mut self, input: &Input) {
-        static KEYS: [Key; 12] = [
-            Key::A,
-            Key::W,
-            Key::S,
-            Key::E,
-            Key::D,
-            Key::F,
-            Key::T,
-            Key::G,
-            Key::Y,
-            Key::H,
-            Key::U,
-            Key::J,
-        ];
-
-        for key in KEYS.iter().copied() {
-            if let Some(note) = Self::note_by_key(key) {
-                if input.is_front_edge(key) {
-                    self.hold_key(note);
-                }
+        self.controls.update(input, &self.bindings);
 
-                if input.is_back_edge(key) {
-                    self.release_key(note);
-                }
+        if self.controls.is_front_edge(Control::OctaveDown) {
+            self.octave = max(self.octave - 1, MIN_OCTAVE);
+        } else if self.controls.is_front_edge(Control::OctaveUp) {
+            self.octave = min(self.octave + 1, MAX_OCTAVE);
+        }
+        let shift = self.controls.is_pressed(Control::OctaveShift) as i32;
+        let octave = min(self.octave + shift, MAX_OCTAVE);
+
+        for &note in Note::ALL.iter() {
+            if self.controls.is_front_edge(Control::Play(note)) {
+                self.hold_key(note, octave);
+            }
+
+            if self.controls.is_back_edge(Control::Play(note)) {
+                self.release_key(note);
             }
         }
     }
@@ -313,9 +441,19 @@ impl App for State {
 }
 
 fn main() -> Result<(), String> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    // a bindings file lists notes and octave keys with their keys, e.g.
+    // `c_sharp: W Num2` or `octave_up: X`
+    let mut bindings = default_bindings();
+    if let Some(path) = args.iter().position(|arg| arg == "--bindings").and_then(|i| args.get(i + 1)) {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        bindings.apply(&text)?;
+    }
+
     let (tx, rx) = mpsc::channel();
 
-    let mut state = State::new(tx);
+    let mut state = State::new(tx, bindings);
 
     let params = RunParams {
         tileset_path: "assets/tileset_24_24.bmp",
@@ -323,7 +461,79 @@ fn main() -> Result<(), String> {
         scale: 1,
         width_in_tiles: 30,
         height_in_tiles: 30,
+        tick_rate: 125,
+        render_rate: None,
+        vsync: true,
+        max_ticks_per_frame: 10,
     };
 
     run(&mut state, params, move |s| Audio::new(s.freq as i64, rx))
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use engine::base::Backend;
+    use engine::headless::{Headless, HeadlessParams, Script};
+
+    fn play(bindings: Bindings<Control>, script: Script) -> Vec<SoundMessage> {
+        let (tx, rx) = mpsc::channel();
+        let mut state = State::new(tx, bindings);
+        let params = HeadlessParams {
+            width_in_tiles: 30,
+            height_in_tiles: 30,
+            tick_count: 10,
+            ticks_per_frame: 1,
+        };
+        Headless::new(params, script).run(&mut state).unwrap();
+        rx.try_iter().collect()
+    }
+
+    #[test]
+    fn key_plays_note() {
+        let messages = play(default_bindings(), Script::new().press(1, Key::W).release(5, Key::W));
+        assert_eq!(messages.len(), 2);
+        assert!(matches!(messages[0], SoundMessage::Key { is_pressed: true, note: Note::Csharp, octave: 0, .. }));
+        assert!(matches!(messages[1], SoundMessage::Key { is_pressed: false, note: Note::Csharp, .. }));
+    }
+
+    #[test]
+    fn octave_up() {
+        let messages = play(default_bindings(), Script::new().tap(1, Key::X).tap(3, Key::K));
+        assert!(matches!(messages[0], SoundMessage::Key { is_pressed: true, note: Note::HighC, octave: 1, .. }));
+    }
+
+    #[test]
+    fn octave_shift() {
+        let messages = play(default_bindings(), Script::new().press(1, Key::RightShift).tap(3, Key::A));
+        assert!(matches!(messages[0], SoundMessage::Key { is_pressed: true, note: Note::C, octave: 1, .. }));
+    }
+
+    #[test]
+    fn octave_shift_stays_in_range() {
+        let script = Script::new().tap(1, Key::X).tap(3, Key::X).tap(5, Key::X).press(6, Key::LeftShift).tap(7, Key::A);
+        let messages = play(default_bindings(), script);
+        assert!(matches!(messages[0], SoundMessage::Key { is_pressed: true, note: Note::C, octave: MAX_OCTAVE, .. }));
+    }
+
+    #[test]
+    fn octave_keys_rebound() {
+        let mut bindings = default_bindings();
+        bindings.apply("octave_down: Q").unwrap();
+        let messages = play(bindings, Script::new().tap(1, Key::Z).tap(3, Key::Q).tap(5, Key::A));
+        assert!(matches!(messages[0], SoundMessage::Key { is_pressed: true, note: Note::C, octave: -1, .. }));
+    }
+
+    #[test]
+    fn chord() {
+        let messages = play(default_bindings(), Script::new().press(1, Key::A).press(1, Key::D).press(1, Key::G));
+        let notes: Vec<Note> = messages
+            .iter()
+            .map(|message| match message {
+                SoundMessage::Key { note, .. } => *note,
+            })
+            .collect();
+        assert_eq!(notes.len(), 3);
+        assert!(notes.contains(&Note::C) && notes.contains(&Note::E) && notes.contains(&Note::G));
+    }
+}